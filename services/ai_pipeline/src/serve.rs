@@ -1,12 +1,15 @@
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
 
 use burn::backend::Cuda;
 use burn::backend::cuda::CudaDevice;
 use burn::tensor::bf16;
 use futures_util::{SinkExt, StreamExt};
-use tokio::net::TcpListener;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::sync::{mpsc, oneshot};
 use tokio_tungstenite::accept_async;
 use tokio_tungstenite::tungstenite::Message;
@@ -18,10 +21,16 @@ type InferBackend = Cuda<bf16>;
 /// (jpeg/png frame bytes, channel to send JSON result back on)
 type InferRequest = (Vec<u8>, oneshot::Sender<String>);
 
+/// Pipeline states reported by `/readyz`.
+const WEIGHTS_LOADING: u8 = 0;
+const WEIGHTS_READY: u8 = 1;
+const WEIGHTS_FAILED: u8 = 2;
+
 /// Shared handle to the single inference thread that owns the GPU pipeline.
 #[derive(Clone)]
 struct InferHandle {
     tx: mpsc::Sender<InferRequest>,
+    weights: Arc<AtomicU8>,
 }
 
 impl InferHandle {
@@ -29,6 +38,8 @@ impl InferHandle {
         // Bound of 1: if inference is busy, new frames replace the queued one
         // rather than piling up, keeping latency low.
         let (tx, mut rx) = mpsc::channel::<InferRequest>(1);
+        let weights = Arc::new(AtomicU8::new(WEIGHTS_LOADING));
+        let state = Arc::clone(&weights);
 
         std::thread::spawn(move || {
             let init = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
@@ -39,6 +50,7 @@ impl InferHandle {
             let pipeline = match init {
                 Ok(p) => {
                     tracing::info!("inference pipeline ready");
+                    state.store(WEIGHTS_READY, Ordering::SeqCst);
                     p
                 }
                 Err(e) => {
@@ -48,6 +60,7 @@ impl InferHandle {
                         .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
                         .unwrap_or_else(|| "unknown panic".to_string());
                     tracing::error!(error = %msg, "inference pipeline failed to initialize");
+                    state.store(WEIGHTS_FAILED, Ordering::SeqCst);
                     while let Some((_, resp_tx)) = rx.blocking_recv() {
                         let _ = resp_tx.send(format!(
                             r#"{{"error":"pipeline init failed: {}"}}"#,
@@ -77,7 +90,16 @@ impl InferHandle {
             }
         });
 
-        Self { tx }
+        Self { tx, weights }
+    }
+
+    /// Whether the model weights are loaded and the pipeline can take frames.
+    fn weights_status(&self) -> Result<(), &'static str> {
+        match self.weights.load(Ordering::SeqCst) {
+            WEIGHTS_READY => Ok(()),
+            WEIGHTS_LOADING => Err("still loading"),
+            _ => Err("pipeline failed to initialize"),
+        }
     }
 
     /// Submit a frame for inference. Returns None if the inference thread has
@@ -109,17 +131,56 @@ fn decode_and_infer(
 ///
 /// A single inference thread (owning the GPU pipeline) is shared across all connections.
 /// Frames are processed serially; excess frames are silently dropped so latency stays low.
+///
+/// Plain `GET /healthz` and `GET /readyz` on the same port answer the Kubernetes probes.
+/// On SIGTERM `/readyz` fails for `SHUTDOWN_DRAIN_SECS` (default 10) before the server stops,
+/// so the mesh drains traffic first.
 pub async fn serve(addr: &str, head_dir: PathBuf) -> anyhow::Result<()> {
     let listener = TcpListener::bind(addr).await?;
     tracing::info!(addr, "WebSocket server listening");
 
     let handle = Arc::new(InferHandle::new(head_dir));
+    let draining = Arc::new(AtomicBool::new(false));
+
+    let drain = Duration::from_secs(
+        std::env::var("SHUTDOWN_DRAIN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10),
+    );
+    let mut terminate = signal(SignalKind::terminate())?;
+    let shutdown = async {
+        terminate.recv().await;
+        draining.store(true, Ordering::SeqCst);
+        tracing::info!(drain_secs = drain.as_secs(), "received SIGTERM, draining before exit");
+        tokio::time::sleep(drain).await;
+    };
+    tokio::pin!(shutdown);
 
     loop {
-        let (stream, peer) = listener.accept().await?;
-        tracing::info!(%peer, "client connected");
+        let (stream, peer) = tokio::select! {
+            res = listener.accept() => res?,
+            _ = &mut shutdown => {
+                tracing::info!("WebSocket server stopped");
+                return Ok(());
+            }
+        };
         let handle = Arc::clone(&handle);
+        let draining = Arc::clone(&draining);
         tokio::spawn(async move {
+            if let Some(probe) = detect_probe(&stream).await {
+                let answered = tokio::time::timeout(
+                    PROBE_TIMEOUT,
+                    answer_probe(stream, probe, &handle, &draining),
+                )
+                .await;
+                if !matches!(answered, Ok(Ok(()))) {
+                    tracing::debug!(%peer, "probe request failed");
+                }
+                return;
+            }
+
+            tracing::info!(%peer, "client connected");
             if let Err(e) = handle_connection(stream, handle).await {
                 tracing::error!(%peer, error = %e, "connection error");
             }
@@ -128,6 +189,101 @@ pub async fn serve(addr: &str, head_dir: PathBuf) -> anyhow::Result<()> {
     }
 }
 
+#[derive(Clone, Copy)]
+enum Probe {
+    Live,
+    Ready,
+}
+
+/// Request lines of the probes, up to the path.
+const PROBES: [(&[u8], Probe); 2] = [(b"GET /healthz", Probe::Live), (b"GET /readyz", Probe::Ready)];
+
+/// How long a probe client gets to send its request and read the answer.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tells a probe from a WebSocket handshake by peeking at the request line, so a handshake
+/// reaches tungstenite with nothing consumed.
+async fn detect_probe(stream: &TcpStream) -> Option<Probe> {
+    let mut buf = [0u8; 13];
+    // The request line nearly always arrives in one segment; allow a few short waits if not.
+    for _ in 0..20 {
+        let n = stream.peek(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        let seen = &buf[..n];
+        let mut undecided = false;
+        for (prefix, probe) in PROBES {
+            if seen.len() > prefix.len() {
+                if seen.starts_with(prefix) && matches!(seen[prefix.len()], b' ' | b'?') {
+                    return Some(probe);
+                }
+            } else if prefix.starts_with(seen) {
+                undecided = true;
+            }
+        }
+        if !undecided {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    None
+}
+
+/// Answers a probe with a JSON body and closes the connection.
+async fn answer_probe(
+    mut stream: TcpStream,
+    probe: Probe,
+    handle: &InferHandle,
+    draining: &AtomicBool,
+) -> std::io::Result<()> {
+    // Read the whole request head first; closing with it unread would reset the connection.
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < 8192 {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let (ready, body) = match probe {
+        Probe::Live => (true, serde_json::json!({ "status": "ok" })),
+        Probe::Ready if draining.load(Ordering::SeqCst) => (
+            false,
+            serde_json::json!({ "status": "draining", "checks": {} }),
+        ),
+        Probe::Ready => {
+            let started = Instant::now();
+            let weights = handle.weights_status();
+            let mut check = serde_json::json!({
+                "status": if weights.is_ok() { "ok" } else { "fail" },
+                "latency_ms": started.elapsed().as_secs_f64() * 1000.0,
+            });
+            if let Err(e) = weights {
+                check["error"] = e.into();
+            }
+            (
+                weights.is_ok(),
+                serde_json::json!({
+                    "status": if weights.is_ok() { "ok" } else { "fail" },
+                    "checks": { "weights": check },
+                }),
+            )
+        }
+    };
+
+    let body = body.to_string();
+    let status = if ready { "200 OK" } else { "503 Service Unavailable" };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
 async fn handle_connection(
     stream: tokio::net::TcpStream,
     handle: Arc<InferHandle>,
@@ -0,0 +1,27 @@
+-- Random per-user handle sent to authenticators as the WebAuthn user.id. Assigned lazily on the
+-- first passkey registration so it never exposes the numeric users.id.
+ALTER TABLE users ADD COLUMN webauthn_user_id UUID UNIQUE;
+
+CREATE TABLE webauthn_credentials (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    credential_id BYTEA NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- Serialized webauthn_rs::Passkey (public key, sign counter, backup state).
+    passkey JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX webauthn_credentials_user_id_idx ON webauthn_credentials (user_id);
+
+-- Server-side state for an in-flight registration or authentication ceremony. The BFF only ever
+-- holds the id; the challenge itself never leaves auth.
+CREATE TABLE webauthn_ceremonies (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('registration', 'authentication')),
+    -- Set for registration (the user adding a passkey); NULL for discoverable login.
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    state JSONB NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '5 minutes'
+);
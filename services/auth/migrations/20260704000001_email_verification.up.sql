@@ -0,0 +1,13 @@
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+-- Accounts that predate verification are grandfathered in.
+UPDATE users SET email_verified_at = NOW() WHERE email IS NOT NULL;
+
+CREATE TABLE email_verification_tokens (
+    -- SHA-256 of the emailed token; the token itself is never stored.
+    token_hash TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '24 hours'
+);
+
+CREATE INDEX email_verification_tokens_user_id_idx ON email_verification_tokens (user_id);
@@ -0,0 +1,2 @@
+DELETE FROM bff_tokens WHERE revoked_at IS NOT NULL;
+ALTER TABLE bff_tokens DROP COLUMN IF EXISTS revoked_at;
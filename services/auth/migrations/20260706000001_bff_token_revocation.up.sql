@@ -0,0 +1,3 @@
+-- Logged-out tokens are kept (until they would have expired anyway) so the sessions gauge can
+-- tell revoked sessions apart from ones that simply ran out.
+ALTER TABLE bff_tokens ADD COLUMN revoked_at TIMESTAMPTZ;
@@ -0,0 +1,7 @@
+ALTER TABLE bff_tokens
+    DROP COLUMN IF EXISTS id,
+    DROP COLUMN IF EXISTS created_at,
+    DROP COLUMN IF EXISTS last_used_at,
+    DROP COLUMN IF EXISTS user_agent,
+    DROP COLUMN IF EXISTS client_ip,
+    DROP COLUMN IF EXISTS login_method;
@@ -0,0 +1,14 @@
+-- Details shown on the "Your sessions" list. `id` is a handle the browser can refer to a session
+-- by without ever seeing the token itself.
+ALTER TABLE bff_tokens
+    ADD COLUMN id BIGSERIAL UNIQUE,
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN last_used_at TIMESTAMPTZ,
+    ADD COLUMN user_agent TEXT,
+    ADD COLUMN client_ip TEXT,
+    ADD COLUMN login_method TEXT NOT NULL DEFAULT 'unknown';
+
+-- Tokens have always lived for seven days, so this recovers when existing ones were issued.
+UPDATE bff_tokens SET created_at = expires_at - INTERVAL '7 days';
+
+ALTER TABLE bff_tokens ALTER COLUMN login_method DROP DEFAULT;
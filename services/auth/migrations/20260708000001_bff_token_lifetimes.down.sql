@@ -0,0 +1,9 @@
+DELETE FROM bff_tokens WHERE rotated_at IS NOT NULL;
+DROP INDEX IF EXISTS bff_tokens_expires_at_idx;
+DROP INDEX IF EXISTS bff_tokens_rotated_into_idx;
+ALTER TABLE bff_tokens ALTER COLUMN expires_at SET DEFAULT NOW() + INTERVAL '7 days';
+ALTER TABLE bff_tokens
+    DROP COLUMN IF EXISTS rotated_into,
+    DROP COLUMN IF EXISTS rotated_at,
+    DROP COLUMN IF EXISTS issued_at,
+    DROP COLUMN IF EXISTS absolute_expires_at;
@@ -0,0 +1,17 @@
+-- `expires_at` becomes the idle expiry: it slides forward on use, up to `absolute_expires_at`.
+ALTER TABLE bff_tokens
+    ADD COLUMN absolute_expires_at TIMESTAMPTZ,
+    -- When the current token value was issued; drives periodic rotation.
+    ADD COLUMN issued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set on the short-lived alias row kept for a token value that was rotated away, pointing
+    -- at the session it was rotated into.
+    ADD COLUMN rotated_at TIMESTAMPTZ,
+    ADD COLUMN rotated_into BIGINT REFERENCES bff_tokens(id) ON DELETE CASCADE;
+
+UPDATE bff_tokens SET absolute_expires_at = expires_at, issued_at = created_at;
+
+ALTER TABLE bff_tokens ALTER COLUMN absolute_expires_at SET NOT NULL;
+ALTER TABLE bff_tokens ALTER COLUMN expires_at DROP DEFAULT;
+
+CREATE INDEX bff_tokens_rotated_into_idx ON bff_tokens (rotated_into);
+CREATE INDEX bff_tokens_expires_at_idx ON bff_tokens (expires_at);
@@ -0,0 +1,17 @@
+-- Ed25519 keys for the JWTs returned by introspection. Exactly one key is `active` and signs new
+-- tokens; `retiring` keys no longer sign but stay in the JWKS until every JWT they signed has
+-- expired; `retired` keys are kept only for the record.
+CREATE TABLE jwt_signing_keys (
+    kid TEXT PRIMARY KEY,
+    algorithm TEXT NOT NULL DEFAULT 'EdDSA' CHECK (algorithm = 'EdDSA'),
+    -- PKCS#8 v2 DER document holding the private and public key.
+    private_key BYTEA NOT NULL,
+    -- Raw 32-byte Ed25519 public key, published as the JWK `x` parameter.
+    public_key BYTEA NOT NULL,
+    state TEXT NOT NULL DEFAULT 'active' CHECK (state IN ('active', 'retiring', 'retired')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    retiring_at TIMESTAMPTZ,
+    retired_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX jwt_signing_keys_one_active_idx ON jwt_signing_keys ((true)) WHERE state = 'active';
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS oidc_access_tokens;
+DROP TABLE IF EXISTS oidc_authorization_codes;
+DROP TABLE IF EXISTS oidc_authorization_requests;
+DROP TABLE IF EXISTS oauth_clients;
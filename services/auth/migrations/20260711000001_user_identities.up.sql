@@ -0,0 +1,35 @@
+-- Upstream accounts a user can log in with, keyed by the provider's stable account id rather
+-- than a GitHub login (which users can change) or an email address.
+CREATE TABLE user_identities (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Provider name from OAUTH_PROVIDERS, e.g. 'github'.
+    provider TEXT NOT NULL,
+    -- The provider's account id (`sub`, or `id` for GitHub, Google and Discord). Rows backfilled
+    -- below hold 'login:<github login>' or 'email:<address>' instead, until the user's next login
+    -- replaces it with the real id.
+    subject TEXT NOT NULL,
+    email TEXT,
+    linked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
+
+-- Every OAuth-only account so far was matched by GitHub login (no email) or by email address.
+-- The provider is taken from the account's most recent OAuth session where there is one; older
+-- accounts can only have come from GitHub or Google.
+INSERT INTO user_identities (user_id, provider, subject, email, linked_at)
+SELECT
+    u.id,
+    COALESCE(
+        (SELECT t.login_method FROM bff_tokens t
+         WHERE t.user_id = u.id AND t.login_method NOT IN ('password', 'passkey', 'unknown')
+         ORDER BY t.created_at DESC LIMIT 1),
+        CASE WHEN u.email IS NULL THEN 'github' ELSE 'google' END
+    ),
+    CASE WHEN u.email IS NULL THEN 'login:' || u.username ELSE 'email:' || u.email END,
+    u.email,
+    COALESCE(u.created_at, NOW())
+FROM users u
+WHERE u.password IS NULL AND u.access_token IS NOT NULL;
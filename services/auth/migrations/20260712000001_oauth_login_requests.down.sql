@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS oauth_handoff_codes (
+    code TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '5 minutes'
+);
+
+DROP TABLE IF EXISTS oauth_login_requests;
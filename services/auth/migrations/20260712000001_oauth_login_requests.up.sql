@@ -0,0 +1,12 @@
+-- Upstream OAuth logins in flight, keyed by their `state`. Holds the PKCE verifier and, for
+-- OpenID Connect providers, the nonce, so neither ever leaves auth.
+CREATE TABLE oauth_login_requests (
+    state TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    pkce_verifier TEXT NOT NULL,
+    nonce TEXT,
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '10 minutes'
+);
+
+-- Left over from the redirect-based login the BFF replaced; nothing reads or writes it.
+DROP TABLE IF EXISTS oauth_handoff_codes;
@@ -0,0 +1,40 @@
+-- Encrypted tokens cannot be decrypted here; OAuth users get a fresh one on their next login.
+DROP FUNCTION IF EXISTS insert_user(VARCHAR, VARCHAR, VARCHAR);
+
+ALTER TABLE users ADD COLUMN access_token TEXT;
+ALTER TABLE users
+ADD CONSTRAINT users_access_token_max_bytes
+CHECK (octet_length(access_token) <= 2048);
+
+UPDATE users u
+SET access_token = i.legacy_access_token
+FROM user_identities i
+WHERE i.user_id = u.id AND i.legacy_access_token IS NOT NULL;
+
+ALTER TABLE users DROP COLUMN session_key;
+
+ALTER TABLE user_identities
+    DROP COLUMN access_token_ciphertext,
+    DROP COLUMN access_token_data_key,
+    DROP COLUMN access_token_key_id,
+    DROP COLUMN legacy_access_token;
+
+CREATE OR REPLACE FUNCTION insert_user(v_username VARCHAR, v_email VARCHAR, v_password VARCHAR, v_access_token VARCHAR DEFAULT NULL)
+RETURNS TABLE(id BIGINT, username VARCHAR, email VARCHAR, password VARCHAR, access_token VARCHAR) AS $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users AS u WHERE u.username = v_username) THEN
+        RAISE EXCEPTION 'UserAlreadyExists';
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM users AS u WHERE u.email = v_email) THEN
+        RAISE EXCEPTION 'EmailAlreadyInUse';
+    END IF;
+
+    RETURN QUERY
+    INSERT INTO users (username, email, password, access_token)
+    VALUES (v_username, v_email,
+            CASE WHEN v_access_token IS NOT NULL THEN NULL ELSE v_password END,
+            v_access_token)
+    RETURNING users.id AS id, users.username, users.email, users.password AS password, users.access_token AS access_token;
+END;
+$$ LANGUAGE plpgsql;
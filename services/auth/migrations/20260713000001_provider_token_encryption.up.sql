@@ -0,0 +1,47 @@
+-- Upstream access tokens move off `users`, where they sat in plaintext and doubled as the
+-- axum-login session hash, onto the identity they were issued for, envelope-encrypted.
+ALTER TABLE user_identities
+    ADD COLUMN access_token_ciphertext BYTEA,
+    -- The token's data key, sealed under the key-encryption key `access_token_key_id` names.
+    ADD COLUMN access_token_data_key BYTEA,
+    ADD COLUMN access_token_key_id TEXT,
+    -- Plaintext tokens carried over from users.access_token. auth encrypts and clears these on
+    -- startup, before it serves any request.
+    ADD COLUMN legacy_access_token TEXT;
+
+-- Until now every OAuth account had exactly one identity.
+UPDATE user_identities i
+SET legacy_access_token = u.access_token
+FROM users u
+WHERE u.id = i.user_id
+  AND u.access_token IS NOT NULL
+  AND i.id = (SELECT MAX(id) FROM user_identities WHERE user_id = u.id);
+
+-- What invalidates a user's axum-login sessions when it changes. Password accounts keep using
+-- the password hash.
+ALTER TABLE users ADD COLUMN session_key TEXT NOT NULL DEFAULT gen_random_uuid()::text;
+
+DROP FUNCTION IF EXISTS insert_user(VARCHAR, VARCHAR, VARCHAR, VARCHAR);
+
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_access_token_max_bytes;
+ALTER TABLE users DROP COLUMN access_token;
+
+CREATE FUNCTION insert_user(v_username VARCHAR, v_email VARCHAR, v_password VARCHAR)
+RETURNS SETOF users AS $$
+BEGIN
+    -- Check if the username already exists
+    IF EXISTS (SELECT 1 FROM users AS u WHERE u.username = v_username) THEN
+        RAISE EXCEPTION 'UserAlreadyExists';
+    END IF;
+
+    -- Check if the email already exists
+    IF EXISTS (SELECT 1 FROM users AS u WHERE u.email = v_email) THEN
+        RAISE EXCEPTION 'EmailAlreadyInUse';
+    END IF;
+
+    RETURN QUERY
+    INSERT INTO users (username, email, password)
+    VALUES (v_username, v_email, v_password)
+    RETURNING *;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,31 @@
+-- Append-only log of authentication events and RBAC changes. `actor_user_id` is deliberately not
+-- a foreign key so the history survives the actor's account.
+CREATE TABLE audit_events (
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    actor_user_id BIGINT,
+    action TEXT NOT NULL,
+    target TEXT,
+    before JSONB,
+    after JSONB,
+    trace_id TEXT,
+    client_ip TEXT
+);
+
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at DESC);
+CREATE INDEX audit_events_actor_idx ON audit_events (actor_user_id, occurred_at DESC);
+CREATE INDEX audit_events_action_idx ON audit_events (action, occurred_at DESC);
+
+CREATE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_no_update_or_delete
+BEFORE UPDATE OR DELETE ON audit_events
+FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate
+BEFORE TRUNCATE ON audit_events
+FOR EACH STATEMENT EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1,3 @@
+-- Roles can now be created and renamed from the admin panel; names identify them (the `admin`
+-- role is protected by name) so they must be unique like permission names already are.
+ALTER TABLE roles ADD CONSTRAINT roles_name_key UNIQUE (name);
@@ -0,0 +1,4 @@
+-- Disabled accounts keep their row, so the username and email stay taken (a ban), but can't log
+-- in and have no live tokens. Deleting the row instead cascades to everything hanging off it.
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN disabled_reason TEXT;
@@ -0,0 +1,2 @@
+DROP VIEW IF EXISTS role_ancestors;
+DROP TABLE IF EXISTS role_parents;
@@ -0,0 +1,24 @@
+-- A role inherits every permission of its parents, transitively. Cycles are refused when a parent
+-- is added; the view also stops at a role already on the path so a stray cycle can't loop.
+CREATE TABLE role_parents (
+  role_id INT NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+  parent_id INT NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+  PRIMARY KEY (role_id, parent_id),
+  CHECK (role_id <> parent_id)
+);
+
+CREATE INDEX role_parents_parent_id_idx ON role_parents (parent_id);
+
+-- Every role paired with itself and each role it inherits from. `path` names the roles from
+-- `role_id` to `ancestor_id`, both included.
+CREATE VIEW role_ancestors AS
+WITH RECURSIVE chain (role_id, ancestor_id, path) AS (
+    SELECT id, id, ARRAY[name::TEXT] FROM roles
+  UNION ALL
+    SELECT c.role_id, rp.parent_id, c.path || r.name::TEXT
+    FROM chain c
+    JOIN role_parents rp ON rp.role_id = c.ancestor_id
+    JOIN roles r ON r.id = rp.parent_id
+    WHERE NOT r.name::TEXT = ANY (c.path)
+)
+SELECT role_id, ancestor_id, path FROM chain;
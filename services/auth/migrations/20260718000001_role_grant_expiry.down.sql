@@ -0,0 +1,5 @@
+DROP VIEW IF EXISTS active_user_roles;
+DROP INDEX IF EXISTS user_roles_expires_at_idx;
+ALTER TABLE user_roles DROP COLUMN IF EXISTS expires_at;
+ALTER TABLE user_roles DROP COLUMN IF EXISTS granted_at;
+ALTER TABLE user_roles DROP COLUMN IF EXISTS granted_by;
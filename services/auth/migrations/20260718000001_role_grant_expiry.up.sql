@@ -0,0 +1,14 @@
+-- Who granted a role and when, and optionally when the grant lapses. Grants made before this
+-- migration have no record of either. Expired grants are ignored through `active_user_roles` until
+-- the sweeper deletes them.
+ALTER TABLE user_roles ADD COLUMN granted_by BIGINT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE user_roles ADD COLUMN granted_at TIMESTAMPTZ;
+ALTER TABLE user_roles ALTER COLUMN granted_at SET DEFAULT NOW();
+ALTER TABLE user_roles ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX user_roles_expires_at_idx ON user_roles (expires_at) WHERE expires_at IS NOT NULL;
+
+CREATE VIEW active_user_roles AS
+SELECT user_id, role_id, granted_by, granted_at, expires_at
+FROM user_roles
+WHERE expires_at IS NULL OR expires_at > NOW();
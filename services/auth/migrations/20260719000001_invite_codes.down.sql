@@ -0,0 +1,4 @@
+ALTER TABLE oauth_login_requests DROP COLUMN IF EXISTS invite_code;
+DROP TABLE IF EXISTS invite_redemptions;
+DROP TABLE IF EXISTS invite_code_roles;
+DROP TABLE IF EXISTS invite_codes;
@@ -0,0 +1,37 @@
+-- Admin-issued invite codes. Only a hash of the code is kept; `hint` is its last characters so
+-- admins can tell codes apart. `max_uses` NULL means unlimited.
+CREATE TABLE invite_codes (
+    id BIGSERIAL PRIMARY KEY,
+    code_hash TEXT NOT NULL UNIQUE,
+    hint TEXT NOT NULL,
+    note TEXT,
+    created_by BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,
+    max_uses INT CHECK (max_uses > 0),
+    use_count INT NOT NULL DEFAULT 0,
+    -- Only a registration with this email (compared case-insensitively) may redeem the code.
+    email TEXT,
+    revoked_at TIMESTAMPTZ
+);
+
+-- Roles granted to whoever redeems the code.
+CREATE TABLE invite_code_roles (
+    invite_id BIGINT NOT NULL REFERENCES invite_codes(id) ON DELETE CASCADE,
+    role_id INT NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (invite_id, role_id)
+);
+
+CREATE TABLE invite_redemptions (
+    id BIGSERIAL PRIMARY KEY,
+    invite_id BIGINT NOT NULL REFERENCES invite_codes(id) ON DELETE CASCADE,
+    -- Kept when the account is later deleted, so the use still counts.
+    user_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT NOT NULL,
+    redeemed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX invite_redemptions_invite_id_idx ON invite_redemptions (invite_id);
+
+-- The invite code given when an OAuth signup was started, redeemed if the login creates a user.
+ALTER TABLE oauth_login_requests ADD COLUMN invite_code TEXT;
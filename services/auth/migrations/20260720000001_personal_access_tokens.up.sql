@@ -0,0 +1,19 @@
+-- Long-lived tokens users create for scripts and bots. Only a hash of the token is kept;
+-- `prefix` is its first characters so users can tell tokens apart. `scopes` are permission
+-- names; a token grants a permission only while its owner still holds it.
+CREATE TABLE personal_access_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+-- Names identify a user's live tokens; a revoked token's name can be reused.
+CREATE UNIQUE INDEX personal_access_tokens_user_name_idx
+    ON personal_access_tokens (user_id, name) WHERE revoked_at IS NULL;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS device_authorizations;
+ALTER TABLE oauth_clients DROP COLUMN IF EXISTS device_grant;
@@ -0,0 +1,22 @@
+-- Clients allowed to use the device authorization grant (RFC 8628). Those are native apps that
+-- receive a full BFF session, so it is opt-in per client.
+ALTER TABLE oauth_clients ADD COLUMN device_grant BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- A pending device login. The device polls with `device_code` (stored hashed) while the user
+-- enters `user_code` on the frontend's /device page and approves or denies it.
+CREATE TABLE device_authorizations (
+    id BIGSERIAL PRIMARY KEY,
+    device_code_hash TEXT NOT NULL UNIQUE,
+    user_code TEXT NOT NULL UNIQUE,
+    client_id TEXT NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    -- The device that started the flow, shown on the approval page.
+    client_ip TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    poll_interval_secs INT NOT NULL,
+    last_polled_at TIMESTAMPTZ,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    approved_at TIMESTAMPTZ,
+    denied_at TIMESTAMPTZ
+);
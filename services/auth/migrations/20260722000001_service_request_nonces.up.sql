@@ -0,0 +1,9 @@
+-- Nonces of signed BFF requests to /internal, kept until the request's timestamp is outside the
+-- accepted clock skew, so a captured request can't be replayed against any replica.
+CREATE TABLE service_request_nonces (
+    nonce TEXT PRIMARY KEY,
+    key_id TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX service_request_nonces_expires_at_idx ON service_request_nonces (expires_at);
@@ -1,13 +1,34 @@
 pub mod arcane;
+mod access_tokens;
+mod accounts;
+mod audit;
+pub mod config;
 mod core;
+mod device;
+mod email_verification;
+mod health;
+mod identities;
 mod internal;
+mod invites;
+mod jwt_keys;
+mod login_throttle;
+mod mail;
+mod mfa;
+mod oidc;
+mod password_reset;
 pub mod permissions;
 mod protected_route;
+mod provider_tokens;
+mod providers;
+mod roles;
+mod service_auth;
 mod session_store;
+mod sessions;
 pub mod telemetry;
 mod user;
+mod webauthn;
 
-use std::{env, panic};
+use std::panic;
 
 use axum::{Router, routing::get};
 use axum_login::{
@@ -20,58 +41,87 @@ use axum_login::{
 };
 use axum_prometheus::PrometheusMetricLayer;
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
-use oauth2::{AuthUrl, ClientId, ClientSecret, TokenUrl, basic::BasicClient};
 use sqlx::PgPool;
 use tower_sessions_sqlx_store::PostgresStore;
 
-use crate::auth::user::BasicClientSet;
-
 use self::{
+    config::Config,
+    health::Health,
     internal::InternalState,
+    jwt_keys::JwtKeys,
+    mail::Mailer,
+    providers::ProviderRegistry,
     session_store::{handler, shutdown_signal},
     user::Backend,
 };
 
 pub struct Auth {
     db: PgPool,
-    client: BasicClientSet,
-    g_client: BasicClientSet,
+    providers: ProviderRegistry,
+    config: Config,
+}
+
+/// `auth rotate-jwt-key`: adds a new active JWT signing key and retires the current one.
+/// Running replicas pick the new key up within a minute.
+pub async fn rotate_jwt_key(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let db = PgPool::connect(config.database_url.expose()).await?;
+    sqlx::migrate!().run(&db).await?;
+
+    let kid = jwt_keys::rotate(&db).await?;
+    tracing::info!(%kid, "rotated jwt signing key");
+    println!("new jwt signing key: {kid}");
+    Ok(())
+}
+
+/// `auth register-oauth-client <client-id> <name> <redirect-uri>... [--public] [--device]`:
+/// registers (or replaces) an OpenID Connect relying party and prints its client secret.
+/// `--public` registers a client without a secret that relies on PKCE alone. `--device` allows the
+/// device authorization grant, for native apps; such clients need no redirect URI.
+pub async fn register_oauth_client(
+    config: &Config,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let public = args.iter().any(|a| a == "--public");
+    let device_grant = args.iter().any(|a| a == "--device");
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let [client_id, name, redirect_uris @ ..] = positional.as_slice() else {
+        return Err(
+            "usage: auth register-oauth-client <client-id> <name> <redirect-uri>... [--public] [--device]".into(),
+        );
+    };
+    if redirect_uris.is_empty() && !device_grant {
+        return Err("at least one redirect URI is required".into());
+    }
+    let redirect_uris: Vec<String> = redirect_uris.iter().map(|u| u.to_string()).collect();
+
+    let db = PgPool::connect(config.database_url.expose()).await?;
+    sqlx::migrate!().run(&db).await?;
+
+    match oidc::register_client(&db, client_id, name, &redirect_uris, public, device_grant).await? {
+        Some(secret) => println!("client_id: {client_id}\nclient_secret: {secret}"),
+        None => println!("client_id: {client_id} (public client, no secret)"),
+    }
+    Ok(())
+}
+
+/// `auth rewrap-provider-tokens`: re-seals every stored provider token's data key under the first
+/// key in `PROVIDER_TOKEN_KEYS`. Run after putting a new key in front; the old one can be dropped
+/// once this reports nothing left to do.
+pub async fn rewrap_provider_tokens(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let db = PgPool::connect(config.database_url.expose()).await?;
+    sqlx::migrate!().run(&db).await?;
+
+    let rewrapped = provider_tokens::rewrap(&db, &config.provider_token_keys).await?;
+    tracing::info!(rewrapped, "rewrapped provider token data keys");
+    println!("rewrapped {rewrapped} provider token data keys");
+    Ok(())
 }
 
 impl Auth {
-    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let client_id = env::var("CLIENT_ID")
-            .map(ClientId::new)
-            .expect("CLIENT_ID should be provided");
-        let client_secret = env::var("CLIENT_SECRET")
-            .map(ClientSecret::new)
-            .expect("CLIENT_SECRET should be provided");
-
-        let g_client_id = env::var("G_CLIENT_ID")
-            .map(ClientId::new)
-            .expect("G_CLIENT_ID should be provided");
-        let g_client_secret = env::var("G_CLIENT_SECRET")
-            .map(ClientSecret::new)
-            .expect("G_CLIENT_SECRET should be provided");
-
-        let auth_url = AuthUrl::new("https://github.com/login/oauth/authorize".to_string())?;
-        let token_url = TokenUrl::new("https://github.com/login/oauth/access_token".to_string())?;
-
-        let g_auth_url = AuthUrl::new("https://accounts.google.com/o/oauth2/auth".to_string())?;
-        let g_token_url = TokenUrl::new("https://oauth2.googleapis.com/token".to_string())?;
-
-        let client = BasicClient::new(client_id)
-            .set_client_secret(client_secret)
-            .set_auth_uri(auth_url)
-            .set_token_uri(token_url);
-
-        let g_client = BasicClient::new(g_client_id)
-            .set_client_secret(g_client_secret)
-            .set_auth_uri(g_auth_url)
-            .set_token_uri(g_token_url);
-
-        let db_connection = env::var("DATABASE_URL").expect("DATABASE_URL should be provided.");
-        let db = PgPool::connect(&db_connection).await?;
+    pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let providers = ProviderRegistry::load(&config.providers, &config.public_url).await?;
+
+        let db = PgPool::connect(config.database_url.expose()).await?;
 
         let mig_res = sqlx::migrate!().run(&db).await;
         match mig_res {
@@ -79,14 +129,20 @@ impl Auth {
             Err(e) => panic!("Could not apply migrations: {e}"),
         }
 
+        let encrypted = provider_tokens::encrypt_legacy(&db, &config.provider_token_keys).await?;
+        if encrypted > 0 {
+            tracing::info!(encrypted, "encrypted stored provider tokens");
+        }
+
         Ok(Auth {
             db,
-            client,
-            g_client,
+            providers,
+            config,
         })
     }
 
     pub async fn server(self) -> Result<(), Box<dyn std::error::Error>> {
+        let config = self.config;
         let session_store = PostgresStore::new(self.db.clone());
         session_store.migrate().await?;
         let deletion_task = tokio::task::spawn(
@@ -96,6 +152,8 @@ impl Auth {
         );
         tokio::spawn(sync_sessions_gauge(self.db.clone()));
         tokio::spawn(poll_pool_metrics(self.db.clone()));
+        tokio::spawn(roles::sweep_expired_grants(self.db.clone()));
+        tokio::spawn(service_auth::purge_expired_nonces(self.db.clone()));
 
         let session_layer = SessionManagerLayer::new(session_store)
             // Defense-in-depth: even though auth is now cluster-internal, require Secure
@@ -103,19 +161,35 @@ impl Auth {
             .with_secure(!cfg!(debug_assertions))
             .with_same_site(SameSite::Lax)
             .with_name("milesstorm.auth")
-            .with_expiry(Expiry::OnInactivity(Duration::days(7)));
+            .with_expiry(Expiry::OnInactivity(Duration::seconds(config.session_idle_secs)));
 
-        let backend = Backend::new(self.db.clone(), self.client, self.g_client);
+        let backend = Backend::new(
+            self.db.clone(),
+            self.providers,
+            config.provider_token_keys,
+            config.registration_mode,
+            &config.public_url,
+            &config.ark_url,
+        );
         let auth_layer = AuthManagerLayerBuilder::new(backend.clone(), session_layer).build();
 
+        let jwt_keys = JwtKeys::load(self.db.clone(), config.jwt_ttl_secs).await?;
+        jwt_keys.spawn_refresh();
+
         let internal_state = InternalState {
             db: self.db.clone(),
-            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
-            service_secret: env::var("BFF_SERVICE_SECRET").expect("BFF_SERVICE_SECRET must be set"),
+            jwt_keys: jwt_keys.clone(),
+            service_keys: config.service_keys,
             backend,
+            mailer: Mailer::from_config(&config.mail)?,
+            email_policy: config.email_policy,
+            token_lifetimes: config.token_lifetimes,
+            oidc_issuer: config.oidc_issuer,
+            public_url: config.public_url,
         };
 
         let (prometheus_layer, metric_handle) = PrometheusMetricLayer::pair();
+        let health = Health::new(self.db.clone());
 
         let app = Router::new()
             .route(
@@ -123,21 +197,20 @@ impl Auth {
                 get(move || async move { metric_handle.render() }),
             )
             .route("/auth", get(handler))
-            .merge(internal::router(internal_state))
+            .merge(oidc::router(internal_state.clone()))
+            .merge(internal::router(internal_state.clone()))
+            .merge(jwt_keys::router(jwt_keys))
             .merge(protected_route::router())
             .merge(permissions::router())
-            .merge(core::router())
+            .merge(core::router(internal_state))
             .layer(auth_layer)
             .layer(axum::middleware::from_fn(record_trace_id))   // ← now runs after OtelAxumLayer
             .layer(OtelInResponseLayer)
             .layer(OtelAxumLayer::default())
-            .layer(prometheus_layer);
+            .layer(prometheus_layer)
+            .merge(health::router(health.clone()));
 
-        let listener = match tokio::net::TcpListener::bind(format!(
-            "{}:{}",
-            std::env::var("SERVER_IP").unwrap_or("localhost".to_string()),
-            std::env::var("SERVER_PORT").unwrap_or("7070".to_string())
-        ))
+        let listener = match tokio::net::TcpListener::bind((config.server_ip.as_str(), config.server_port))
         .await
         {
             Ok(l) => l,
@@ -146,7 +219,11 @@ impl Auth {
 
         tracing::info!("Listening on: {}", listener.local_addr().unwrap());
         axum::serve(listener, app.into_make_service())
-            .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
+            .with_graceful_shutdown(shutdown_signal(
+                deletion_task.abort_handle(),
+                health,
+                std::time::Duration::from_secs(config.shutdown_drain_secs),
+            ))
             .await?;
 
         deletion_task.await??;
@@ -180,14 +257,29 @@ async fn poll_pool_metrics(db: PgPool) {
 
 async fn sync_sessions_gauge(db: PgPool) {
     loop {
-        let result = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM bff_tokens WHERE expires_at > NOW()",
+        if let Err(e) = sessions::purge_ended(&db).await {
+            tracing::warn!(error = %e, "failed to purge ended sessions");
+        }
+
+        let result = sqlx::query_as::<_, (i64, i64, i64)>(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE revoked_at IS NULL AND expires_at > NOW()),
+                COUNT(*) FILTER (WHERE revoked_at IS NOT NULL),
+                COUNT(*) FILTER (WHERE revoked_at IS NULL AND expires_at <= NOW())
+            FROM bff_tokens
+            WHERE rotated_at IS NULL
+            "#,
         )
         .fetch_one(&db)
         .await;
 
         match result {
-            Ok(count) => metrics::gauge!("auth_sessions_active").set(count as f64),
+            Ok((active, revoked, expired)) => {
+                metrics::gauge!("auth_sessions_active").set(active as f64);
+                metrics::gauge!("auth_sessions_ended", "reason" => "revoked").set(revoked as f64);
+                metrics::gauge!("auth_sessions_ended", "reason" => "expired").set(expired as f64);
+            }
             Err(e) => tracing::warn!(error = %e, "failed to sync sessions gauge"),
         }
 
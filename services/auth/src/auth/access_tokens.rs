@@ -0,0 +1,322 @@
+//! Personal access tokens: long-lived bearer tokens users create for scripts and bots.
+//!
+//! A token carries scopes, which are permission names its owner held when creating it. It grants a
+//! scope only while the owner still holds that permission and their account is enabled, so taking
+//! a role away or disabling the account takes effect on tokens immediately. Tokens are shown to
+//! the user once; only their hash is stored, next to a short prefix for telling them apart.
+//!
+//! The BFF's public API passes a token wherever it would pass a BFF token; endpoints that accept
+//! them check it with [`authorize`].
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+
+use super::audit::{self, Event};
+use super::email_verification::UnverifiedPolicy;
+use super::internal::{InternalState, effective_permissions, resolve_token_user};
+use super::password_reset::{generate_token, hash_token};
+
+/// Every personal access token starts with this, so it can't be mistaken for a BFF token.
+pub(super) const TOKEN_PREFIX: &str = "pat_";
+/// Characters of the token kept in the clear to identify it in lists.
+const SHOWN_PREFIX_LEN: usize = 12;
+const MAX_NAME_LEN: usize = 64;
+const MAX_LIVE_TOKENS: i64 = 25;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/access_tokens/list", post(list_tokens))
+        .route("/internal/access_tokens/create", post(create_token))
+        .route("/internal/access_tokens/revoke", post(revoke_token))
+}
+
+/// The owner of `token` if it is a live personal access token with `scope`, and the owner holds
+/// that permission. Counts as a use of the token.
+pub(super) async fn authorize(
+    db: &PgPool,
+    token: &str,
+    scope: &str,
+    policy: UnverifiedPolicy,
+) -> Option<i64> {
+    sqlx::query_scalar(
+        r#"
+        UPDATE personal_access_tokens t SET last_used_at = NOW()
+        FROM users u
+        WHERE t.token_hash = $1 AND u.id = t.user_id
+          AND t.revoked_at IS NULL AND (t.expires_at IS NULL OR t.expires_at > NOW())
+          AND $2 = ANY(t.scopes)
+          AND u.disabled_at IS NULL
+          AND (NOT $3 OR u.email IS NULL OR u.email_verified_at IS NOT NULL)
+          AND EXISTS (
+              SELECT 1 FROM active_user_roles ur
+              JOIN role_ancestors ra ON ra.role_id = ur.role_id
+              JOIN role_permissions rp ON rp.role_id = ra.ancestor_id
+              JOIN permissions p ON p.id = rp.permission_id
+              WHERE ur.user_id = t.user_id AND p.name = $2
+          )
+        RETURNING t.user_id
+        "#,
+    )
+    .bind(hash_token(token))
+    .bind(scope)
+    .bind(policy.hides_permissions())
+    .fetch_optional(db)
+    .await
+    .unwrap_or_else(|e| {
+        tracing::error!(error = %e, "access token lookup failed");
+        None
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+enum AccessTokenError {
+    #[error("The name must be 1 to 64 characters")]
+    InvalidName,
+
+    #[error("Choose at least one scope")]
+    NoScopes,
+
+    #[error("You don't have the {0} permission")]
+    ScopeNotHeld(String),
+
+    #[error("The expiry must be between 1 and 365 days")]
+    InvalidExpiry,
+
+    #[error("You already have a token with this name")]
+    NameTaken,
+
+    #[error("You can have at most 25 tokens; revoke one first")]
+    TooMany,
+
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+fn error_response(e: AccessTokenError, context: &str) -> Response {
+    match e {
+        AccessTokenError::Db(e) => {
+            tracing::error!(error = %e, "{context}: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        AccessTokenError::NameTaken | AccessTokenError::TooMany => {
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        e => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct TokenResp {
+    id: i64,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct TokenReq {
+    token: String,
+}
+
+/// The caller's live tokens, newest first.
+#[tracing::instrument(name = "access_tokens.list", skip_all)]
+async fn list_tokens(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let rows: Result<Vec<TokenResp>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT id, name, prefix, scopes, created_at, expires_at, last_used_at
+        FROM personal_access_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(user.user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match rows {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "list_tokens: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateTokenReq {
+    token: String,
+    name: String,
+    scopes: Vec<String>,
+    /// Unset for a token that doesn't expire.
+    expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct CreatedTokenResp {
+    /// The token itself. It can't be retrieved again.
+    token: String,
+    #[serde(flatten)]
+    info: TokenResp,
+}
+
+#[tracing::instrument(name = "access_tokens.create", skip_all)]
+async fn create_token(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateTokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let event = Event::new("access_token.create", &headers).actor(user.user_id);
+    let result = async {
+        let name = req.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(AccessTokenError::InvalidName);
+        }
+        let mut scopes = req.scopes;
+        scopes.sort();
+        scopes.dedup();
+        if scopes.is_empty() {
+            return Err(AccessTokenError::NoScopes);
+        }
+        let (held, _) = effective_permissions(&state.db, state.email_policy, user.user_id).await?;
+        if let Some(missing) = scopes.iter().find(|s| !held.contains(s)) {
+            return Err(AccessTokenError::ScopeNotHeld(missing.clone()));
+        }
+        let expires_at = match req.expires_in_days {
+            None => None,
+            Some(days @ 1..=MAX_EXPIRY_DAYS) => Some(Utc::now() + chrono::Duration::days(days)),
+            Some(_) => return Err(AccessTokenError::InvalidExpiry),
+        };
+
+        let mut tx = state.db.begin().await?;
+        // Serializes creations by the same user, so the limit holds.
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user.user_id)
+            .execute(&mut *tx)
+            .await?;
+        let live: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM personal_access_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(user.user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if live >= MAX_LIVE_TOKENS {
+            return Err(AccessTokenError::TooMany);
+        }
+
+        let token = format!("{TOKEN_PREFIX}{}", generate_token());
+        let info: TokenResp = sqlx::query_as(
+            r#"
+            INSERT INTO personal_access_tokens (user_id, name, token_hash, prefix, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, prefix, scopes, created_at, expires_at, last_used_at
+            "#,
+        )
+        .bind(user.user_id)
+        .bind(&name)
+        .bind(hash_token(&token))
+        .bind(&token[..SHOWN_PREFIX_LEN])
+        .bind(&scopes)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => AccessTokenError::NameTaken,
+            e => AccessTokenError::Db(e),
+        })?;
+
+        let event = event
+            .target(format!("access token {} (#{})", info.name, info.id))
+            .after(json!({ "scopes": info.scopes, "expires_at": info.expires_at }));
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(CreatedTokenResp { token, info })
+    }
+    .await;
+
+    match result {
+        Ok(created) => {
+            tracing::info!(user_id = user.user_id, token_id = created.info.id, "access token created");
+            (StatusCode::CREATED, Json(created)).into_response()
+        }
+        Err(e) => error_response(e, "create_token"),
+    }
+}
+
+#[derive(Deserialize)]
+struct RevokeTokenReq {
+    token: String,
+    id: i64,
+}
+
+/// Revokes one of the caller's own tokens by id.
+#[tracing::instrument(name = "access_tokens.revoke", skip_all)]
+async fn revoke_token(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<RevokeTokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let event = Event::new("access_token.revoke", &headers).actor(user.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let name: Option<String> = sqlx::query_scalar(
+            r#"
+            UPDATE personal_access_tokens SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            RETURNING name
+            "#,
+        )
+        .bind(req.id)
+        .bind(user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(name) = &name {
+            audit::record(&mut *tx, event.target(format!("access token {name} (#{})", req.id))).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(name.is_some())
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            tracing::info!(user_id = user.user_id, token_id = req.id, "access token revoked");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Token not found").into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "revoke_token: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
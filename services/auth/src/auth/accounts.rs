@@ -0,0 +1,265 @@
+//! Admin account state: disabling (banning), re-enabling and deleting users.
+//!
+//! A disabled user keeps their row, so their username and email stay taken, but every login path
+//! refuses them and every token they hold is revoked in the same transaction that disables them.
+//! Deleting removes the row and everything hanging off it by `ON DELETE CASCADE`. Neither may be
+//! done to your own account or to the last user able to manage permissions.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::PgConnection;
+
+use super::audit::{self, AdminActor, Event};
+use super::internal::InternalState;
+use super::roles::{self, RbacError};
+use super::sessions;
+
+const MAX_REASON_LEN: usize = 500;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/admin/users/{user_id}", delete(delete_user))
+        .route("/internal/admin/users/{user_id}/disable", post(disable_user))
+        .route("/internal/admin/users/{user_id}/enable", post(enable_user))
+}
+
+#[derive(Debug, thiserror::Error)]
+enum AccountError {
+    #[error("You cannot disable or delete your own account")]
+    OwnAccount,
+
+    #[error("The reason must be at most 500 characters")]
+    ReasonTooLong,
+
+    #[error("Not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Rbac(#[from] RbacError),
+
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+fn error_response(e: AccountError, context: &str) -> Response {
+    match e {
+        AccountError::NotFound => StatusCode::NOT_FOUND.into_response(),
+        AccountError::ReasonTooLong => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        AccountError::OwnAccount => (StatusCode::CONFLICT, e.to_string()).into_response(),
+        AccountError::Rbac(e) => roles::error_response(e, context),
+        AccountError::Db(e) => roles::error_response(RbacError::Db(e), context),
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AccountRow {
+    username: String,
+    email: Option<String>,
+    disabled_at: Option<DateTime<Utc>>,
+    disabled_reason: Option<String>,
+}
+
+impl AccountRow {
+    fn snapshot(&self) -> Value {
+        json!({
+            "email": self.email,
+            "disabled_at": self.disabled_at,
+            "disabled_reason": self.disabled_reason,
+        })
+    }
+}
+
+/// Locks the RBAC invariants and the user's row, refusing to act on the caller's own account.
+async fn lock_account(
+    conn: &mut PgConnection,
+    actor: &AdminActor,
+    user_id: i64,
+) -> Result<AccountRow, AccountError> {
+    if actor.user_id == user_id {
+        return Err(AccountError::OwnAccount);
+    }
+    roles::lock_rbac_changes(conn).await?;
+    sqlx::query_as(
+        "SELECT username, email, disabled_at, disabled_reason FROM users WHERE id = $1 FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(AccountError::NotFound)
+}
+
+#[derive(Deserialize)]
+struct DisableReq {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Disables an account and revokes its BFF tokens, personal access tokens, OIDC tokens and codes,
+/// device logins it approved, and pending MFA challenges. Disabling an already disabled account only updates the reason.
+#[tracing::instrument(name = "admin.disable_user", skip_all, fields(user_id))]
+async fn disable_user(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(user_id): Path<i64>,
+    Json(req): Json<DisableReq>,
+) -> impl IntoResponse {
+    let event = Event::new("user.disable", &headers).actor(actor.user_id);
+    let result = async {
+        let reason = req
+            .reason
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        if reason.as_ref().is_some_and(|r| r.chars().count() > MAX_REASON_LEN) {
+            return Err(AccountError::ReasonTooLong);
+        }
+
+        let mut tx = state.db.begin().await?;
+        let before = lock_account(&mut tx, &actor, user_id).await?;
+        let after: AccountRow = sqlx::query_as(
+            r#"
+            UPDATE users SET disabled_at = COALESCE(disabled_at, NOW()), disabled_reason = $2
+            WHERE id = $1
+            RETURNING username, email, disabled_at, disabled_reason
+            "#,
+        )
+        .bind(user_id)
+        .bind(&reason)
+        .fetch_one(&mut *tx)
+        .await?;
+        roles::ensure_permission_admin_remains(&mut tx).await?;
+
+        let revoked = sessions::revoke_all_for_user(&mut *tx, user_id).await?;
+        for table in [
+            "oidc_access_tokens",
+            "oidc_authorization_codes",
+            "device_authorizations",
+            "mfa_challenges",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE user_id = $1"))
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "UPDATE personal_access_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let event = event
+            .target(format!("user {} (#{user_id})", before.username))
+            .before(before.snapshot())
+            .after(after.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(revoked)
+    }
+    .await;
+
+    match result {
+        Ok(revoked) => {
+            tracing::info!(actor = actor.user_id, user_id, revoked, "disabled user");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "disable_user"),
+    }
+}
+
+#[tracing::instrument(name = "admin.enable_user", skip_all, fields(user_id))]
+async fn enable_user(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let event = Event::new("user.enable", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let before = lock_account(&mut tx, &actor, user_id).await?;
+        if before.disabled_at.is_none() {
+            return Ok(());
+        }
+        let after: AccountRow = sqlx::query_as(
+            r#"
+            UPDATE users SET disabled_at = NULL, disabled_reason = NULL
+            WHERE id = $1
+            RETURNING username, email, disabled_at, disabled_reason
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let event = event
+            .target(format!("user {} (#{user_id})", before.username))
+            .before(before.snapshot())
+            .after(after.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, user_id, "enabled user");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "enable_user"),
+    }
+}
+
+/// Deletes an account outright. Its tokens, roles, credentials and identities go with it; audit
+/// events naming it are kept.
+#[tracing::instrument(name = "admin.delete_user", skip_all, fields(user_id))]
+async fn delete_user(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let event = Event::new("user.delete", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let before = lock_account(&mut tx, &actor, user_id).await?;
+        let roles: Vec<String> = sqlx::query_scalar(
+            "SELECT r.name FROM user_roles ur JOIN roles r ON r.id = ur.role_id WHERE ur.user_id = $1 ORDER BY r.name",
+        )
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        roles::ensure_permission_admin_remains(&mut tx).await?;
+
+        let mut snapshot = before.snapshot();
+        snapshot["roles"] = json!(roles);
+        let event = event
+            .target(format!("user {} (#{user_id})", before.username))
+            .before(snapshot);
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, user_id, "deleted user");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "delete_user"),
+    }
+}
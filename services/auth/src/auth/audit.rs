@@ -0,0 +1,253 @@
+//! Append-only audit log of logins, registrations, ark commands and RBAC changes.
+//!
+//! The BFF reaches `/internal/*` with the shared service secret, so on admin calls it also forwards
+//! the acting user's opaque token in `x-acting-user-token`. [`AdminActor`] resolves it, checks the
+//! user still holds `manage_permissions`, and is what admin handlers record as the actor. RBAC
+//! changes write their event in the same transaction as the change; the rest are best effort and
+//! only logged when the insert fails. The table refuses updates and deletes.
+
+use axum::{
+    Json, Router,
+    extract::{FromRequestParts, Query, State},
+    http::{HeaderMap, StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use opentelemetry::trace::TraceContextExt as _;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use super::internal::{InternalState, PagedResp, effective_permissions, resolve_token_user};
+use super::login_throttle;
+
+/// Header the BFF puts the acting user's opaque token in on admin calls.
+pub(super) const ACTOR_HEADER: &str = "x-acting-user-token";
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new().route("/internal/admin/audit", get(list_events))
+}
+
+/// One audit entry, built where the action happens and written with [`record`] or [`log`].
+pub(super) struct Event {
+    action: &'static str,
+    actor_user_id: Option<i64>,
+    target: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+    trace_id: Option<String>,
+    client_ip: Option<String>,
+}
+
+impl Event {
+    /// Starts an event for `action`, taking the client IP the BFF forwarded and the current trace.
+    pub(super) fn new(action: &'static str, headers: &HeaderMap) -> Self {
+        let cx = tracing::Span::current().context();
+        let span = cx.span();
+        let span_context = span.span_context();
+        Self {
+            action,
+            actor_user_id: None,
+            target: None,
+            before: None,
+            after: None,
+            trace_id: span_context
+                .is_valid()
+                .then(|| span_context.trace_id().to_string()),
+            client_ip: login_throttle::forwarded_client_ip(headers),
+        }
+    }
+
+    pub(super) fn actor(mut self, user_id: i64) -> Self {
+        self.actor_user_id = Some(user_id);
+        self
+    }
+
+    pub(super) fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub(super) fn before(mut self, before: Value) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub(super) fn after(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+}
+
+/// Writes `event`, inside the caller's transaction when given one.
+pub(super) async fn record<'e>(db: impl PgExecutor<'e>, event: Event) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_events (actor_user_id, action, target, before, after, trace_id, client_ip)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(event.actor_user_id)
+    .bind(event.action)
+    .bind(&event.target)
+    .bind(&event.before)
+    .bind(&event.after)
+    .bind(&event.trace_id)
+    .bind(&event.client_ip)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Writes `event` without failing the request it belongs to.
+pub(super) async fn log(db: &PgPool, event: Event) {
+    let action = event.action;
+    if let Err(e) = record(db, event).await {
+        tracing::error!(action, error = %e, "failed to record audit event");
+    }
+}
+
+/// The admin an `/internal/admin/*` call is made on behalf of.
+pub(super) struct AdminActor {
+    pub user_id: i64,
+}
+
+impl FromRequestParts<InternalState> for AdminActor {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &InternalState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(token) = parts
+            .headers
+            .get(ACTOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return Err((StatusCode::UNAUTHORIZED, "Missing acting user token").into_response());
+        };
+        let Some(user) = resolve_token_user(&state.db, token).await else {
+            return Err((StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response());
+        };
+
+        match effective_permissions(&state.db, state.email_policy, user.user_id).await {
+            Ok((permissions, _)) if permissions.iter().any(|p| p == "manage_permissions") => {
+                Ok(Self {
+                    user_id: user.user_id,
+                })
+            }
+            Ok(_) => {
+                tracing::warn!(
+                    user_id = user.user_id,
+                    "admin call denied: missing manage_permissions"
+                );
+                Err(StatusCode::FORBIDDEN.into_response())
+            }
+            Err(e) => {
+                tracing::error!(user_id = user.user_id, error = %e, "admin actor: db error");
+                Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+            }
+        }
+    }
+}
+
+// ---- Admin listing ----
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    #[serde(default)]
+    page: u32,
+    #[serde(default = "default_limit")]
+    limit: u32,
+    /// Matches actions starting with this, so `rbac.` selects every RBAC change.
+    #[serde(default)]
+    action: String,
+    /// Substring of the actor's username.
+    #[serde(default)]
+    actor: String,
+    /// Substring of the target.
+    #[serde(default)]
+    target: String,
+}
+
+fn default_limit() -> u32 {
+    50
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct AuditEventResp {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    actor_user_id: Option<i64>,
+    actor_username: Option<String>,
+    action: String,
+    target: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+    trace_id: Option<String>,
+    client_ip: Option<String>,
+}
+
+/// Newest first. The filters are combined with AND; empty ones match everything.
+#[tracing::instrument(name = "admin.list_audit_events", skip_all)]
+async fn list_events(
+    State(state): State<InternalState>,
+    _actor: AdminActor,
+    Query(q): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let limit = q.limit.clamp(1, 200) as i64;
+    let offset = q.page as i64 * limit;
+    let action = format!("{}%", q.action);
+    let actor = format!("%{}%", q.actor.to_lowercase());
+    let target = format!("%{}%", q.target.to_lowercase());
+
+    const FILTER: &str = r#"
+        FROM audit_events e
+        LEFT JOIN users u ON u.id = e.actor_user_id
+        WHERE e.action LIKE $1
+          AND ($2 = '%%' OR LOWER(u.username) LIKE $2)
+          AND ($3 = '%%' OR LOWER(e.target) LIKE $3)
+    "#;
+
+    let total: i64 = match sqlx::query_scalar(&format!("SELECT COUNT(*) {FILTER}"))
+        .bind(&action)
+        .bind(&actor)
+        .bind(&target)
+        .fetch_one(&state.db)
+        .await
+    {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!(error = %e, "list_audit_events: count error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let items: Vec<AuditEventResp> = match sqlx::query_as(&format!(
+        r#"
+        SELECT e.id, e.occurred_at, e.actor_user_id, u.username AS actor_username, e.action,
+               e.target, e.before, e.after, e.trace_id, e.client_ip
+        {FILTER}
+        ORDER BY e.occurred_at DESC, e.id DESC
+        LIMIT $4 OFFSET $5
+        "#
+    ))
+    .bind(&action)
+    .bind(&actor)
+    .bind(&target)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = %e, "list_audit_events: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    Json(PagedResp { items, total }).into_response()
+}
@@ -0,0 +1,412 @@
+//! Settings for the auth service, read once at startup.
+//!
+//! Every setting is named by its environment variable. A TOML file, given with `--config <path>`
+//! or `AUTH_CONFIG_FILE`, can hold the same settings as top-level keys with the lower-cased name
+//! (`database_url = "postgres://…"`, `oauth_github_client_id = "…"`); where both set one, the
+//! environment wins. Everything is checked before the service starts and every problem is
+//! reported at once. Secrets print as `[redacted]`, so the `Debug` output is what
+//! `auth --print-config` shows.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+    str::FromStr,
+};
+
+use lettre::message::Mailbox;
+use oauth2::url::Url;
+use tracing_subscriber::EnvFilter;
+
+use super::{
+    email_verification::UnverifiedPolicy,
+    invites::RegistrationMode,
+    mail::{self, MailTransport},
+    provider_tokens::TokenKeys,
+    providers::{self, ProviderSettings},
+    service_auth::ServiceKeys,
+    sessions::TokenLifetimes,
+    webauthn,
+};
+
+/// A setting that must not be logged. Its `Debug` output is `[redacted]`.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+/// Every problem found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigErrors(Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+#[derive(Debug)]
+pub struct Config {
+    pub database_url: Secret,
+    pub server_ip: String,
+    pub server_port: u16,
+    /// Public URL of the frontend, from `BFF_CALLBACK_URL`: the base of OAuth redirect URIs and
+    /// email links, and the WebAuthn origin.
+    pub public_url: String,
+    /// OpenID Connect issuer identifier, defaulting to `public_url`. The gateway routes the
+    /// `/oauth2/*` endpoints below it to auth.
+    pub oidc_issuer: String,
+    /// Base URL of the agent that starts and stops the Ark and Valheim servers.
+    pub ark_url: String,
+    /// Lifetime of the JWTs returned by introspection.
+    pub jwt_ttl_secs: u64,
+    /// Inactivity after which a session on auth's own login form ends.
+    pub session_idle_secs: i64,
+    pub token_lifetimes: TokenLifetimes,
+    pub registration_mode: RegistrationMode,
+    pub email_policy: UnverifiedPolicy,
+    pub mail: MailTransport,
+    pub providers: Vec<ProviderSettings>,
+    pub provider_token_keys: TokenKeys,
+    pub service_keys: ServiceKeys,
+    /// How long readiness fails after SIGTERM before the listener closes.
+    pub shutdown_drain_secs: u64,
+    /// Where to export traces and logs over OTLP. Nothing is exported when unset.
+    pub otel_endpoint: Option<String>,
+    /// `tracing` filter directives, from `RUST_LOG`.
+    pub log_filter: String,
+}
+
+impl Config {
+    /// Reads the configuration from the environment and `file`, falling back to
+    /// `AUTH_CONFIG_FILE` when no file is given.
+    pub fn load(file: Option<&str>) -> Result<Self, ConfigErrors> {
+        let file = file.map(str::to_string).or_else(|| env::var("AUTH_CONFIG_FILE").ok());
+        let mut l = Loader::new(file.as_deref());
+
+        let database_url = l.required("DATABASE_URL").map(Secret);
+        let server_ip = l.string("SERVER_IP", "localhost");
+        let server_port = l.parse("SERVER_PORT", 7070);
+
+        let public_url = l.url("BFF_CALLBACK_URL", "http://localhost:8080");
+        if webauthn::relying_party(&public_url).is_err() {
+            l.error(format!("BFF_CALLBACK_URL {public_url:?} is not a valid WebAuthn origin"));
+        }
+        let oidc_issuer = l
+            .get("OIDC_ISSUER")
+            .unwrap_or_else(|| public_url.clone())
+            .trim_end_matches('/')
+            .to_string();
+        let ark_url = l
+            .url("ARK_HOST_URL", "http://192.168.1.21:9090")
+            .trim_end_matches('/')
+            .to_string();
+
+        let jwt_ttl_secs = l.secs("JWT_TTL_SECS", 900);
+        if jwt_ttl_secs > 24 * 3600 {
+            l.error("JWT_TTL_SECS must be at most a day");
+        }
+        let session_idle_secs: i64 = l.secs("AUTH_SESSION_IDLE_SECS", 7 * 24 * 3600);
+
+        let token_lifetimes = TokenLifetimes {
+            absolute: l.secs("BFF_TOKEN_ABSOLUTE_TTL_SECS", 30 * 24 * 3600),
+            idle: l.secs("BFF_TOKEN_IDLE_TTL_SECS", 7 * 24 * 3600),
+            rotate_after: l.secs("BFF_TOKEN_ROTATE_AFTER_SECS", 3600),
+            rotation_grace: l.secs("BFF_TOKEN_ROTATION_GRACE_SECS", 60),
+        };
+        if token_lifetimes.idle > token_lifetimes.absolute {
+            l.error("BFF_TOKEN_IDLE_TTL_SECS must not exceed BFF_TOKEN_ABSOLUTE_TTL_SECS");
+        }
+
+        let registration_mode = l.parse("REGISTRATION_MODE", RegistrationMode::Open);
+        let email_policy = l.parse("UNVERIFIED_EMAIL_POLICY", UnverifiedPolicy::NoPermissions);
+        let mail = load_mail(&mut l);
+        let providers = load_providers(&mut l);
+
+        let provider_token_keys = l
+            .required("PROVIDER_TOKEN_KEYS")
+            .and_then(|raw| TokenKeys::parse(&raw).map_err(|e| l.error(e.to_string())).ok());
+        let service_keys = {
+            let keys = l.get("BFF_SERVICE_KEYS");
+            let legacy_secret = l.get("BFF_SERVICE_SECRET");
+            ServiceKeys::parse(keys.as_deref(), legacy_secret.as_deref())
+                .map_err(|e| l.error(e.to_string()))
+                .ok()
+        };
+
+        let shutdown_drain_secs = l.parse("SHUTDOWN_DRAIN_SECS", 10);
+        let otel_endpoint = l.get("OTEL_EXPORTER_OTLP_ENDPOINT");
+        let log_filter = l.string("RUST_LOG", "info,sqlx=warn,tower_sessions=warn");
+        if let Err(e) = EnvFilter::try_new(&log_filter) {
+            l.error(format!("RUST_LOG is invalid: {e}"));
+        }
+
+        let errors = l.finish();
+        match (database_url, provider_token_keys, service_keys) {
+            (Some(database_url), Some(provider_token_keys), Some(service_keys)) if errors.is_empty() => {
+                Ok(Self {
+                    database_url,
+                    server_ip,
+                    server_port,
+                    public_url,
+                    oidc_issuer,
+                    ark_url,
+                    jwt_ttl_secs,
+                    session_idle_secs,
+                    token_lifetimes,
+                    registration_mode,
+                    email_policy,
+                    mail,
+                    providers,
+                    provider_token_keys,
+                    service_keys,
+                    shutdown_drain_secs,
+                    otel_endpoint,
+                    log_filter,
+                })
+            }
+            _ => Err(ConfigErrors(errors)),
+        }
+    }
+}
+
+fn load_mail(l: &mut Loader) -> MailTransport {
+    match l.string("MAIL_TRANSPORT", "stdout").as_str() {
+        "stdout" => MailTransport::Stdout,
+        "file" => MailTransport::File {
+            dir: l.string("MAIL_DIR", "mail"),
+        },
+        "smtp" => {
+            let url = l.required("SMTP_URL").map(Secret);
+            let default_from: Mailbox = mail::DEFAULT_FROM.parse().expect("DEFAULT_FROM is a valid mailbox");
+            let from = l.parse("MAIL_FROM", default_from);
+            match url {
+                Some(url) => MailTransport::Smtp { url, from },
+                None => MailTransport::Stdout,
+            }
+        }
+        other => {
+            l.error(format!("MAIL_TRANSPORT must be smtp, file or stdout (got {other:?})"));
+            MailTransport::Stdout
+        }
+    }
+}
+
+fn load_providers(l: &mut Loader) -> Vec<ProviderSettings> {
+    let names = l.string("OAUTH_PROVIDERS", "github,google");
+    let mut settings = Vec::new();
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            l.error(format!(
+                "OAUTH_PROVIDERS: provider name {name:?} may only contain lowercase letters, digits and '-'"
+            ));
+            continue;
+        }
+        let prefix = format!("OAUTH_{}_", name.to_uppercase().replace('-', "_"));
+        let key = |suffix: &str| format!("{prefix}{suffix}");
+
+        // The original GitHub and Google credentials predate the registry.
+        let (legacy_id, legacy_secret) = match name {
+            "github" => (Some("CLIENT_ID"), Some("CLIENT_SECRET")),
+            "google" => (Some("G_CLIENT_ID"), Some("G_CLIENT_SECRET")),
+            _ => (None, None),
+        };
+        let client_id = l
+            .get(&key("CLIENT_ID"))
+            .or_else(|| legacy_id.and_then(|k| l.get(k)));
+        let client_secret = l
+            .get(&key("CLIENT_SECRET"))
+            .or_else(|| legacy_secret.and_then(|k| l.get(k)));
+        if client_id.is_none() {
+            l.error(format!("{} must be set", key("CLIENT_ID")));
+        }
+        if client_secret.is_none() {
+            l.error(format!("{} must be set", key("CLIENT_SECRET")));
+        }
+
+        let issuer = if providers::BUILT_IN.contains(&name) {
+            None
+        } else {
+            let issuer = l
+                .get(&key("ISSUER"))
+                .or_else(|| (name == "gitlab").then(|| "https://gitlab.com".to_string()));
+            match &issuer {
+                None => l.error(format!("{} must be set", key("ISSUER"))),
+                Some(url) => {
+                    if let Err(e) = Url::parse(url) {
+                        l.error(format!("{} is not a valid URL: {e}", key("ISSUER")));
+                    }
+                }
+            }
+            issuer
+        };
+
+        settings.push(ProviderSettings {
+            name: name.to_string(),
+            client_id: client_id.unwrap_or_default(),
+            client_secret: Secret(client_secret.unwrap_or_default()),
+            issuer,
+            display_name: l.get(&key("DISPLAY_NAME")),
+            scopes: l.get(&key("SCOPES")),
+            subject_claim: l.get(&key("SUBJECT_CLAIM")),
+            username_claim: l.get(&key("USERNAME_CLAIM")),
+            email_claim: l.get(&key("EMAIL_CLAIM")),
+        });
+    }
+    settings
+}
+
+/// Looks settings up in the environment, then the file, and collects what is wrong with them.
+struct Loader {
+    path: Option<String>,
+    /// The file's settings, by lower-cased name.
+    file: HashMap<String, String>,
+    /// Lower-cased names of every setting asked for, to spot unknown keys in the file.
+    asked: HashSet<String>,
+    errors: Vec<String>,
+}
+
+impl Loader {
+    fn new(path: Option<&str>) -> Self {
+        let mut loader = Self {
+            path: path.map(str::to_string),
+            file: HashMap::new(),
+            asked: HashSet::new(),
+            errors: Vec::new(),
+        };
+        if let Some(path) = path {
+            loader.read_file(path);
+        }
+        loader
+    }
+
+    fn read_file(&mut self, path: &str) {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return self.error(format!("could not read {path}: {e}")),
+        };
+        let table: toml::Table = match toml::from_str(&text) {
+            Ok(table) => table,
+            Err(e) => return self.error(format!("{path} is not valid TOML: {e}")),
+        };
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                // Lists such as `oauth_providers = ["github", "google"]`.
+                toml::Value::Array(items) if items.iter().all(toml::Value::is_str) => items
+                    .iter()
+                    .filter_map(toml::Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                _ => {
+                    self.error(format!(
+                        "{path}: {key} must be a string, an integer, a boolean or a list of strings"
+                    ));
+                    continue;
+                }
+            };
+            self.file.insert(key.to_ascii_lowercase(), value);
+        }
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+
+    fn get(&mut self, key: &str) -> Option<String> {
+        let name = key.to_ascii_lowercase();
+        let value = env::var(key).ok().or_else(|| self.file.get(&name).cloned());
+        self.asked.insert(name);
+        value
+    }
+
+    fn required(&mut self, key: &str) -> Option<String> {
+        let value = self.get(key);
+        if value.is_none() {
+            self.error(format!("{key} must be set"));
+        }
+        value
+    }
+
+    fn string(&mut self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    /// Parses the setting, keeping `default` when it is unset or invalid.
+    fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T
+    where
+        T::Err: fmt::Display,
+    {
+        let Some(raw) = self.get(key) else {
+            return default;
+        };
+        match raw.trim().parse() {
+            Ok(value) => value,
+            Err(e) => {
+                self.error(format!("{key} is invalid: {e} (got {raw:?})"));
+                default
+            }
+        }
+    }
+
+    /// A positive number of seconds.
+    fn secs<T: FromStr + Default + PartialOrd>(&mut self, key: &str, default: T) -> T {
+        let Some(raw) = self.get(key) else {
+            return default;
+        };
+        match raw.trim().parse::<T>() {
+            Ok(value) if value > T::default() => value,
+            _ => {
+                self.error(format!("{key} must be a positive number of seconds (got {raw:?})"));
+                default
+            }
+        }
+    }
+
+    /// An absolute URL, kept as given.
+    fn url(&mut self, key: &str, default: &str) -> String {
+        let value = self.string(key, default);
+        match Url::parse(&value) {
+            Ok(_) => value,
+            Err(e) => {
+                self.error(format!("{key} is not a valid URL: {e} (got {value:?})"));
+                default.to_string()
+            }
+        }
+    }
+
+    /// Flags settings in the file that nothing asked for and returns every error found.
+    fn finish(mut self) -> Vec<String> {
+        if let Some(path) = &self.path {
+            let mut unknown: Vec<&String> =
+                self.file.keys().filter(|k| !self.asked.contains(*k)).collect();
+            unknown.sort();
+            let unknown: Vec<String> = unknown
+                .into_iter()
+                .map(|key| format!("{path}: unknown setting {key}"))
+                .collect();
+            self.errors.extend(unknown);
+        }
+        self.errors
+    }
+}
@@ -1,6 +1,8 @@
 use crate::auth::user::{Backend, Credentials, PasswordCreds};
 use axum::{
     Form, Router,
+    extract::State,
+    http::HeaderMap,
     response::IntoResponse,
     routing::{get, post},
 };
@@ -10,6 +12,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::auth::user::AuthSession;
 
+use super::email_verification;
+use super::internal::InternalState;
+use super::login_throttle::{self, Verdict};
+use super::mfa;
 use super::telemetry;
 use super::user::ClientUser;
 
@@ -33,7 +39,7 @@ pub struct NextUrl {
     next: Option<String>,
 }
 
-pub fn router() -> Router<()> {
+pub fn router(state: InternalState) -> Router<()> {
     Router::new()
         .route("/auth/logout", get(self::get::logout))
         .route_layer(login_required!(Backend, login_url = "/auth/login"))
@@ -44,6 +50,7 @@ pub fn router() -> Router<()> {
         )
         .route("/auth/login/password", post(self::post::login::password))
         .route("/auth/login", get(self::get::login))
+        .with_state(state)
 }
 
 mod post {
@@ -58,6 +65,7 @@ mod post {
 
         #[tracing::instrument(name = "register.password", skip_all)]
         pub async fn password(
+            State(state): State<InternalState>,
             auth_session: AuthSession,
             Form(creds): Form<SignUpCreds>,
         ) -> impl IntoResponse {
@@ -66,6 +74,20 @@ mod post {
                 .register_user(&creds.username, &creds.email, &creds.password)
                 .await;
 
+            if let Ok(user) = &result
+                && let Err(e) = email_verification::send_verification(
+                    &state.db,
+                    &state.mailer,
+                    &state.public_url,
+                    user.id,
+                    &user.username,
+                    &creds.email,
+                )
+                .await
+            {
+                tracing::error!(user_id = user.id, error = %e, "failed to issue email verification token");
+            }
+
             match result {
                 Ok(user) => (
                     axum::http::StatusCode::OK,
@@ -88,6 +110,13 @@ mod post {
                         user: None,
                     }),
                 ),
+                Err(UserError::RegistrationClosed) => (
+                    axum::http::StatusCode::FORBIDDEN,
+                    Json(ApiResponse {
+                        message: "Registration is not open".to_string(),
+                        user: None,
+                    }),
+                ),
                 Err(_) => (
                     axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                     Json(ApiResponse {
@@ -111,14 +140,34 @@ mod post {
         #[tracing::instrument(name = "login.password", skip_all)]
         pub async fn password(
             mut auth_session: AuthSession,
+            headers: HeaderMap,
             Form(creds): Form<PasswordCreds>,
         ) -> impl IntoResponse {
+            let db = auth_session.backend.db.clone();
+            let client_ip = login_throttle::edge_client_ip(&headers);
+            match login_throttle::check(&db, &creds.username, client_ip.as_deref()).await {
+                Ok(Verdict::Allowed) => {}
+                Ok(Verdict::Throttled(retry_after)) => {
+                    telemetry::login_attempt("password", "throttled");
+                    return login_throttle::too_many_attempts(retry_after);
+                }
+                Ok(Verdict::Locked(retry_after)) => {
+                    telemetry::login_attempt("password", "locked");
+                    return login_throttle::too_many_attempts(retry_after);
+                }
+                Err(_) => {
+                    telemetry::login_attempt("password", "error");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+
             let user = match auth_session
                 .authenticate(Credentials::Password(creds.clone()))
                 .await
             {
                 Ok(Some(user)) => user,
                 Ok(None) => {
+                    let _ = login_throttle::record_failure(&db, &creds.username, client_ip.as_deref()).await;
                     tracing::info!("Invalid password");
                     telemetry::login_attempt("password", "failure");
                     return (StatusCode::UNAUTHORIZED, "invalid password".to_string())
@@ -130,6 +179,39 @@ mod post {
                 }
             };
 
+            match mfa::is_enabled(&auth_session.backend.db, user.id).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    let Some(code) = creds.code.as_deref() else {
+                        telemetry::login_attempt("password", "mfa_required");
+                        return (StatusCode::UNAUTHORIZED, "mfa code required".to_string())
+                            .into_response();
+                    };
+                    match mfa::verify_code(&auth_session.backend.db, user.id, code).await {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            // This path has no challenge attempt cap, so a wrong code counts
+                            // like a wrong password.
+                            let _ = login_throttle::record_failure(&db, &creds.username, client_ip.as_deref()).await;
+                            tracing::info!("Invalid mfa code");
+                            telemetry::login_attempt("totp", "failure");
+                            return (StatusCode::UNAUTHORIZED, "invalid mfa code".to_string())
+                                .into_response();
+                        }
+                        Err(_) => {
+                            telemetry::login_attempt("totp", "error");
+                            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                        }
+                    }
+                }
+                Err(_) => {
+                    telemetry::login_attempt("password", "error");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+
+            let _ = login_throttle::record_success(&db, &creds.username).await;
+
             if auth_session.login(&user).await.is_err() {
                 telemetry::login_attempt("password", "error");
                 return StatusCode::INTERNAL_SERVER_ERROR.into_response();
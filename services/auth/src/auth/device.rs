@@ -0,0 +1,492 @@
+//! Device authorization grant (RFC 8628) for the desktop and mobile apps.
+//!
+//! Native clients can't hold the BFF's cookie session or receive the OAuth callback, so they
+//! sign in on another screen instead:
+//!
+//! 1. The app posts its `client_id` to `/oauth2/device_authorization` and gets a `device_code`
+//!    to poll with and a short `user_code` to show the user, with the frontend's `/device` page
+//!    as `verification_uri`.
+//! 2. The user opens that page in a logged-in browser, enters the code and approves or denies
+//!    it; the frontend calls `/internal/device/approve` or `/internal/device/deny` with its
+//!    opaque token.
+//! 3. Meanwhile the app polls `/oauth2/token` with the device code grant type. Once approved it
+//!    receives a BFF token of its own, with `login_method` `device`, which it presents to the
+//!    frontend as `Authorization: Bearer …` and which carries the same permissions as the
+//!    user's browser session. It shows up in the user's sessions list and is revoked like one.
+//!
+//! Only clients registered with `--device` may use the grant.
+
+use axum::{
+    Form, Json, Router,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::audit::{self, Event};
+use super::internal::{InternalState, create_bff_token, resolve_token_user};
+use super::login_throttle;
+use super::oidc::{ClientRow, authenticate_client, token_error};
+use super::password_reset::{generate_token, hash_token};
+use super::telemetry;
+
+pub(super) const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const DEVICE_CODE_TTL_SECS: i64 = 600;
+const POLL_INTERVAL_SECS: i32 = 5;
+/// Added to a device's polling interval each time it polls too fast, as RFC 8628 §3.5 asks.
+const SLOW_DOWN_STEP_SECS: i32 = 5;
+/// Consonants only, so codes are easy to type and can't spell words.
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LEN: usize = 8;
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Service-token protected endpoints used by the frontend's `/device` page.
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/device/lookup", post(lookup))
+        .route("/internal/device/approve", post(approve))
+        .route("/internal/device/deny", post(deny))
+}
+
+fn generate_user_code() -> String {
+    let mut rng = rand::rng();
+    let code: String = (0..USER_CODE_LEN)
+        .map(|_| USER_CODE_ALPHABET[rng.random_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+/// User codes are compared ignoring case, dashes and spaces.
+fn normalize_user_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if code.len() == USER_CODE_LEN {
+        format!("{}-{}", &code[..4], &code[4..])
+    } else {
+        code
+    }
+}
+
+// ---- Device authorization endpoint ----
+
+#[derive(Deserialize)]
+pub(super) struct DeviceAuthorizationReq {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DeviceAuthorizationResp {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: String,
+    expires_in: i64,
+    interval: i32,
+}
+
+/// The device's user agent, as sent straight to the gateway.
+fn device_user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.trim().chars().take(MAX_USER_AGENT_LEN).collect::<String>())
+        .filter(|ua| !ua.is_empty())
+}
+
+#[tracing::instrument(name = "device.authorize", skip_all)]
+pub(super) async fn device_authorization(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Form(req): Form<DeviceAuthorizationReq>,
+) -> Response {
+    let client = match authenticate_client(
+        &state.db,
+        &headers,
+        req.client_id.as_deref(),
+        req.client_secret.as_deref(),
+        "device_authorization",
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    if !client.device_grant {
+        return token_error(
+            StatusCode::BAD_REQUEST,
+            "unauthorized_client",
+            "This client may not use the device authorization grant",
+        );
+    }
+
+    let _ = sqlx::query("DELETE FROM device_authorizations WHERE expires_at < NOW() - INTERVAL '1 hour'")
+        .execute(&state.db)
+        .await;
+
+    let device_code = generate_token();
+    // A fresh user code can collide with a pending one; try again with another.
+    let mut attempts = 0;
+    let user_code = loop {
+        let user_code = generate_user_code();
+        let stored = sqlx::query(
+            r#"
+            INSERT INTO device_authorizations
+                (device_code_hash, user_code, client_id, client_ip, user_agent, expires_at, poll_interval_secs)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6), $7)
+            "#,
+        )
+        .bind(hash_token(&device_code))
+        .bind(&user_code)
+        .bind(&client.client_id)
+        .bind(login_throttle::edge_client_ip(&headers))
+        .bind(device_user_agent(&headers))
+        .bind(DEVICE_CODE_TTL_SECS as f64)
+        .bind(POLL_INTERVAL_SECS)
+        .execute(&state.db)
+        .await;
+        match stored {
+            Ok(_) => break user_code,
+            Err(sqlx::Error::Database(db)) if db.is_unique_violation() && attempts < 3 => attempts += 1,
+            Err(e) => {
+                tracing::error!(error = %e, "device_authorization: could not store request");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    };
+
+    tracing::info!(client_id = %client.client_id, "device authorization started");
+    telemetry::token_operation("device_authorization", "success");
+    let verification_uri = format!("{}/device", state.public_url);
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(DeviceAuthorizationResp {
+            device_code,
+            verification_uri_complete: format!("{verification_uri}?code={user_code}"),
+            user_code,
+            verification_uri,
+            expires_in: DEVICE_CODE_TTL_SECS,
+            interval: POLL_INTERVAL_SECS,
+        }),
+    )
+        .into_response()
+}
+
+// ---- Polling, from the token endpoint ----
+
+#[derive(sqlx::FromRow)]
+struct PollRow {
+    id: i64,
+    user_id: Option<i64>,
+    approved: bool,
+    denied: bool,
+    expired: bool,
+    too_soon: bool,
+    /// The approving user's account has been disabled since.
+    user_disabled: bool,
+}
+
+/// Token endpoint answers to a device that can't have its token (yet).
+#[derive(Debug, Clone, Copy)]
+enum PollError {
+    Pending,
+    SlowDown,
+    Denied,
+    Expired,
+    Unknown,
+}
+
+impl PollError {
+    fn code(self) -> &'static str {
+        match self {
+            Self::Pending => "authorization_pending",
+            Self::SlowDown => "slow_down",
+            Self::Denied => "access_denied",
+            Self::Expired => "expired_token",
+            Self::Unknown => "invalid_grant",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Pending => "The user has not approved the request yet",
+            Self::SlowDown => "Polling too fast; wait longer between requests",
+            Self::Denied => "The user denied the request",
+            Self::Expired => "The device code has expired; start again",
+            Self::Unknown => "Unknown device code",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeviceTokenResp {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: i64,
+}
+
+/// The poll reaches auth straight through the gateway, so the client headers the BFF normally
+/// forwards can't be trusted here. Rebuilds them from what the edge proxy recorded, for the new
+/// session's sessions-list entry and audit event.
+fn device_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = HeaderMap::new();
+    if let Some(ip) = login_throttle::edge_client_ip(headers).and_then(|ip| HeaderValue::from_str(&ip).ok()) {
+        forwarded.insert("x-client-ip", ip);
+    }
+    if let Some(ua) = headers.get(header::USER_AGENT) {
+        forwarded.insert("x-client-user-agent", ua.clone());
+    }
+    forwarded
+}
+
+/// Answers a device code grant at `/oauth2/token` for an already authenticated client.
+pub(super) async fn token(
+    state: &InternalState,
+    client: &ClientRow,
+    headers: &HeaderMap,
+    device_code: Option<&str>,
+) -> Response {
+    if !client.device_grant {
+        return token_error(
+            StatusCode::BAD_REQUEST,
+            "unauthorized_client",
+            "This client may not use the device authorization grant",
+        );
+    }
+    let Some(device_code) = device_code else {
+        return token_error(StatusCode::BAD_REQUEST, "invalid_request", "device_code is required");
+    };
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let row: Option<PollRow> = sqlx::query_as(
+            r#"
+            SELECT id, user_id,
+                   approved_at IS NOT NULL AS approved,
+                   denied_at IS NOT NULL AS denied,
+                   expires_at <= NOW() AS expired,
+                   COALESCE(last_polled_at > NOW() - make_interval(secs => poll_interval_secs), FALSE) AS too_soon,
+                   EXISTS (
+                       SELECT 1 FROM users u WHERE u.id = d.user_id AND u.disabled_at IS NOT NULL
+                   ) AS user_disabled
+            FROM device_authorizations d
+            WHERE device_code_hash = $1 AND client_id = $2
+            FOR UPDATE OF d
+            "#,
+        )
+        .bind(hash_token(device_code))
+        .bind(&client.client_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(row) = row else {
+            return Ok(Err(PollError::Unknown));
+        };
+
+        if !row.approved && !row.denied && !row.expired {
+            sqlx::query(
+                r#"
+                UPDATE device_authorizations
+                SET last_polled_at = NOW(), poll_interval_secs = poll_interval_secs + $2
+                WHERE id = $1
+                "#,
+            )
+            .bind(row.id)
+            .bind(if row.too_soon { SLOW_DOWN_STEP_SECS } else { 0 })
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(Err(if row.too_soon { PollError::SlowDown } else { PollError::Pending }));
+        }
+
+        // Finished requests are deleted, so a device code yields at most one token.
+        sqlx::query("DELETE FROM device_authorizations WHERE id = $1")
+            .bind(row.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(match (row.expired, row.denied, row.user_id) {
+            (true, _, _) => Err(PollError::Expired),
+            (_, true, _) => Err(PollError::Denied),
+            _ if row.user_disabled => Err(PollError::Denied),
+            (_, _, Some(user_id)) => Ok(user_id),
+            (_, _, None) => Err(PollError::Unknown),
+        })
+    }
+    .await;
+
+    let user_id = match result {
+        Ok(Ok(user_id)) => user_id,
+        Ok(Err(e)) => {
+            if !matches!(e, PollError::Pending | PollError::SlowDown) {
+                telemetry::token_operation("device_token", e.code());
+            }
+            return token_error(StatusCode::BAD_REQUEST, e.code(), e.description());
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "device token: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let token = match create_bff_token(state, user_id, "device", &device_headers(headers)).await {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!(user_id, error = %e, "device token: could not create bff token");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    tracing::info!(user_id, client_id = %client.client_id, "device token issued");
+    telemetry::token_operation("device_token", "success");
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(DeviceTokenResp {
+            access_token: token.token,
+            token_type: "Bearer",
+            expires_in: (token.expires_at - Utc::now()).num_seconds(),
+        }),
+    )
+        .into_response()
+}
+
+// ---- Approval page (from the frontend) ----
+
+#[derive(Deserialize)]
+struct UserCodeReq {
+    token: String,
+    user_code: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct PendingDeviceResp {
+    user_code: String,
+    client_name: String,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Describes a pending request by its user code, so the user can check it is their device
+/// before approving.
+#[tracing::instrument(name = "device.lookup", skip_all)]
+async fn lookup(
+    State(state): State<InternalState>,
+    Json(req): Json<UserCodeReq>,
+) -> impl IntoResponse {
+    if resolve_token_user(&state.db, &req.token).await.is_none() {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    }
+
+    let row: Result<Option<PendingDeviceResp>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT d.user_code, c.name AS client_name, d.client_ip, d.user_agent, d.created_at, d.expires_at
+        FROM device_authorizations d
+        JOIN oauth_clients c ON c.client_id = d.client_id
+        WHERE d.user_code = $1 AND d.expires_at > NOW()
+          AND d.approved_at IS NULL AND d.denied_at IS NULL
+        "#,
+    )
+    .bind(normalize_user_code(&req.user_code))
+    .fetch_optional(&state.db)
+    .await;
+
+    match row {
+        Ok(Some(row)) => Json(row).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Unknown or expired code").into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "device lookup: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "device.approve", skip_all)]
+async fn approve(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<UserCodeReq>,
+) -> impl IntoResponse {
+    decide(&state, &headers, req, true).await
+}
+
+#[tracing::instrument(name = "device.deny", skip_all)]
+async fn deny(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<UserCodeReq>,
+) -> impl IntoResponse {
+    decide(&state, &headers, req, false).await
+}
+
+#[derive(sqlx::FromRow)]
+struct DecidedRow {
+    id: i64,
+    client_id: String,
+    client_name: String,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+/// Settles a pending request for the caller. The device picks the outcome up on its next poll.
+async fn decide(state: &InternalState, headers: &HeaderMap, req: UserCodeReq, approved: bool) -> Response {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let (action, column) = if approved {
+        ("device.approve", "approved_at")
+    } else {
+        ("device.deny", "denied_at")
+    };
+    let event = Event::new(action, headers).actor(user.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let row: Option<DecidedRow> = sqlx::query_as(&format!(
+            r#"
+            UPDATE device_authorizations d SET user_id = $2, {column} = NOW()
+            FROM oauth_clients c
+            WHERE d.user_code = $1 AND c.client_id = d.client_id AND d.expires_at > NOW()
+              AND d.approved_at IS NULL AND d.denied_at IS NULL
+            RETURNING d.id, d.client_id, c.name AS client_name, d.client_ip, d.user_agent
+            "#
+        ))
+        .bind(normalize_user_code(&req.user_code))
+        .bind(user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(row) = &row {
+            let event = event
+                .target(format!("device login #{} ({})", row.id, row.client_name))
+                .after(json!({
+                    "client_id": row.client_id,
+                    "client_ip": row.client_ip,
+                    "user_agent": row.user_agent,
+                }));
+            audit::record(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(row)
+    }
+    .await;
+
+    match result {
+        Ok(Some(row)) => {
+            tracing::info!(user_id = user.user_id, client_id = %row.client_id, approved, "device login decided");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Unknown or expired code").into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "device decide: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Proof of ownership for the email address given at signup.
+//!
+//! Registration mails a single-use link; `/internal/email/verify` redeems it and sets
+//! `users.email_verified_at`. Accounts without an email (GitHub logins) have nothing to verify
+//! and are always treated as verified. What an unverified account may do is decided by
+//! [`UnverifiedPolicy`].
+
+use std::str::FromStr;
+
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::post};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::internal::{InternalState, resolve_token_user};
+use super::mail::{Mail, Mailer};
+use super::password_reset::{generate_token, hash_token};
+use super::telemetry;
+
+/// What accounts with an unverified email may do, from `UNVERIFIED_EMAIL_POLICY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnverifiedPolicy {
+    /// No restrictions.
+    Allow,
+    /// Introspection reports no permissions until the email is verified. Roles can still be
+    /// granted and take effect on verification.
+    NoPermissions,
+    /// As `NoPermissions`, and admins cannot grant roles to the account at all.
+    Strict,
+}
+
+impl FromStr for UnverifiedPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "no_permissions" => Ok(Self::NoPermissions),
+            "strict" => Ok(Self::Strict),
+            _ => Err("expected allow, no_permissions or strict"),
+        }
+    }
+}
+
+impl UnverifiedPolicy {
+    pub fn hides_permissions(self) -> bool {
+        self != Self::Allow
+    }
+
+    pub fn blocks_role_grants(self) -> bool {
+        self == Self::Strict
+    }
+}
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/email/verify", post(verify_email))
+        .route("/internal/email/status", post(email_status))
+        .route("/internal/email/resend", post(resend_verification))
+}
+
+/// True when the account has no email or has verified it.
+pub(super) async fn is_verified(db: &PgPool, user_id: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT email IS NULL OR email_verified_at IS NOT NULL FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Issues a fresh verification token (replacing any earlier one) and queues the email.
+pub(super) async fn send_verification(
+    db: &PgPool,
+    mailer: &Mailer,
+    public_url: &str,
+    user_id: i64,
+    username: &str,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    let token = generate_token();
+
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO email_verification_tokens (token_hash, user_id) VALUES ($1, $2)")
+        .bind(hash_token(&token))
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    mailer.enqueue(Mail {
+        to: email.to_string(),
+        subject: "Confirm your milesstorm.com email address".to_string(),
+        body: format!(
+            "Hi {username},\n\nPlease confirm this is your email address by opening the link \
+             below within the next 24 hours:\n\n{public_url}/verify-email?token={token}\n\n\
+             If you didn't create an account, you can ignore this email.\n"
+        ),
+    });
+    tracing::info!(user_id, "verification email queued");
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct VerifyReq {
+    token: String,
+}
+
+#[tracing::instrument(name = "email.verify", skip_all)]
+async fn verify_email(
+    State(state): State<InternalState>,
+    Json(req): Json<VerifyReq>,
+) -> impl IntoResponse {
+    let result: Result<Option<i64>, sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        let user_id: Option<i64> = sqlx::query_scalar(
+            "DELETE FROM email_verification_tokens WHERE token_hash = $1 AND expires_at > NOW() RETURNING user_id",
+        )
+        .bind(hash_token(&req.token))
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(user_id) = user_id {
+            sqlx::query("UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(user_id)
+    }
+    .await;
+
+    match result {
+        Ok(Some(user_id)) => {
+            tracing::info!(user_id, "email verified");
+            telemetry::token_operation("email_verify", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => {
+            telemetry::token_operation("email_verify", "invalid");
+            (StatusCode::BAD_REQUEST, "This verification link is invalid or has expired").into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "verify_email: db error");
+            telemetry::token_operation("email_verify", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenReq {
+    token: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct EmailStatusResp {
+    email: Option<String>,
+    verified: bool,
+}
+
+#[tracing::instrument(name = "email.status", skip_all)]
+async fn email_status(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let row: Result<EmailStatusResp, sqlx::Error> = sqlx::query_as(
+        "SELECT email, email IS NULL OR email_verified_at IS NOT NULL AS verified FROM users WHERE id = $1",
+    )
+    .bind(user.user_id)
+    .fetch_one(&state.db)
+    .await;
+
+    match row {
+        Ok(r) => Json(r).into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "email_status: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "email.resend", skip_all)]
+async fn resend_verification(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let email: Result<Option<String>, sqlx::Error> = sqlx::query_scalar(
+        "SELECT email FROM users WHERE id = $1 AND email IS NOT NULL AND email_verified_at IS NULL",
+    )
+    .bind(user.user_id)
+    .fetch_optional(&state.db)
+    .await;
+
+    let email = match email {
+        Ok(Some(e)) => e,
+        Ok(None) => {
+            return (StatusCode::CONFLICT, "There is no unverified email on this account").into_response();
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "resend_verification: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match send_verification(
+        &state.db,
+        &state.mailer,
+        &state.public_url,
+        user.user_id,
+        &user.username,
+        &email,
+    )
+    .await
+    {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "resend_verification: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Liveness and readiness probes for Kubernetes.
+//!
+//! `/healthz` answers as long as the process serves requests. `/readyz` also checks that
+//! Postgres answers and every migration this build ships has been applied, and starts failing
+//! as soon as a SIGTERM arrives so the mesh drains traffic before the listener closes. Both are
+//! outside the tracing and metrics layers; probes would only add noise there.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+/// How long a dependency may take to answer before it counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shared readiness state: the database to check and whether shutdown has begun.
+#[derive(Clone)]
+pub(super) struct Health {
+    db: PgPool,
+    draining: Arc<AtomicBool>,
+}
+
+impl Health {
+    pub(super) fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            draining: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Fails readiness from now on, then waits `drain` for the mesh to notice.
+    pub(super) async fn drain(&self, drain: Duration) {
+        self.draining.store(true, Ordering::SeqCst);
+        tracing::info!(drain_secs = drain.as_secs(), "draining before shutdown");
+        tokio::time::sleep(drain).await;
+    }
+}
+
+pub(super) fn router(health: Health) -> Router<()> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(health)
+}
+
+#[derive(Serialize)]
+struct Check {
+    status: &'static str,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs `check` with [`CHECK_TIMEOUT`], timing it.
+async fn run<F>(check: F) -> Check
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err("timed out".to_string()),
+    };
+    Check {
+        status: if result.is_ok() { "ok" } else { "fail" },
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err(),
+    }
+}
+
+async fn healthz() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn readyz(State(health): State<Health>) -> Response {
+    if health.draining.load(Ordering::SeqCst) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "draining", "checks": {} })),
+        )
+            .into_response();
+    }
+
+    let postgres = run(async {
+        sqlx::query("SELECT 1")
+            .execute(&health.db)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+    .await;
+    let migrations = run(pending_migrations(&health.db)).await;
+
+    let ready = postgres.ok() && migrations.ok();
+    if !ready {
+        tracing::warn!(postgres = ?postgres.error, migrations = ?migrations.error, "not ready");
+    }
+    let body = json!({
+        "status": if ready { "ok" } else { "fail" },
+        "checks": { "postgres": postgres, "migrations": migrations },
+    });
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Fails if a migration embedded in this build hasn't been applied successfully.
+async fn pending_migrations(db: &PgPool) -> Result<(), String> {
+    let applied: HashSet<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+    let pending: Vec<String> = sqlx::migrate!()
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .map(|m| m.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("not applied: {}", pending.join(", ")))
+    }
+}
@@ -0,0 +1,336 @@
+//! Upstream accounts linked to a user.
+//!
+//! Each row of `user_identities` ties a provider account, by the provider's stable account id, to
+//! a local user. A user can have any number of them next to a password and passkeys. Logging in
+//! with an account that isn't linked to anyone creates a new user; linking one to an existing user
+//! happens from the profile page, which runs the OAuth flow while logged in and hands the code to
+//! `/internal/identities/link` instead of `/internal/oauth/exchange`.
+//!
+//! A user must keep at least one way to log in, so removing the last of their identities,
+//! passkeys and password is refused.
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+
+use super::internal::{InternalState, resolve_token_user};
+use super::providers;
+use super::user::BackendError;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/identities/list", post(list_identities))
+        .route("/internal/identities/link", post(link_identity))
+        .route("/internal/identities/unlink", post(unlink_identity))
+}
+
+/// What a provider told us about the account that just authorized us.
+#[derive(Debug, Clone)]
+pub struct UpstreamIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub access_token: String,
+}
+
+/// Finds the user an upstream account is linked to.
+///
+/// Rows backfilled from before identities were tracked are keyed by GitHub login or by email
+/// instead of the provider's account id; the first login matching one of those swaps in the real
+/// id, after which renaming the upstream account no longer matters.
+pub(super) async fn find_user(
+    db: &PgPool,
+    identity: &UpstreamIdentity,
+) -> Result<Option<i64>, sqlx::Error> {
+    let user_id: Option<i64> = sqlx::query_scalar(
+        r#"
+        UPDATE user_identities SET email = COALESCE($3, email)
+        WHERE provider = $1 AND subject = $2
+        RETURNING user_id
+        "#,
+    )
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .fetch_optional(db)
+    .await?;
+    if user_id.is_some() {
+        return Ok(user_id);
+    }
+
+    let mut legacy = Vec::new();
+    if let Some(username) = &identity.username {
+        legacy.push(format!("login:{username}"));
+    }
+    if identity.email_verified
+        && let Some(email) = &identity.email
+    {
+        legacy.push(format!("email:{email}"));
+    }
+    if legacy.is_empty() {
+        return Ok(None);
+    }
+
+    sqlx::query_scalar(
+        r#"
+        UPDATE user_identities SET subject = $3, email = COALESCE($4, email)
+        WHERE id = (
+            SELECT id FROM user_identities
+            WHERE provider = $1 AND subject = ANY($2)
+            LIMIT 1
+        )
+        RETURNING user_id
+        "#,
+    )
+    .bind(&identity.provider)
+    .bind(&legacy)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .fetch_optional(db)
+    .await
+}
+
+pub(super) async fn insert<'e>(
+    db: impl sqlx::PgExecutor<'e>,
+    user_id: i64,
+    identity: &UpstreamIdentity,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, provider, subject, email) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Serializes removals of a user's login methods. Callers removing one take this lock in a
+/// transaction, delete, and check [`login_method_count`] before committing.
+pub(super) async fn lock_login_methods(
+    conn: &mut PgConnection,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// The ways a user can log in: a password, each passkey and each linked identity.
+pub(super) async fn login_method_count(
+    conn: &mut PgConnection,
+    user_id: i64,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT (u.password IS NOT NULL)::int8
+            + (SELECT COUNT(*) FROM webauthn_credentials w WHERE w.user_id = u.id)
+            + (SELECT COUNT(*) FROM user_identities i WHERE i.user_id = u.id)
+        FROM users u WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+}
+
+// ---- Handlers ----
+
+#[derive(Deserialize)]
+struct TokenReq {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct LinkReq {
+    token: String,
+    provider: String,
+    code: String,
+    state: String,
+}
+
+#[derive(Deserialize)]
+struct UnlinkReq {
+    token: String,
+    id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct IdentityRow {
+    id: i64,
+    provider: String,
+    email: Option<String>,
+    linked_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct IdentityResp {
+    id: i64,
+    provider: String,
+    display_name: String,
+    email: Option<String>,
+    linked_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "identities.list", skip_all)]
+async fn list_identities(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let rows: Result<Vec<IdentityRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, provider, email, linked_at FROM user_identities WHERE user_id = $1 ORDER BY linked_at",
+    )
+    .bind(user.user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let resp: Vec<IdentityResp> = rows
+                .into_iter()
+                .map(|r| IdentityResp {
+                    // Providers removed from OAUTH_PROVIDERS keep their rows but have no label.
+                    display_name: state
+                        .backend
+                        .provider(&r.provider)
+                        .map(|p| p.display_name.clone())
+                        .unwrap_or_else(|| r.provider.clone()),
+                    id: r.id,
+                    provider: r.provider,
+                    email: r.email,
+                    linked_at: r.linked_at,
+                })
+                .collect();
+            Json(resp).into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "list_identities: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Finishes the OAuth flow started from the profile page by linking the upstream account to the
+/// token's user. Linking an account that is already linked to someone else is refused with
+/// `identity_in_use`; relinking one's own is a no-op.
+#[tracing::instrument(name = "identities.link", skip_all, fields(provider = %req.provider))]
+async fn link_identity(
+    State(state): State<InternalState>,
+    Json(req): Json<LinkReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+    let Some(provider) = state.backend.provider(&req.provider) else {
+        return (StatusCode::NOT_FOUND, "Unknown provider").into_response();
+    };
+
+    let secrets = match providers::take_login_request(&state.db, &provider.name, &req.state).await {
+        Ok(Some(secrets)) => secrets,
+        Ok(None) => return (StatusCode::BAD_REQUEST, "invalid_state").into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "link_identity: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let identity = match state.backend.fetch_identity(provider, req.code, secrets).await {
+        Ok(i) => i,
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = ?e, "link_identity: oauth exchange failed");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    let result = match find_user(&state.db, &identity).await {
+        Ok(Some(owner)) if owner == user.user_id => Ok(()),
+        Ok(Some(_)) => return (StatusCode::CONFLICT, "identity_in_use").into_response(),
+        Ok(None) => insert(&state.db, user.user_id, &identity).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => {
+            tracing::info!(user_id = user.user_id, provider = %identity.provider, "identity linked");
+            if let Err(e) = state.backend.store_provider_token(&identity).await {
+                tracing::error!(user_id = user.user_id, error = %e, "link_identity: could not store provider token");
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        // Someone else linked the same account in the meantime.
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            (StatusCode::CONFLICT, "identity_in_use").into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "link_identity: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "identities.unlink", skip_all)]
+async fn unlink_identity(
+    State(state): State<InternalState>,
+    Json(req): Json<UnlinkReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    match unlink(&state.db, user.user_id, req.id).await {
+        Ok(true) => {
+            tracing::info!(user_id = user.user_id, identity = req.id, "identity unlinked");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(BackendError::LastLoginMethod) => (
+            StatusCode::CONFLICT,
+            "This is your only way to log in. Add a password, passkey or another login first.",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "unlink_identity: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Removes one of the user's identities unless it is their last way to log in. Returns whether a
+/// row was removed.
+async fn unlink(db: &PgPool, user_id: i64, id: i64) -> Result<bool, BackendError> {
+    let mut tx = db.begin().await?;
+    lock_login_methods(&mut tx, user_id).await?;
+
+    let deleted = sqlx::query("DELETE FROM user_identities WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Ok(false);
+    }
+    if login_method_count(&mut tx, user_id).await? == 0 {
+        return Err(BackendError::LastLoginMethod);
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
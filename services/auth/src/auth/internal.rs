@@ -1,28 +1,53 @@
 use axum::{
     Json, Router,
-    body::Body,
     extract::{Path, Query, State},
-    http::{Request, StatusCode},
-    middleware::{self, Next},
+    http::{HeaderMap, StatusCode},
+    middleware,
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{get, post},
 };
-use jsonwebtoken::{EncodingKey, Header, encode};
+use chrono::{DateTime, Utc};
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
 use tokio::task;
 use ulid::Ulid;
 
+use super::access_tokens;
+use super::accounts;
+use super::audit::{self, AdminActor, Event};
+use super::device;
+use super::email_verification::{self, UnverifiedPolicy};
+use super::identities;
+use super::invites::{self, InviteError};
+use super::jwt_keys::JwtKeys;
+use super::login_throttle::{self, Verdict};
+use super::mail::Mailer;
+use super::mfa;
+use super::oidc;
+use super::password_reset;
+use super::providers;
+use super::roles::{self, RbacError};
+use super::service_auth::{self, ServiceKeys};
+use super::sessions::{self, TokenLifetimes};
+use super::webauthn;
 use super::telemetry;
-use super::user::{Backend, BackendError, BffToken, OAuthProvider};
+use super::user::{Backend, BackendError, BffToken};
 
 #[derive(Clone)]
 pub struct InternalState {
     pub db: PgPool,
-    pub jwt_secret: String,
-    pub service_secret: String,
+    pub jwt_keys: JwtKeys,
+    pub service_keys: ServiceKeys,
     pub backend: Backend,
+    pub mailer: Mailer,
+    /// Public URL of the frontend, used to build links in outgoing email.
+    pub public_url: String,
+    /// OpenID Connect issuer identifier, from `OIDC_ISSUER`.
+    pub oidc_issuer: String,
+    pub email_policy: UnverifiedPolicy,
+    pub token_lifetimes: TokenLifetimes,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +65,7 @@ pub fn router(state: InternalState) -> Router<()> {
         .route("/internal/token/exchange", post(exchange_password))
         .route("/internal/token/introspect", post(introspect))
         .route("/internal/register", post(register))
+        .route("/internal/oauth/providers", get(oauth_providers))
         .route("/internal/oauth/start", post(oauth_start))
         .route("/internal/oauth/exchange", post(oauth_exchange))
         .route("/internal/ark/num_players", post(ark_num_players))
@@ -51,36 +77,62 @@ pub fn router(state: InternalState) -> Router<()> {
         .route("/internal/admin/roles/all", get(admin_list_all_roles))
         .route("/internal/admin/permissions", get(admin_list_permissions))
         .route("/internal/admin/roles/{role_id}/permissions/{permission_id}", post(admin_assign_role_permission).delete(admin_revoke_role_permission))
+        .merge(mfa::routes())
+        .merge(webauthn::routes())
+        .merge(password_reset::routes())
+        .merge(email_verification::routes())
+        .merge(sessions::routes())
+        .merge(oidc::routes())
+        .merge(identities::routes())
+        .merge(audit::routes())
+        .merge(roles::routes())
+        .merge(accounts::routes())
+        .merge(invites::routes())
+        .merge(access_tokens::routes())
+        .merge(device::routes())
         .layer(middleware::from_fn_with_state(
             state.clone(),
-            verify_service_token,
+            service_auth::verify,
         ))
         .with_state(state)
 }
 
-async fn create_bff_token(db: &PgPool, user_id: i64) -> Result<BffToken, sqlx::Error> {
-    sqlx::query_as("INSERT INTO bff_tokens (token, user_id) VALUES ($1, $2) RETURNING *")
-        .bind(Ulid::new().to_string())
-        .bind(user_id)
-        .fetch_one(db)
-        .await
-}
-
-async fn verify_service_token(
-    State(state): State<InternalState>,
-    req: Request<Body>,
-    next: Next,
-) -> impl IntoResponse {
-    let token = req
-        .headers()
-        .get("x-service-token")
-        .and_then(|v| v.to_str().ok());
-
-    if token != Some(state.service_secret.as_str()) {
-        return (StatusCode::UNAUTHORIZED, "Invalid service token").into_response();
-    }
+/// Issues a new opaque BFF token, recording how the user logged in and the client details the
+/// BFF forwarded so the session can be recognised on the sessions list. Every login ends here, so
+/// this is also where logins are audited.
+pub(super) async fn create_bff_token(
+    state: &InternalState,
+    user_id: i64,
+    login_method: &str,
+    headers: &HeaderMap,
+) -> Result<BffToken, sqlx::Error> {
+    let token = sqlx::query_as(
+        r#"
+        INSERT INTO bff_tokens (
+            token, user_id, login_method, client_ip, user_agent, expires_at, absolute_expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6), NOW() + make_interval(secs => $7))
+        RETURNING token, user_id, expires_at
+        "#,
+    )
+    .bind(Ulid::new().to_string())
+    .bind(user_id)
+    .bind(login_method)
+    .bind(login_throttle::forwarded_client_ip(headers))
+    .bind(sessions::forwarded_user_agent(headers))
+    .bind(state.token_lifetimes.idle as f64)
+    .bind(state.token_lifetimes.absolute as f64)
+    .fetch_one(&state.db)
+    .await?;
 
-    next.run(req).await
+    audit::log(
+        &state.db,
+        Event::new("auth.login", headers)
+            .actor(user_id)
+            .after(json!({ "method": login_method })),
+    )
+    .await;
+    Ok(token)
 }
 
 // ---- Token exchange (password credentials) ----
@@ -92,8 +144,16 @@ struct ExchangePasswordReq {
 }
 
 #[derive(Serialize)]
-struct TokenResp {
-    token: String,
+pub(super) struct TokenResp {
+    pub token: String,
+    pub username: String,
+}
+
+/// Returned with `202 Accepted` instead of [`TokenResp`] when the password was correct but the
+/// account has TOTP enabled. The BFF finishes the login via `/internal/token/exchange/mfa`.
+#[derive(Serialize)]
+struct MfaChallengeResp {
+    mfa_token: String,
     username: String,
 }
 
@@ -102,15 +162,37 @@ struct UserRow {
     id: i64,
     username: String,
     password: Option<String>,
+    disabled_at: Option<DateTime<Utc>>,
 }
 
 #[tracing::instrument(name = "token.exchange.password", skip_all)]
 async fn exchange_password(
     State(state): State<InternalState>,
+    headers: HeaderMap,
     Json(req): Json<ExchangePasswordReq>,
 ) -> impl IntoResponse {
+    let client_ip = login_throttle::forwarded_client_ip(&headers);
+    match login_throttle::check(&state.db, &req.username, client_ip.as_deref()).await {
+        Ok(Verdict::Allowed) => {}
+        Ok(Verdict::Throttled(retry_after)) => {
+            tracing::warn!(username = %req.username, ip = ?client_ip, retry_after, "password login throttled");
+            telemetry::login_attempt("password", "throttled");
+            return login_throttle::too_many_attempts(retry_after);
+        }
+        Ok(Verdict::Locked(retry_after)) => {
+            tracing::warn!(username = %req.username, ip = ?client_ip, retry_after, "password login locked out");
+            telemetry::login_attempt("password", "locked");
+            return login_throttle::too_many_attempts(retry_after);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to check login attempts");
+            telemetry::login_attempt("password", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
     let user: Option<UserRow> = sqlx::query_as(
-        "SELECT id, username, password FROM users WHERE username = $1 AND password IS NOT NULL",
+        "SELECT id, username, password, disabled_at FROM users WHERE username = $1 AND password IS NOT NULL",
     )
     .bind(&req.username)
     .fetch_optional(&state.db)
@@ -118,6 +200,15 @@ async fn exchange_password(
     .unwrap_or(None);
 
     let Some(user) = user else {
+        if let Err(e) = login_throttle::record_failure(&state.db, &req.username, client_ip.as_deref()).await {
+            tracing::error!(error = %e, "failed to record login attempt");
+        }
+        audit::log(
+            &state.db,
+            Event::new("auth.login_failed", &headers).target(format!("user {}", req.username)),
+        )
+        .await;
+        telemetry::login_attempt("password", "failure");
         return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response();
     };
 
@@ -129,13 +220,66 @@ async fn exchange_password(
         .unwrap_or(false);
 
     if !valid {
+        if let Err(e) = login_throttle::record_failure(&state.db, &req.username, client_ip.as_deref()).await {
+            tracing::error!(error = %e, "failed to record login attempt");
+        }
+        audit::log(
+            &state.db,
+            Event::new("auth.login_failed", &headers)
+                .target(format!("user {} (#{})", user.username, user.id)),
+        )
+        .await;
         tracing::warn!(username = %req.username, "password login failed: invalid credentials");
         telemetry::login_attempt("password", "failure");
         telemetry::token_operation("exchange", "failure");
         return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response();
     }
 
-    match create_bff_token(&state.db, user.id).await {
+    // Only said once the password checks out, so it doesn't reveal which accounts are disabled.
+    if user.disabled_at.is_some() {
+        tracing::warn!(user_id = user.id, username = %user.username, "password login refused: account disabled");
+        telemetry::login_attempt("password", "disabled");
+        telemetry::token_operation("exchange", "rejected");
+        return (StatusCode::FORBIDDEN, "account_disabled").into_response();
+    }
+
+    match mfa::is_enabled(&state.db, user.id).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return match mfa::create_challenge(&state.db, user.id).await {
+                Ok(mfa_token) => {
+                    tracing::info!(user_id = user.id, username = %user.username, "password accepted, mfa required");
+                    telemetry::login_attempt("password", "mfa_required");
+                    (
+                        StatusCode::ACCEPTED,
+                        Json(MfaChallengeResp {
+                            mfa_token,
+                            username: user.username,
+                        }),
+                    )
+                        .into_response()
+                }
+                Err(e) => {
+                    tracing::error!(user_id = user.id, error = %e, "failed to create mfa challenge");
+                    telemetry::token_operation("exchange", "error");
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            };
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.id, error = %e, "failed to look up mfa status");
+            telemetry::token_operation("exchange", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    // Not reset when a second factor is still owed: `exchange_mfa` counts wrong codes against the
+    // same username, and a correct password alone mustn't wipe them.
+    if let Err(e) = login_throttle::record_success(&state.db, &req.username).await {
+        tracing::error!(error = %e, "failed to reset login attempts");
+    }
+
+    match create_bff_token(&state, user.id, "password", &headers).await {
         Ok(bff_token) => {
             tracing::info!(user_id = user.id, username = %user.username, "password login succeeded");
             telemetry::login_attempt("password", "success");
@@ -158,7 +302,10 @@ async fn exchange_password(
 
 #[derive(Deserialize)]
 struct OAuthStartReq {
-    provider: OAuthProvider,
+    provider: String,
+    /// Redeemed if this login creates an account.
+    #[serde(default)]
+    invite_code: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -169,44 +316,96 @@ struct OAuthStartResp {
 
 #[derive(Deserialize)]
 struct OAuthExchangeReq {
-    provider: OAuthProvider,
+    provider: String,
     code: String,
+    /// The `state` `/internal/oauth/start` returned for this login.
+    state: String,
 }
 
-#[tracing::instrument(name = "auth.oauth_start", skip_all, fields(provider = ?req.provider))]
+/// The configured upstream identity providers, for the Login view's buttons.
+#[tracing::instrument(name = "auth.oauth_providers", skip_all)]
+async fn oauth_providers(State(state): State<InternalState>) -> impl IntoResponse {
+    Json(state.backend.providers().list())
+}
+
+#[tracing::instrument(name = "auth.oauth_start", skip_all, fields(provider = %req.provider))]
 async fn oauth_start(
     State(state): State<InternalState>,
     Json(req): Json<OAuthStartReq>,
 ) -> impl IntoResponse {
-    let (url, csrf) = match req.provider {
-        OAuthProvider::Github => state.backend.authorize_url(),
-        OAuthProvider::Google => state.backend.authorize_g_url(),
+    let Some(provider) = state.backend.provider(&req.provider) else {
+        return (StatusCode::NOT_FOUND, "Unknown provider").into_response();
     };
+    let request = provider.authorize_url();
+    if let Err(e) = providers::store_login_request(
+        &state.db,
+        &provider.name,
+        &request,
+        req.invite_code.as_deref(),
+    )
+    .await
+    {
+        tracing::error!(error = %e, "oauth_start: failed to store login request");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
     Json(OAuthStartResp {
-        auth_url: url.to_string(),
-        state: csrf.secret().to_string(),
+        auth_url: request.url.to_string(),
+        state: request.state,
     })
     .into_response()
 }
 
-#[tracing::instrument(name = "token.exchange.oauth", skip_all, fields(provider = ?req.provider))]
+#[tracing::instrument(name = "token.exchange.oauth", skip_all, fields(provider = %req.provider))]
 async fn oauth_exchange(
     State(state): State<InternalState>,
+    headers: HeaderMap,
     Json(req): Json<OAuthExchangeReq>,
 ) -> impl IntoResponse {
-    let provider_str = format!("{:?}", req.provider).to_lowercase();
+    let Some(provider) = state.backend.provider(&req.provider) else {
+        return (StatusCode::NOT_FOUND, "Unknown provider").into_response();
+    };
+    let provider_str = provider.name.clone();
+
+    let secrets = match providers::take_login_request(&state.db, &provider_str, &req.state).await {
+        Ok(Some(secrets)) => secrets,
+        Ok(None) => {
+            tracing::warn!("oauth exchange: unknown or expired state");
+            telemetry::login_attempt(&provider_str, "invalid_state");
+            telemetry::token_operation("exchange", "rejected");
+            return (StatusCode::BAD_REQUEST, "invalid_state").into_response();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "oauth exchange: db error");
+            telemetry::token_operation("exchange", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
 
-    let user = match state.backend.complete_oauth(req.provider, req.code).await {
+    let user = match state.backend.complete_oauth(provider, req.code, secrets, &headers).await {
         Ok(u) => u,
         Err(BackendError::EmailAlreadyInUse) => {
-            tracing::warn!("oauth exchange: email already in use by a password account");
+            tracing::warn!("oauth exchange: email already in use by another account");
             telemetry::login_attempt(&provider_str, "conflict");
             telemetry::token_operation("exchange", "conflict");
-            return (
-                StatusCode::CONFLICT,
-                "Email already in use by a password account",
-            )
-                .into_response();
+            return (StatusCode::CONFLICT, "Email already in use by another account").into_response();
+        }
+        Err(BackendError::UsernameTaken) => {
+            tracing::warn!("oauth exchange: username already in use by another account");
+            telemetry::login_attempt(&provider_str, "conflict");
+            telemetry::token_operation("exchange", "conflict");
+            return (StatusCode::CONFLICT, "username_taken").into_response();
+        }
+        Err(BackendError::AccountDisabled) => {
+            tracing::warn!("oauth exchange refused: account disabled");
+            telemetry::login_attempt(&provider_str, "disabled");
+            telemetry::token_operation("exchange", "rejected");
+            return (StatusCode::FORBIDDEN, "account_disabled").into_response();
+        }
+        Err(BackendError::Invite(e)) => {
+            tracing::warn!(reason = e.code(), "oauth signup refused");
+            telemetry::login_attempt(&provider_str, "signup_refused");
+            telemetry::token_operation("exchange", "rejected");
+            return invites::error_response(e, "oauth_exchange");
         }
         Err(e) => {
             tracing::error!(error = ?e, "oauth exchange failed");
@@ -216,7 +415,7 @@ async fn oauth_exchange(
         }
     };
 
-    let bff_token = match create_bff_token(&state.db, user.id).await {
+    let bff_token = match create_bff_token(&state, user.id, &provider_str, &headers).await {
         Ok(t) => t,
         Err(e) => {
             tracing::error!(user_id = user.id, error = %e, "failed to insert bff_token after oauth exchange");
@@ -247,50 +446,82 @@ struct IntrospectResp {
     jwt: String,
     username: String,
     permissions: Vec<String>,
+    email_verified: bool,
 }
 
 #[derive(sqlx::FromRow)]
-struct TokenRow {
-    user_id: i64,
-    username: String,
+pub(super) struct TokenRow {
+    pub user_id: i64,
+    pub username: String,
 }
 
-#[tracing::instrument(name = "token.introspect", skip_all)]
-async fn introspect(
-    State(state): State<InternalState>,
-    Json(req): Json<IntrospectReq>,
-) -> impl IntoResponse {
-    let row: Option<TokenRow> = sqlx::query_as(
+/// Looks up the user behind a live (unexpired, unrevoked) opaque BFF token.
+pub(super) async fn resolve_token_user(db: &PgPool, token: &str) -> Option<TokenRow> {
+    sqlx::query_as(
         r#"
         SELECT t.user_id, u.username
         FROM bff_tokens t
         JOIN users u ON u.id = t.user_id
-        WHERE t.token = $1 AND t.expires_at > NOW()
+        WHERE t.token = $1 AND t.expires_at > NOW() AND t.revoked_at IS NULL
+          AND u.disabled_at IS NULL
         "#,
     )
-    .bind(&req.token)
-    .fetch_optional(&state.db)
+    .bind(token)
+    .fetch_optional(db)
     .await
-    .unwrap_or(None);
+    .unwrap_or(None)
+}
 
-    let Some(row) = row else {
-        telemetry::token_operation("introspect", "invalid");
-        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
-    };
+/// The permission names granted to `user_id` through their roles and the roles those inherit
+/// from, and whether their email is verified. Permissions are withheld from unverified accounts when the policy says so.
+pub(super) async fn effective_permissions(
+    db: &PgPool,
+    policy: UnverifiedPolicy,
+    user_id: i64,
+) -> Result<(Vec<String>, bool), sqlx::Error> {
+    let email_verified = email_verification::is_verified(db, user_id).await?;
+    if !email_verified && policy.hides_permissions() {
+        return Ok((Vec::new(), false));
+    }
 
-    let permissions: Vec<String> = sqlx::query_scalar(
+    let permissions = sqlx::query_scalar(
         r#"
-        SELECT permissions.name
-        FROM user_roles
-        JOIN role_permissions ON user_roles.role_id = role_permissions.role_id
+        SELECT DISTINCT permissions.name
+        FROM active_user_roles
+        JOIN role_ancestors ON active_user_roles.role_id = role_ancestors.role_id
+        JOIN role_permissions ON role_ancestors.ancestor_id = role_permissions.role_id
         JOIN permissions ON role_permissions.permission_id = permissions.id
-        WHERE user_roles.user_id = $1
+        WHERE active_user_roles.user_id = $1
         "#,
     )
-    .bind(row.user_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok((permissions, email_verified))
+}
+
+#[tracing::instrument(name = "token.introspect", skip_all)]
+async fn introspect(
+    State(state): State<InternalState>,
+    Json(req): Json<IntrospectReq>,
+) -> impl IntoResponse {
+    let Some(row) = resolve_token_user(&state.db, &req.token).await else {
+        telemetry::token_operation("introspect", "invalid");
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    if let Err(e) = sessions::touch(&state.db, &state.token_lifetimes, &req.token).await {
+        tracing::warn!(user_id = row.user_id, error = %e, "introspect: could not update last_used_at");
+    }
+
+    let (permissions, email_verified) =
+        match effective_permissions(&state.db, state.email_policy, row.user_id).await {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::error!(user_id = row.user_id, error = %e, "introspect: could not load permissions");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
 
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -301,16 +532,12 @@ async fn introspect(
         sub: row.user_id.to_string(),
         preferred_username: row.username.clone(),
         permissions: permissions.clone(),
-        exp: now + 900, // 15 minute JWT lifetime
+        exp: now + state.jwt_keys.ttl_secs() as usize,
         iat: now,
         iss: "milesstorm-auth".to_string(),
     };
 
-    let jwt = match encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    ) {
+    let jwt = match state.jwt_keys.sign(&claims) {
         Ok(t) => t,
         Err(e) => {
             tracing::error!(user_id = row.user_id, error = %e, "JWT encoding failed");
@@ -324,6 +551,7 @@ async fn introspect(
         jwt,
         username: row.username,
         permissions,
+        email_verified,
     })
     .into_response()
 }
@@ -335,29 +563,76 @@ struct RegisterReq {
     username: String,
     email: String,
     password: String,
+    /// Required when registration is invite-only; redeemed in any mode.
+    #[serde(default)]
+    invite_code: Option<String>,
 }
 
 #[tracing::instrument(name = "auth.register", skip_all)]
 async fn register(
     State(state): State<InternalState>,
+    headers: HeaderMap,
     Json(req): Json<RegisterReq>,
 ) -> impl IntoResponse {
+    let invite_code = req.invite_code.as_deref().filter(|c| !c.trim().is_empty());
+    if let Err(e) = invites::check_allowed(state.backend.registration(), invite_code) {
+        tracing::warn!(reason = e.code(), "registration refused");
+        telemetry::token_operation("register", "rejected");
+        return invites::error_response(e, "register");
+    }
+
     let password = req.password.clone();
     let hashed = task::spawn_blocking(move || password_auth::generate_hash(password))
         .await
         .expect("password hashing failed");
 
-    let user: Result<UserRow, sqlx::Error> = sqlx::query_as(
-        "INSERT INTO users (username, email, password) VALUES ($1, $2, $3) RETURNING id, username, password",
-    )
-    .bind(&req.username)
-    .bind(&req.email)
-    .bind(&hashed)
-    .fetch_one(&state.db)
+    let user: Result<UserRow, InviteError> = async {
+        let mut tx = state.db.begin().await?;
+        let u: UserRow = sqlx::query_as(
+            "INSERT INTO users (username, email, password) VALUES ($1, $2, $3) RETURNING id, username, password, disabled_at",
+        )
+        .bind(&req.username)
+        .bind(&req.email)
+        .bind(&hashed)
+        .fetch_one(&mut *tx)
+        .await?;
+        if let Some(code) = invite_code {
+            let event = Event::new("invite.redeem", &headers);
+            invites::redeem(&mut tx, code, u.id, &u.username, Some(&req.email), event).await?;
+        }
+        tx.commit().await?;
+        Ok(u)
+    }
     .await;
 
+    if let Ok(u) = &user {
+        audit::log(
+            &state.db,
+            Event::new("auth.register", &headers)
+                .actor(u.id)
+                .target(format!("user {} (#{})", u.username, u.id))
+                .after(json!({ "username": u.username, "email": req.email })),
+        )
+        .await;
+    }
+
+    if let Ok(u) = &user
+        && let Err(e) = email_verification::send_verification(
+            &state.db,
+            &state.mailer,
+            &state.public_url,
+            u.id,
+            &u.username,
+            &req.email,
+        )
+        .await
+    {
+        // The account exists either way; the user can ask for another link from their profile.
+        tracing::error!(user_id = u.id, error = %e, "failed to issue email verification token");
+    }
+
     match user {
-        Ok(u) => match create_bff_token(&state.db, u.id).await {
+        Ok(u) => match create_bff_token(&state, u.id, "password", &headers).await {
             Ok(bff_token) => {
                 tracing::info!(user_id = u.id, username = %u.username, "registration succeeded");
                 telemetry::token_operation("register", "success");
@@ -373,7 +648,7 @@ async fn register(
                 StatusCode::INTERNAL_SERVER_ERROR.into_response()
             }
         },
-        Err(sqlx::Error::Database(db_err)) => match db_err.constraint() {
+        Err(InviteError::Db(sqlx::Error::Database(db_err))) => match db_err.constraint() {
             Some("users_username_key") => {
                 tracing::warn!(username = %req.username, "registration failed: username already exists");
                 telemetry::token_operation("register", "conflict");
@@ -390,11 +665,16 @@ async fn register(
                 StatusCode::INTERNAL_SERVER_ERROR.into_response()
             }
         },
-        Err(e) => {
+        Err(e @ InviteError::Db(_)) => {
             tracing::error!(username = %req.username, error = %e, "registration failed");
             telemetry::token_operation("register", "error");
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
+        Err(e) => {
+            tracing::warn!(username = %req.username, reason = e.code(), "registration refused");
+            telemetry::token_operation("register", "rejected");
+            invites::error_response(e, "register")
+        }
     }
 }
 
@@ -442,17 +722,28 @@ struct DockerRequestResponse {
     command_result: Option<CommandResult>,
 }
 
-async fn resolve_ark_user(db: &PgPool, token: &str) -> Option<i64> {
+/// The user behind `token` if they may use ark: a BFF session token, or a personal access token
+/// with the `llama` scope.
+async fn resolve_ark_user(db: &PgPool, token: &str, policy: UnverifiedPolicy) -> Option<i64> {
+    if token.starts_with(access_tokens::TOKEN_PREFIX) {
+        return access_tokens::authorize(db, token, "llama", policy).await;
+    }
+
     let row: Option<(i64,)> = sqlx::query_as(
         r#"
         SELECT t.user_id FROM bff_tokens t
-        JOIN user_roles ur ON ur.user_id = t.user_id
-        JOIN role_permissions rp ON rp.role_id = ur.role_id
+        JOIN users u ON u.id = t.user_id
+        JOIN active_user_roles ur ON ur.user_id = t.user_id
+        JOIN role_ancestors ra ON ra.role_id = ur.role_id
+        JOIN role_permissions rp ON rp.role_id = ra.ancestor_id
         JOIN permissions p ON p.id = rp.permission_id
-        WHERE t.token = $1 AND t.expires_at > NOW() AND p.name = 'llama'
+        WHERE t.token = $1 AND t.expires_at > NOW() AND t.revoked_at IS NULL AND p.name = 'llama'
+          AND (NOT $2 OR u.email IS NULL OR u.email_verified_at IS NOT NULL)
+          AND u.disabled_at IS NULL
         "#,
     )
     .bind(token)
+    .bind(policy.hides_permissions())
     .fetch_optional(db)
     .await
     .unwrap_or(None);
@@ -465,14 +756,14 @@ async fn ark_num_players(
     State(state): State<InternalState>,
     Json(req): Json<ArkTokenReq>,
 ) -> impl IntoResponse {
-    let Some(user_id) = resolve_ark_user(&state.db, &req.token).await else {
+    let Some(user_id) = resolve_ark_user(&state.db, &req.token, state.email_policy).await else {
         tracing::warn!("ark num_players denied: missing llama permission");
         telemetry::ark_command("num_players", "denied");
         return (StatusCode::FORBIDDEN, "No ark permission").into_response();
     };
     tracing::debug!(user_id, "ark num_players request");
 
-    let mut builder = reqwest::Client::new().get("http://192.168.1.21:9090/ark/num_players");
+    let mut builder = reqwest::Client::new().get(format!("{}/ark/num_players", state.backend.ark_url()));
     if let Some(tp) = traceparent() {
         builder = builder.header("traceparent", tp);
     }
@@ -501,14 +792,21 @@ async fn ark_num_players(
 #[tracing::instrument(name = "ark.command", skip_all, fields(cmd = %req.cmd))]
 async fn ark_command(
     State(state): State<InternalState>,
+    headers: HeaderMap,
     Json(req): Json<ArkCommandReq>,
 ) -> impl IntoResponse {
-    let Some(user_id) = resolve_ark_user(&state.db, &req.token).await else {
+    let Some(user_id) = resolve_ark_user(&state.db, &req.token, state.email_policy).await else {
         tracing::warn!(cmd = %req.cmd, "ark command denied: missing llama permission");
         telemetry::ark_command(&req.cmd, "denied");
         return (StatusCode::FORBIDDEN, "No ark permission").into_response();
     };
 
+    let via = if req.token.starts_with(access_tokens::TOKEN_PREFIX) {
+        "access_token"
+    } else {
+        "session"
+    };
+
     let cmd = match req.cmd.as_str() {
         "start" | "stop" | "restart" => req.cmd.clone(),
         _ => {
@@ -519,30 +817,37 @@ async fn ark_command(
     };
     tracing::info!(user_id, cmd = %cmd, "ark command issued");
 
-    let mut builder = reqwest::Client::new().get(format!("http://192.168.1.21:9090/ark/{cmd}"));
+    let mut builder = reqwest::Client::new().get(format!("{}/ark/{cmd}", state.backend.ark_url()));
     if let Some(tp) = traceparent() {
         builder = builder.header("traceparent", tp);
     }
-    match builder.send().await {
+    let (outcome, response) = match builder.send().await {
         Ok(resp) => match resp.json::<DockerRequestResponse>().await {
-            Ok(body) => {
-                telemetry::ark_command(&cmd, "success");
-                Json(body).into_response()
-            }
-            Err(e) => {
-                telemetry::ark_command(&cmd, "error");
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
+            Ok(body) => ("success", Json(body).into_response()),
+            Err(e) => (
+                "error",
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            ),
         },
-        Err(_) => {
-            telemetry::ark_command(&cmd, "unreachable");
+        Err(_) => (
+            "unreachable",
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Could not reach ark host",
             )
-                .into_response()
-        }
-    }
+                .into_response(),
+        ),
+    };
+    telemetry::ark_command(&cmd, outcome);
+    audit::log(
+        &state.db,
+        Event::new("ark.command", &headers)
+            .actor(user_id)
+            .target(cmd)
+            .after(json!({ "outcome": outcome, "via": via })),
+    )
+    .await;
+    response
 }
 
 // ---- Admin RBAC endpoints ----
@@ -560,9 +865,9 @@ struct PageQuery {
 fn default_page_limit() -> u32 { 25 }
 
 #[derive(Serialize)]
-struct PagedResp<T: Serialize> {
-    items: Vec<T>,
-    total: i64,
+pub(super) struct PagedResp<T: Serialize> {
+    pub items: Vec<T>,
+    pub total: i64,
 }
 
 #[derive(Serialize)]
@@ -570,26 +875,42 @@ struct AdminUserResp {
     id: i64,
     username: String,
     email: Option<String>,
-    roles: Vec<RoleResp>,
+    mfa_enabled: bool,
+    disabled_at: Option<DateTime<Utc>>,
+    disabled_reason: Option<String>,
+    roles: Vec<UserRoleResp>,
 }
 
-#[derive(Serialize, Clone)]
-struct RoleResp {
+#[derive(Serialize)]
+struct UserRoleResp {
     id: i32,
     name: String,
+    granted_at: Option<DateTime<Utc>>,
+    /// Unset for grants that don't expire.
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Clone)]
+pub(super) struct RoleResp {
+    pub id: i32,
+    pub name: String,
 }
 
 #[derive(Serialize)]
-struct PermissionResp {
-    id: i32,
-    name: String,
+pub(super) struct PermissionResp {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
 }
 
 #[derive(Serialize)]
-struct AdminRoleResp {
-    id: i32,
-    name: String,
-    permissions: Vec<PermissionResp>,
+pub(super) struct AdminRoleResp {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<PermissionResp>,
+    /// The roles this one inherits permissions from.
+    pub parents: Vec<RoleResp>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -597,6 +918,9 @@ struct UserRow2 {
     id: i64,
     username: String,
     email: Option<String>,
+    mfa_enabled: bool,
+    disabled_at: Option<DateTime<Utc>>,
+    disabled_reason: Option<String>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -604,12 +928,15 @@ struct UserRoleRow {
     user_id: i32, // user_roles.user_id is INT (not BIGINT), matching the schema
     role_id: i32,
     role_name: String,
+    granted_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(sqlx::FromRow)]
 struct RoleRow {
     id: i32,
     name: String,
+    description: Option<String>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -617,17 +944,27 @@ struct RolePermRow {
     role_id: i32,
     permission_id: i32,
     permission_name: String,
+    permission_description: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RoleParentRow {
+    role_id: i32,
+    parent_id: i32,
+    parent_name: String,
 }
 
 #[derive(sqlx::FromRow)]
 struct PermRow {
     id: i32,
     name: String,
+    description: Option<String>,
 }
 
 #[tracing::instrument(name = "admin.list_users", skip_all)]
 async fn admin_list_users(
     State(state): State<InternalState>,
+    _actor: AdminActor,
     Query(q): Query<PageQuery>,
 ) -> impl IntoResponse {
     let search = format!("%{}%", q.search.to_lowercase());
@@ -650,9 +987,11 @@ async fn admin_list_users(
     };
 
     let users: Vec<UserRow2> = match sqlx::query_as(
-        "SELECT id, username, email FROM users \
-         WHERE LOWER(username) LIKE $1 OR LOWER(COALESCE(email, '')) LIKE $1 \
-         ORDER BY username LIMIT $2 OFFSET $3",
+        "SELECT u.id, u.username, u.email, (t.enabled_at IS NOT NULL) AS mfa_enabled, \
+         u.disabled_at, u.disabled_reason \
+         FROM users u LEFT JOIN user_totp t ON t.user_id = u.id \
+         WHERE LOWER(u.username) LIKE $1 OR LOWER(COALESCE(u.email, '')) LIKE $1 \
+         ORDER BY u.username LIMIT $2 OFFSET $3",
     )
     .bind(&search)
     .bind(limit)
@@ -673,8 +1012,8 @@ async fn admin_list_users(
         vec![]
     } else {
         match sqlx::query_as(
-            "SELECT ur.user_id, r.id as role_id, r.name as role_name \
-             FROM user_roles ur JOIN roles r ON r.id = ur.role_id \
+            "SELECT ur.user_id, r.id as role_id, r.name as role_name, ur.granted_at, ur.expires_at \
+             FROM active_user_roles ur JOIN roles r ON r.id = ur.role_id \
              WHERE ur.user_id = ANY($1)",
         )
         .bind(&user_ids)
@@ -695,9 +1034,22 @@ async fn admin_list_users(
             let roles = user_roles
                 .iter()
                 .filter(|ur| ur.user_id as i64 == u.id)
-                .map(|ur| RoleResp { id: ur.role_id, name: ur.role_name.clone() })
+                .map(|ur| UserRoleResp {
+                    id: ur.role_id,
+                    name: ur.role_name.clone(),
+                    granted_at: ur.granted_at,
+                    expires_at: ur.expires_at,
+                })
                 .collect();
-            AdminUserResp { id: u.id, username: u.username, email: u.email, roles }
+            AdminUserResp {
+                id: u.id,
+                username: u.username,
+                email: u.email,
+                mfa_enabled: u.mfa_enabled,
+                disabled_at: u.disabled_at,
+                disabled_reason: u.disabled_reason,
+                roles,
+            }
         })
         .collect();
 
@@ -707,6 +1059,7 @@ async fn admin_list_users(
 #[tracing::instrument(name = "admin.list_roles", skip_all)]
 async fn admin_list_roles(
     State(state): State<InternalState>,
+    _actor: AdminActor,
     Query(q): Query<PageQuery>,
 ) -> impl IntoResponse {
     let search = format!("%{}%", q.search.to_lowercase());
@@ -728,7 +1081,7 @@ async fn admin_list_roles(
     };
 
     let roles: Vec<RoleRow> = match sqlx::query_as(
-        "SELECT id, name FROM roles WHERE LOWER(name) LIKE $1 ORDER BY name LIMIT $2 OFFSET $3",
+        "SELECT id, name, description FROM roles WHERE LOWER(name) LIKE $1 ORDER BY name LIMIT $2 OFFSET $3",
     )
     .bind(&search)
     .bind(limit)
@@ -749,7 +1102,8 @@ async fn admin_list_roles(
         vec![]
     } else {
         match sqlx::query_as(
-            "SELECT rp.role_id, p.id as permission_id, p.name as permission_name \
+            "SELECT rp.role_id, p.id as permission_id, p.name as permission_name, \
+             p.description as permission_description \
              FROM role_permissions rp JOIN permissions p ON p.id = rp.permission_id \
              WHERE rp.role_id = ANY($1)",
         )
@@ -764,6 +1118,25 @@ async fn admin_list_roles(
             }
         }
     };
+    let role_parents: Vec<RoleParentRow> = if role_ids.is_empty() {
+        vec![]
+    } else {
+        match sqlx::query_as(
+            "SELECT rp.role_id, r.id as parent_id, r.name as parent_name \
+             FROM role_parents rp JOIN roles r ON r.id = rp.parent_id \
+             WHERE rp.role_id = ANY($1) ORDER BY r.name",
+        )
+        .bind(&role_ids)
+        .fetch_all(&state.db)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::error!(error = %e, "admin_list_roles: parents db error");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    };
 
     let items: Vec<AdminRoleResp> = roles
         .into_iter()
@@ -771,9 +1144,18 @@ async fn admin_list_roles(
             let permissions = role_perms
                 .iter()
                 .filter(|rp| rp.role_id == r.id)
-                .map(|rp| PermissionResp { id: rp.permission_id, name: rp.permission_name.clone() })
+                .map(|rp| PermissionResp {
+                    id: rp.permission_id,
+                    name: rp.permission_name.clone(),
+                    description: rp.permission_description.clone(),
+                })
+                .collect();
+            let parents = role_parents
+                .iter()
+                .filter(|rp| rp.role_id == r.id)
+                .map(|rp| RoleResp { id: rp.parent_id, name: rp.parent_name.clone() })
                 .collect();
-            AdminRoleResp { id: r.id, name: r.name, permissions }
+            AdminRoleResp { id: r.id, name: r.name, description: r.description, permissions, parents }
         })
         .collect();
 
@@ -783,14 +1165,23 @@ async fn admin_list_roles(
 /// Returns all roles (without permissions) for use in assignment dropdowns.
 /// Capped at 1000 — roles are admin-defined so this is not expected to be hit.
 #[tracing::instrument(name = "admin.list_all_roles", skip_all)]
-async fn admin_list_all_roles(State(state): State<InternalState>) -> impl IntoResponse {
-    match sqlx::query_as::<_, RoleRow>("SELECT id, name FROM roles ORDER BY name LIMIT 1000")
+async fn admin_list_all_roles(
+    State(state): State<InternalState>,
+    _actor: AdminActor,
+) -> impl IntoResponse {
+    match sqlx::query_as::<_, RoleRow>("SELECT id, name, description FROM roles ORDER BY name LIMIT 1000")
         .fetch_all(&state.db)
         .await
     {
         Ok(rows) => Json(
             rows.into_iter()
-                .map(|r| AdminRoleResp { id: r.id, name: r.name, permissions: vec![] })
+                .map(|r| AdminRoleResp {
+                    id: r.id,
+                    name: r.name,
+                    description: r.description,
+                    permissions: vec![],
+                    parents: vec![],
+                })
                 .collect::<Vec<_>>(),
         )
         .into_response(),
@@ -802,14 +1193,17 @@ async fn admin_list_all_roles(State(state): State<InternalState>) -> impl IntoRe
 }
 
 #[tracing::instrument(name = "admin.list_permissions", skip_all)]
-async fn admin_list_permissions(State(state): State<InternalState>) -> impl IntoResponse {
-    match sqlx::query_as::<_, PermRow>("SELECT id, name FROM permissions ORDER BY name")
+async fn admin_list_permissions(
+    State(state): State<InternalState>,
+    _actor: AdminActor,
+) -> impl IntoResponse {
+    match sqlx::query_as::<_, PermRow>("SELECT id, name, description FROM permissions ORDER BY name")
         .fetch_all(&state.db)
         .await
     {
         Ok(rows) => Json(
             rows.into_iter()
-                .map(|p| PermissionResp { id: p.id, name: p.name })
+                .map(|p| PermissionResp { id: p.id, name: p.name, description: p.description })
                 .collect::<Vec<_>>(),
         )
         .into_response(),
@@ -820,96 +1214,238 @@ async fn admin_list_permissions(State(state): State<InternalState>) -> impl Into
     }
 }
 
+/// How long a role grant lasts; at most one of the two may be set. Without a body, or with
+/// neither, the grant doesn't expire.
+#[derive(Deserialize, Default)]
+struct AssignRoleReq {
+    expires_at: Option<DateTime<Utc>>,
+    expires_in_secs: Option<i64>,
+}
+
+impl AssignRoleReq {
+    fn expires_at(&self) -> Result<Option<DateTime<Utc>>, &'static str> {
+        let expires_at = match (self.expires_at, self.expires_in_secs) {
+            (Some(_), Some(_)) => return Err("Give expires_at or expires_in_secs, not both"),
+            (Some(at), None) => Some(at),
+            (None, Some(secs)) => chrono::Duration::try_seconds(secs).map(|d| Utc::now() + d),
+            (None, None) => return Ok(None),
+        };
+        match expires_at {
+            Some(at) if at > Utc::now() => Ok(Some(at)),
+            _ => Err("The grant must expire in the future"),
+        }
+    }
+}
+
+/// Grants a role, optionally until a given time. Granting a role the user already has replaces
+/// that grant's expiry.
 #[tracing::instrument(name = "admin.assign_user_role", skip_all, fields(user_id, role_id))]
 async fn admin_assign_user_role(
     State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
     Path((user_id, role_id)): Path<(i64, i32)>,
+    req: Option<Json<AssignRoleReq>>,
 ) -> impl IntoResponse {
-    match sqlx::query(
-        "INSERT INTO user_roles (user_id, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let expires_at = match req.expires_at() {
+        Ok(at) => at,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    if state.email_policy.blocks_role_grants() {
+        match email_verification::is_verified(&state.db, user_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return (StatusCode::CONFLICT, "User has not verified their email").into_response();
+            }
+            Err(e) => {
+                tracing::error!(error = %e, user_id, "admin_assign_user_role: db error");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    let event = Event::new("rbac.user_role.assign", &headers).actor(actor.user_id);
+    let insert = sqlx::query(
+        r#"
+        INSERT INTO user_roles (user_id, role_id, granted_by, granted_at, expires_at)
+        VALUES ($1, $2, $3, NOW(), $4)
+        ON CONFLICT (user_id, role_id) DO UPDATE
+        SET granted_by = EXCLUDED.granted_by, granted_at = EXCLUDED.granted_at,
+            expires_at = EXCLUDED.expires_at
+        WHERE user_roles.expires_at IS DISTINCT FROM EXCLUDED.expires_at
+        "#,
     )
     .bind(user_id)
     .bind(role_id)
-    .execute(&state.db)
-    .await
-    {
-        Ok(_) => {
-            tracing::info!(user_id, role_id, "assigned role to user");
+    .bind(actor.user_id)
+    .bind(expires_at);
+    match change_user_roles(&state.db, user_id, event, insert).await {
+        Ok(true) => {
+            tracing::info!(actor = actor.user_id, user_id, role_id, "assigned role to user");
             StatusCode::NO_CONTENT.into_response()
         }
-        Err(e) => {
-            tracing::error!(error = %e, user_id, role_id, "admin_assign_user_role: db error");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No such user").into_response(),
+        Err(e) => roles::error_response(e, "admin_assign_user_role"),
     }
 }
 
 #[tracing::instrument(name = "admin.revoke_user_role", skip_all, fields(user_id, role_id))]
 async fn admin_revoke_user_role(
     State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
     Path((user_id, role_id)): Path<(i64, i32)>,
 ) -> impl IntoResponse {
-    match sqlx::query("DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2")
+    let event = Event::new("rbac.user_role.revoke", &headers).actor(actor.user_id);
+    let delete = sqlx::query("DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2")
         .bind(user_id)
-        .bind(role_id)
-        .execute(&state.db)
-        .await
-    {
-        Ok(_) => {
-            tracing::info!(user_id, role_id, "revoked role from user");
+        .bind(role_id);
+    match change_user_roles(&state.db, user_id, event, delete).await {
+        Ok(true) => {
+            tracing::info!(actor = actor.user_id, user_id, role_id, "revoked role from user");
             StatusCode::NO_CONTENT.into_response()
         }
-        Err(e) => {
-            tracing::error!(error = %e, user_id, role_id, "admin_revoke_user_role: db error");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No such user").into_response(),
+        Err(e) => roles::error_response(e, "admin_revoke_user_role"),
     }
 }
 
 #[tracing::instrument(name = "admin.assign_role_permission", skip_all, fields(role_id, permission_id))]
 async fn admin_assign_role_permission(
     State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
     Path((role_id, permission_id)): Path<(i32, i32)>,
 ) -> impl IntoResponse {
-    match sqlx::query(
+    let event = Event::new("rbac.role_permission.assign", &headers).actor(actor.user_id);
+    let insert = sqlx::query(
         "INSERT INTO role_permissions (role_id, permission_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
     )
     .bind(role_id)
-    .bind(permission_id)
-    .execute(&state.db)
-    .await
-    {
-        Ok(_) => {
-            tracing::info!(role_id, permission_id, "assigned permission to role");
+    .bind(permission_id);
+    match change_role_permissions(&state.db, role_id, event, insert).await {
+        Ok(true) => {
+            tracing::info!(actor = actor.user_id, role_id, permission_id, "assigned permission to role");
             StatusCode::NO_CONTENT.into_response()
         }
-        Err(e) => {
-            tracing::error!(error = %e, role_id, permission_id, "admin_assign_role_permission: db error");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No such role").into_response(),
+        Err(e) => roles::error_response(e, "admin_assign_role_permission"),
     }
 }
 
 #[tracing::instrument(name = "admin.revoke_role_permission", skip_all, fields(role_id, permission_id))]
 async fn admin_revoke_role_permission(
     State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
     Path((role_id, permission_id)): Path<(i32, i32)>,
 ) -> impl IntoResponse {
-    match sqlx::query(
+    let event = Event::new("rbac.role_permission.revoke", &headers).actor(actor.user_id);
+    let delete = sqlx::query(
         "DELETE FROM role_permissions WHERE role_id = $1 AND permission_id = $2",
     )
     .bind(role_id)
-    .bind(permission_id)
-    .execute(&state.db)
-    .await
-    {
-        Ok(_) => {
-            tracing::info!(role_id, permission_id, "revoked permission from role");
+    .bind(permission_id);
+    match change_role_permissions(&state.db, role_id, event, delete).await {
+        Ok(true) => {
+            tracing::info!(actor = actor.user_id, role_id, permission_id, "revoked permission from role");
             StatusCode::NO_CONTENT.into_response()
         }
-        Err(e) => {
-            tracing::error!(error = %e, role_id, permission_id, "admin_revoke_role_permission: db error");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No such role").into_response(),
+        Err(e) => roles::error_response(e, "admin_revoke_role_permission"),
+    }
+}
+
+type Change<'q> = sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>;
+
+/// Runs `change` against a user's roles and audits the role names before and after, in one
+/// transaction. Returns false if the user does not exist; no-op changes are not audited. Refused
+/// if it would leave nobody with a non-expiring grant of `manage_permissions`.
+async fn change_user_roles(
+    db: &PgPool,
+    user_id: i64,
+    event: Event,
+    change: Change<'_>,
+) -> Result<bool, RbacError> {
+    let mut tx = db.begin().await?;
+    roles::lock_rbac_changes(&mut tx).await?;
+    let Some(username) = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    let before = user_role_names(&mut tx, user_id).await?;
+    change.execute(&mut *tx).await?;
+    let after = user_role_names(&mut tx, user_id).await?;
+
+    if before != after {
+        roles::ensure_permission_admin_remains(&mut tx).await?;
+        let event = event
+            .target(format!("user {username} (#{user_id})"))
+            .before(json!({ "roles": before }))
+            .after(json!({ "roles": after }));
+        audit::record(&mut *tx, event).await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+async fn user_role_names(conn: &mut PgConnection, user_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT r.name || COALESCE(' until ' || to_char(ur.expires_at AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI "UTC"'), '')
+        FROM active_user_roles ur JOIN roles r ON r.id = ur.role_id
+        WHERE ur.user_id = $1 ORDER BY r.name
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Like [`change_user_roles`], for a role's permissions.
+async fn change_role_permissions(
+    db: &PgPool,
+    role_id: i32,
+    event: Event,
+    change: Change<'_>,
+) -> Result<bool, RbacError> {
+    let mut tx = db.begin().await?;
+    roles::lock_rbac_changes(&mut tx).await?;
+    let Some(name) = sqlx::query_scalar::<_, String>("SELECT name FROM roles WHERE id = $1 FOR UPDATE")
+        .bind(role_id)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    let before = role_permission_names(&mut tx, role_id).await?;
+    change.execute(&mut *tx).await?;
+    let after = role_permission_names(&mut tx, role_id).await?;
+
+    if before != after {
+        roles::ensure_permission_admin_remains(&mut tx).await?;
+        let event = event
+            .target(format!("role {name} (#{role_id})"))
+            .before(json!({ "permissions": before }))
+            .after(json!({ "permissions": after }));
+        audit::record(&mut *tx, event).await?;
     }
+    tx.commit().await?;
+    Ok(true)
+}
+
+async fn role_permission_names(conn: &mut PgConnection, role_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT p.name FROM role_permissions rp JOIN permissions p ON p.id = rp.permission_id WHERE rp.role_id = $1 ORDER BY p.name",
+    )
+    .bind(role_id)
+    .fetch_all(conn)
+    .await
 }
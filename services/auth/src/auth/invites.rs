@@ -0,0 +1,482 @@
+//! Invite codes and who may create an account at all.
+//!
+//! [`RegistrationMode`] applies to every way of creating an account: password registration, the
+//! legacy form route, and the first login with an upstream provider. In `invite_only` mode each of
+//! them must redeem a code, in the same transaction that creates the user. A code given in `open`
+//! mode is redeemed too, so its roles are granted. Existing accounts are never affected.
+//!
+//! Codes are shown to the admin once, when created; only their hash is stored.
+
+use std::str::FromStr;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgConnection;
+
+use super::audit::{self, AdminActor, Event};
+use super::internal::{InternalState, PagedResp};
+use super::password_reset::hash_token;
+
+/// Unambiguous characters, so codes survive being read out or retyped.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 12;
+
+/// Who may create an account, from `REGISTRATION_MODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    Open,
+    /// New accounts must redeem an invite code.
+    InviteOnly,
+    /// No new accounts.
+    Closed,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "invite_only" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            _ => Err("expected open, invite_only or closed"),
+        }
+    }
+}
+
+/// Why an account could not be created. Shown to the BFF as the code in [`InviteError::code`].
+#[derive(Debug, thiserror::Error)]
+pub enum InviteError {
+    #[error("Registration is closed")]
+    Closed,
+
+    #[error("An invite code is required to sign up")]
+    Required,
+
+    #[error("The invite code is unknown, expired, revoked or used up")]
+    Invalid,
+
+    #[error("The invite code is for a different email address")]
+    EmailMismatch,
+
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+impl InviteError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Closed => "registration_closed",
+            Self::Required => "invite_required",
+            Self::Invalid => "invalid_invite",
+            Self::EmailMismatch => "invite_email_mismatch",
+            Self::Db(_) => "error",
+        }
+    }
+}
+
+/// `403` with the error's code, or `500` for database errors, logged under `context`.
+pub(super) fn error_response(e: InviteError, context: &str) -> Response {
+    match e {
+        InviteError::Db(e) => {
+            tracing::error!(error = %e, "{context}: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        e => (StatusCode::FORBIDDEN, e.code()).into_response(),
+    }
+}
+
+/// Whether `mode` lets an account be created with `code` (blank meaning none), before checking
+/// the code itself.
+pub(super) fn check_allowed(mode: RegistrationMode, code: Option<&str>) -> Result<(), InviteError> {
+    match mode {
+        RegistrationMode::Closed => Err(InviteError::Closed),
+        RegistrationMode::InviteOnly if code.is_none_or(|c| c.trim().is_empty()) => {
+            Err(InviteError::Required)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Codes are compared ignoring case, dashes and spaces.
+fn hash_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    hash_token(&normalized)
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::rng();
+    let chars: Vec<char> = (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[rng.random_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    chars
+        .chunks(4)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[derive(sqlx::FromRow)]
+struct RedeemRow {
+    id: i64,
+    hint: String,
+    created_by: Option<i64>,
+    email: Option<String>,
+}
+
+/// Redeems `code` for the just-created user inside the transaction creating them: counts the use,
+/// records the redemption and grants the code's roles on behalf of whoever created it.
+pub(super) async fn redeem(
+    conn: &mut PgConnection,
+    code: &str,
+    user_id: i64,
+    username: &str,
+    email: Option<&str>,
+    event: Event,
+) -> Result<(), InviteError> {
+    let invite: RedeemRow = sqlx::query_as(
+        r#"
+        SELECT id, hint, created_by, email FROM invite_codes
+        WHERE code_hash = $1 AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+          AND (max_uses IS NULL OR use_count < max_uses)
+        FOR UPDATE
+        "#,
+    )
+    .bind(hash_code(code))
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(InviteError::Invalid)?;
+
+    if let Some(bound) = &invite.email
+        && !email.is_some_and(|e| e.trim().eq_ignore_ascii_case(bound))
+    {
+        return Err(InviteError::EmailMismatch);
+    }
+
+    sqlx::query("UPDATE invite_codes SET use_count = use_count + 1 WHERE id = $1")
+        .bind(invite.id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT INTO invite_redemptions (invite_id, user_id, username) VALUES ($1, $2, $3)")
+        .bind(invite.id)
+        .bind(user_id)
+        .bind(username)
+        .execute(&mut *conn)
+        .await?;
+    let roles: Vec<String> = sqlx::query_scalar(
+        r#"
+        WITH granted AS (
+            INSERT INTO user_roles (user_id, role_id, granted_by, granted_at)
+            SELECT $1, role_id, $2, NOW() FROM invite_code_roles WHERE invite_id = $3
+            ON CONFLICT DO NOTHING
+            RETURNING role_id
+        )
+        SELECT r.name FROM granted g JOIN roles r ON r.id = g.role_id ORDER BY r.name
+        "#,
+    )
+    .bind(user_id)
+    .bind(invite.created_by)
+    .bind(invite.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let event = event
+        .actor(user_id)
+        .target(format!("invite …{} (#{})", invite.hint, invite.id))
+        .after(json!({ "user": username, "roles": roles }));
+    audit::record(&mut *conn, event).await?;
+    Ok(())
+}
+
+// ---- Endpoints ----
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/registration", get(registration))
+        .route(
+            "/internal/admin/invites",
+            get(list_invites).post(create_invite),
+        )
+        .route("/internal/admin/invites/{invite_id}", delete(revoke_invite))
+}
+
+/// The registration mode, so the BFF can ask for an invite code or hide signup.
+#[tracing::instrument(name = "auth.registration", skip_all)]
+async fn registration(State(state): State<InternalState>) -> impl IntoResponse {
+    Json(json!({ "mode": state.backend.registration() }))
+}
+
+#[derive(Deserialize)]
+struct CreateInviteReq {
+    /// Unset for unlimited uses.
+    max_uses: Option<i32>,
+    /// Unset for a code that doesn't expire.
+    expires_in_secs: Option<i64>,
+    email: Option<String>,
+    #[serde(default)]
+    role_ids: Vec<i32>,
+    note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreatedInviteResp {
+    id: i64,
+    /// The code itself. It can't be retrieved again.
+    code: String,
+}
+
+fn blank_to_none(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+#[tracing::instrument(name = "admin.create_invite", skip_all)]
+async fn create_invite(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Json(req): Json<CreateInviteReq>,
+) -> impl IntoResponse {
+    if req.max_uses.is_some_and(|n| n < 1) {
+        return (StatusCode::BAD_REQUEST, "max_uses must be at least 1").into_response();
+    }
+    let expires_at = match req.expires_in_secs {
+        None => None,
+        Some(secs) => match chrono::Duration::try_seconds(secs) {
+            Some(d) if secs > 0 => Some(Utc::now() + d),
+            _ => {
+                return (StatusCode::BAD_REQUEST, "The code must expire in the future")
+                    .into_response();
+            }
+        },
+    };
+    let email = blank_to_none(req.email);
+    let note = blank_to_none(req.note);
+    let code = generate_code();
+    let hint = code[code.len() - 4..].to_string();
+
+    let event = Event::new("invite.create", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO invite_codes (code_hash, hint, note, created_by, expires_at, max_uses, email)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+        )
+        .bind(hash_code(&code))
+        .bind(&hint)
+        .bind(&note)
+        .bind(actor.user_id)
+        .bind(expires_at)
+        .bind(req.max_uses)
+        .bind(&email)
+        .fetch_one(&mut *tx)
+        .await?;
+        let roles: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH added AS (
+                INSERT INTO invite_code_roles (invite_id, role_id)
+                SELECT $1, UNNEST($2::INT[])
+                ON CONFLICT DO NOTHING
+                RETURNING role_id
+            )
+            SELECT r.name FROM added a JOIN roles r ON r.id = a.role_id ORDER BY r.name
+            "#,
+        )
+        .bind(id)
+        .bind(&req.role_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let event = event.target(format!("invite …{hint} (#{id})")).after(json!({
+            "note": note,
+            "expires_at": expires_at,
+            "max_uses": req.max_uses,
+            "email": email,
+            "roles": roles,
+        }));
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(id)
+    }
+    .await;
+
+    match result {
+        Ok(id) => {
+            tracing::info!(actor = actor.user_id, invite_id = id, "created invite code");
+            (StatusCode::CREATED, Json(CreatedInviteResp { id, code })).into_response()
+        }
+        Err(sqlx::Error::Database(db)) if db.is_foreign_key_violation() => {
+            (StatusCode::BAD_REQUEST, "Unknown role").into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "create_invite: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Revoking is idempotent and keeps the code's history.
+#[tracing::instrument(name = "admin.revoke_invite", skip_all, fields(invite_id))]
+async fn revoke_invite(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(invite_id): Path<i64>,
+) -> impl IntoResponse {
+    let event = Event::new("invite.revoke", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let revoked: Option<(String, bool)> = sqlx::query_as(
+            r#"
+            UPDATE invite_codes SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE id = $1
+            RETURNING hint, revoked_at = NOW()
+            "#,
+        )
+        .bind(invite_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some((hint, true)) = &revoked {
+            audit::record(&mut *tx, event.target(format!("invite …{hint} (#{invite_id})"))).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(revoked.is_some())
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            tracing::info!(actor = actor.user_id, invite_id, "revoked invite code");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "revoke_invite: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    page: u32,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+fn default_limit() -> u32 {
+    25
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct InviteResp {
+    id: i64,
+    hint: String,
+    note: Option<String>,
+    created_by: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    max_uses: Option<i32>,
+    use_count: i32,
+    email: Option<String>,
+    revoked_at: Option<DateTime<Utc>>,
+    /// `active`, `revoked`, `expired` or `used_up`.
+    status: String,
+    roles: Vec<String>,
+    #[sqlx(skip)]
+    redemptions: Vec<RedemptionResp>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct RedemptionResp {
+    #[serde(skip)]
+    invite_id: i64,
+    user_id: Option<i64>,
+    username: String,
+    redeemed_at: DateTime<Utc>,
+}
+
+/// Newest first, with who redeemed each code.
+#[tracing::instrument(name = "admin.list_invites", skip_all)]
+async fn list_invites(
+    State(state): State<InternalState>,
+    _actor: AdminActor,
+    Query(q): Query<ListQuery>,
+) -> impl IntoResponse {
+    let limit = q.limit.clamp(1, 100) as i64;
+    let offset = q.page as i64 * limit;
+
+    let result = async {
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM invite_codes")
+            .fetch_one(&state.db)
+            .await?;
+        let mut items: Vec<InviteResp> = sqlx::query_as(
+            r#"
+            SELECT i.id, i.hint, i.note, u.username AS created_by, i.created_at, i.expires_at,
+                   i.max_uses, i.use_count, i.email, i.revoked_at,
+                   CASE
+                       WHEN i.revoked_at IS NOT NULL THEN 'revoked'
+                       WHEN i.expires_at <= NOW() THEN 'expired'
+                       WHEN i.use_count >= i.max_uses THEN 'used_up'
+                       ELSE 'active'
+                   END AS status,
+                   ARRAY(
+                       SELECT r.name FROM invite_code_roles ir JOIN roles r ON r.id = ir.role_id
+                       WHERE ir.invite_id = i.id ORDER BY r.name
+                   ) AS roles
+            FROM invite_codes i
+            LEFT JOIN users u ON u.id = i.created_by
+            ORDER BY i.created_at DESC, i.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&state.db)
+        .await?;
+
+        let ids: Vec<i64> = items.iter().map(|i| i.id).collect();
+        let redemptions: Vec<RedemptionResp> = sqlx::query_as(
+            r#"
+            SELECT invite_id, user_id, username, redeemed_at FROM invite_redemptions
+            WHERE invite_id = ANY($1) ORDER BY redeemed_at
+            "#,
+        )
+        .bind(&ids)
+        .fetch_all(&state.db)
+        .await?;
+        for redemption in redemptions {
+            if let Some(invite) = items.iter_mut().find(|i| i.id == redemption.invite_id) {
+                invite.redemptions.push(redemption);
+            }
+        }
+        Ok::<_, sqlx::Error>(PagedResp { items, total })
+    }
+    .await;
+
+    match result {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "list_invites: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Signing keys for the JWTs handed out by `/internal/token/introspect`.
+//!
+//! JWTs are signed with Ed25519 (`EdDSA`) and carry the signing key's `kid`, so any service can
+//! verify them against the public keys at `/.well-known/jwks.json` without holding a secret.
+//! Keys live in `jwt_signing_keys`. `auth rotate-jwt-key` adds a new active key and moves the old
+//! one to `retiring`: it stops signing but stays published until every JWT it signed has expired,
+//! after which it is marked `retired` and dropped from the JWKS. Each replica reloads the key set
+//! every minute, so a rotation made from any pod or from the CLI propagates on its own.
+
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, EncodingKey, Header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use ulid::Ulid;
+
+/// How much longer than the JWT lifetime a retiring key stays published, for clock skew and
+/// verifiers that cache the JWKS.
+const RETIRE_SLACK_SECS: u64 = 900;
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtKeyError {
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+
+    #[error("could not generate an Ed25519 key")]
+    KeyGeneration,
+
+    #[error("stored key {0} is not a valid Ed25519 PKCS#8 document")]
+    InvalidKey(String),
+
+    #[error("no active JWT signing key")]
+    NoActiveKey,
+
+    #[error(transparent)]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+}
+
+#[derive(sqlx::FromRow)]
+struct KeyRow {
+    kid: String,
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+    state: String,
+}
+
+struct Loaded {
+    signing: Option<(String, EncodingKey)>,
+    jwks: JwkSet,
+}
+
+/// The current key set, shared by every handler and refreshed in the background.
+#[derive(Clone)]
+pub struct JwtKeys {
+    db: PgPool,
+    loaded: Arc<RwLock<Loaded>>,
+    /// Lifetime of the JWTs signed with these keys, from `JWT_TTL_SECS`.
+    ttl_secs: u64,
+}
+
+impl JwtKeys {
+    /// Loads the key set, generating the first key if the table is empty.
+    pub async fn load(db: PgPool, ttl_secs: u64) -> Result<Self, JwtKeyError> {
+        let has_active: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM jwt_signing_keys WHERE state = 'active')")
+                .fetch_one(&db)
+                .await?;
+        if !has_active {
+            match insert_key(&db).await {
+                Ok(kid) => tracing::info!(%kid, "generated initial jwt signing key"),
+                // Another replica got there first.
+                Err(JwtKeyError::Db(sqlx::Error::Database(e))) if e.is_unique_violation() => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let keys = Self {
+            db,
+            loaded: Arc::new(RwLock::new(Loaded {
+                signing: None,
+                jwks: JwkSet { keys: Vec::new() },
+            })),
+            ttl_secs,
+        };
+        keys.reload().await?;
+        Ok(keys)
+    }
+
+    async fn reload(&self) -> Result<(), JwtKeyError> {
+        let rows: Vec<KeyRow> = sqlx::query_as(
+            r#"
+            SELECT kid, private_key, public_key, state FROM jwt_signing_keys
+            WHERE state IN ('active', 'retiring')
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut signing = None;
+        let mut jwks = Vec::with_capacity(rows.len());
+        for row in rows {
+            if row.state == "active" {
+                Ed25519KeyPair::from_pkcs8(&row.private_key)
+                    .map_err(|_| JwtKeyError::InvalidKey(row.kid.clone()))?;
+                signing = Some((row.kid.clone(), EncodingKey::from_ed_der(&row.private_key)));
+            }
+            jwks.push(public_jwk(&row.kid, &row.public_key));
+        }
+
+        let mut loaded = self.loaded.write().expect("jwt key set lock poisoned");
+        loaded.signing = signing;
+        loaded.jwks = JwkSet { keys: jwks };
+        Ok(())
+    }
+
+    /// Retires keys whose JWTs have all expired and reloads the key set, once a minute.
+    pub fn spawn_refresh(&self) {
+        let keys = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                if let Err(e) = retire_expired(&keys.db, keys.ttl_secs + RETIRE_SLACK_SECS).await {
+                    tracing::warn!(error = %e, "failed to retire old jwt keys");
+                }
+                if let Err(e) = keys.reload().await {
+                    tracing::error!(error = %e, "failed to reload jwt keys");
+                }
+            }
+        });
+    }
+
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    /// Signs `claims` with the active key, naming it in the `kid` header.
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, JwtKeyError> {
+        let loaded = self.loaded.read().expect("jwt key set lock poisoned");
+        let (kid, key) = loaded.signing.as_ref().ok_or(JwtKeyError::NoActiveKey)?;
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.clone());
+        Ok(encode(&header, claims, key)?)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        self.loaded
+            .read()
+            .expect("jwt key set lock poisoned")
+            .jwks
+            .clone()
+    }
+}
+
+fn public_jwk(kid: &str, public_key: &[u8]) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(public_key),
+        }),
+    }
+}
+
+/// Generates a key and stores it as the active one. Fails with a unique violation if another key
+/// is already active.
+async fn insert_key<'e>(db: impl sqlx::PgExecutor<'e>) -> Result<String, JwtKeyError> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| JwtKeyError::KeyGeneration)?;
+    let pair =
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|_| JwtKeyError::KeyGeneration)?;
+    let kid = Ulid::new().to_string();
+
+    sqlx::query(
+        "INSERT INTO jwt_signing_keys (kid, private_key, public_key) VALUES ($1, $2, $3)",
+    )
+    .bind(&kid)
+    .bind(pkcs8.as_ref())
+    .bind(pair.public_key().as_ref())
+    .execute(db)
+    .await?;
+    Ok(kid)
+}
+
+/// Retires keys that have been retiring for longer than `after_secs`.
+async fn retire_expired(db: &PgPool, after_secs: u64) -> Result<(), sqlx::Error> {
+    let retired = sqlx::query(
+        r#"
+        UPDATE jwt_signing_keys SET state = 'retired', retired_at = NOW()
+        WHERE state = 'retiring' AND retiring_at < NOW() - make_interval(secs => $1)
+        "#,
+    )
+    .bind(after_secs as f64)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if retired > 0 {
+        tracing::info!(count = retired, "retired jwt signing keys");
+    }
+    Ok(())
+}
+
+/// Makes a freshly generated key the active one and moves the current active key to `retiring`.
+/// JWTs signed by the old key keep verifying until they expire. Returns the new `kid`.
+pub async fn rotate(db: &PgPool) -> Result<String, JwtKeyError> {
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        "UPDATE jwt_signing_keys SET state = 'retiring', retiring_at = NOW() WHERE state = 'active'",
+    )
+    .execute(&mut *tx)
+    .await?;
+    let kid = insert_key(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(kid)
+}
+
+pub fn router(keys: JwtKeys) -> Router<()> {
+    Router::new()
+        .route("/.well-known/jwks.json", get(jwks_json))
+        .with_state(keys)
+}
+
+#[tracing::instrument(name = "jwks", skip_all)]
+async fn jwks_json(State(keys): State<JwtKeys>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(keys.jwks()),
+    )
+}
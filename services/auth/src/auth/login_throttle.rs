@@ -0,0 +1,204 @@
+//! Brute-force protection for password logins.
+//!
+//! Failed attempts are counted per username and per client IP in `login_attempts`, so every
+//! replica sees the same counters. After a few free attempts each further failure doubles the
+//! wait before the next try (capped at a minute); past a hard limit the key is locked out for
+//! fifteen minutes. Counters are forgotten after an hour without failures.
+
+use std::net::IpAddr;
+
+use axum::{
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+
+const MAX_DELAY_SECS: f64 = 60.0;
+const LOCKOUT: &str = "15 minutes";
+const WINDOW: &str = "1 hour";
+
+struct Limits {
+    scope: &'static str,
+    /// Failures allowed before backoff starts.
+    free: i32,
+    /// Failures after which the key is locked out.
+    lock_after: i32,
+}
+
+const USERNAME: Limits = Limits {
+    scope: "username",
+    free: 3,
+    lock_after: 10,
+};
+
+// Looser than per-username: many legitimate users can share one address behind NAT.
+const IP: Limits = Limits {
+    scope: "ip",
+    free: 10,
+    lock_after: 50,
+};
+
+fn limits(scope: &str) -> &'static Limits {
+    if scope == IP.scope { &IP } else { &USERNAME }
+}
+
+pub(super) enum Verdict {
+    Allowed,
+    /// Inside the backoff delay; retry after the given number of seconds.
+    Throttled(u64),
+    /// Locked out; retry after the given number of seconds.
+    Locked(u64),
+}
+
+#[derive(sqlx::FromRow)]
+struct AttemptRow {
+    scope: String,
+    failures: i32,
+    locked_for: Option<f64>,
+    since_last: f64,
+}
+
+/// Decides whether a login for `username` from `ip` may be checked at all.
+pub(super) async fn check(
+    db: &PgPool,
+    username: &str,
+    ip: Option<&str>,
+) -> Result<Verdict, sqlx::Error> {
+    let rows: Vec<AttemptRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT scope, failures,
+            EXTRACT(EPOCH FROM (locked_until - NOW()))::FLOAT8 AS locked_for,
+            EXTRACT(EPOCH FROM (NOW() - last_failure_at))::FLOAT8 AS since_last
+        FROM login_attempts
+        WHERE ((scope = 'username' AND key = $1) OR (scope = 'ip' AND key = $2))
+          AND last_failure_at > NOW() - INTERVAL '{WINDOW}'
+        "#
+    ))
+    .bind(username)
+    .bind(ip)
+    .fetch_all(db)
+    .await?;
+
+    let mut locked = 0.0_f64;
+    let mut throttled = 0.0_f64;
+    for row in rows {
+        if let Some(secs) = row.locked_for.filter(|s| *s > 0.0) {
+            locked = locked.max(secs);
+            continue;
+        }
+        let free = limits(&row.scope).free;
+        if row.failures >= free {
+            let delay = 2f64.powi(row.failures - free).min(MAX_DELAY_SECS);
+            throttled = throttled.max(delay - row.since_last);
+        }
+    }
+
+    Ok(if locked > 0.0 {
+        Verdict::Locked(locked.ceil() as u64)
+    } else if throttled > 0.0 {
+        Verdict::Throttled(throttled.ceil() as u64)
+    } else {
+        Verdict::Allowed
+    })
+}
+
+async fn bump(db: &PgPool, limits: &Limits, key: &str) -> Result<(), sqlx::Error> {
+    let failures: i32 = sqlx::query_scalar(&format!(
+        r#"
+        INSERT INTO login_attempts (scope, key, failures, last_failure_at)
+        VALUES ($1, $2, 1, NOW())
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = CASE
+                WHEN login_attempts.last_failure_at > NOW() - INTERVAL '{WINDOW}'
+                THEN login_attempts.failures + 1
+                ELSE 1
+            END,
+            last_failure_at = NOW()
+        RETURNING failures
+        "#
+    ))
+    .bind(limits.scope)
+    .bind(key)
+    .fetch_one(db)
+    .await?;
+
+    if failures >= limits.lock_after {
+        sqlx::query(&format!(
+            "UPDATE login_attempts SET locked_until = NOW() + INTERVAL '{LOCKOUT}' WHERE scope = $1 AND key = $2"
+        ))
+        .bind(limits.scope)
+        .bind(key)
+        .execute(db)
+        .await?;
+        tracing::warn!(scope = limits.scope, failures, "login key locked out");
+    }
+    Ok(())
+}
+
+/// Counts a failed attempt against both the username and the client IP.
+pub(super) async fn record_failure(
+    db: &PgPool,
+    username: &str,
+    ip: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    bump(db, &USERNAME, username).await?;
+    if let Some(ip) = ip {
+        bump(db, &IP, ip).await?;
+    }
+    sqlx::query(&format!(
+        "DELETE FROM login_attempts WHERE last_failure_at < NOW() - INTERVAL '{WINDOW}' AND (locked_until IS NULL OR locked_until < NOW())"
+    ))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Clears the username's counter. The IP counter is left alone so one valid credential can't
+/// reset a stuffing run from the same address.
+pub(super) async fn record_success(db: &PgPool, username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_attempts WHERE scope = 'username' AND key = $1")
+        .bind(username)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// `429 Too Many Requests` with a `Retry-After` header.
+pub(super) fn too_many_attempts(retry_after: u64) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        "Too many failed login attempts",
+    )
+        .into_response()
+}
+
+fn parse_ip(value: &str) -> Option<String> {
+    value.trim().parse::<IpAddr>().ok().map(|ip| ip.to_string())
+}
+
+/// Client IP forwarded by the BFF on `/internal` calls. Only trusted because those routes
+/// already require the service token.
+pub(super) fn forwarded_client_ip(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-client-ip")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_ip)
+}
+
+/// Client IP as recorded by the Istio edge proxy, for routes reached directly through the
+/// gateway. Uses the last `x-forwarded-for` hop, which is the one the proxy appended itself.
+pub(super) fn edge_client_ip(headers: &HeaderMap) -> Option<String> {
+    if let Some(ip) = headers
+        .get("x-envoy-external-address")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_ip)
+    {
+        return Some(ip);
+    }
+    headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(parse_ip)
+}
@@ -0,0 +1,594 @@
+//! RFC 6238 TOTP second factor for password accounts.
+//!
+//! Enrollment is two-step: `/internal/mfa/enroll` stores a pending secret and returns the
+//! otpauth:// URI (plus a QR PNG), `/internal/mfa/enroll/confirm` activates it once the user
+//! proves they can generate a code and hands back one-time recovery codes. Recovery codes are
+//! only ever stored as SHA-256 hashes.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{delete, post},
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::audit::{self, AdminActor, Event};
+use super::internal::{InternalState, TokenResp, create_bff_token, resolve_token_user};
+use super::login_throttle::{self, Verdict};
+use super::telemetry;
+
+const ISSUER: &str = "milesstorm.com";
+const TOTP_STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/token/exchange/mfa", post(exchange_mfa))
+        .route("/internal/mfa/status", post(mfa_status))
+        .route("/internal/mfa/enroll", post(mfa_enroll))
+        .route("/internal/mfa/enroll/confirm", post(mfa_enroll_confirm))
+        .route("/internal/mfa/disable", post(mfa_disable))
+        .route("/internal/admin/users/{user_id}/mfa", delete(admin_reset_user_mfa))
+}
+
+// ---- TOTP / recovery code helpers ----
+
+fn build_totp(secret: &str, username: &str) -> Result<TOTP, String> {
+    let bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| e.to_string())?;
+    // Skew is 0 because `verify_totp` walks the ±1 window itself so it knows which step matched.
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        TOTP_STEP,
+        bytes,
+        Some(ISSUER.to_string()),
+        username.replace(':', "_"),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let raw: String = (0..10)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())]
+                        as char
+                })
+                .collect();
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == 6 && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether the user has a confirmed TOTP secret.
+pub(super) async fn is_enabled(db: &PgPool, user_id: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)",
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+#[derive(sqlx::FromRow)]
+struct TotpRow {
+    secret: String,
+    last_used_step: i64,
+    username: String,
+}
+
+/// Checks a 6-digit code against the user's TOTP secret (confirmed or pending, per `enabled`).
+/// The matched time step is recorded so the same code cannot be replayed inside its window.
+async fn verify_totp(
+    db: &PgPool,
+    user_id: i64,
+    code: &str,
+    enabled: bool,
+) -> Result<bool, sqlx::Error> {
+    let row: Option<TotpRow> = sqlx::query_as(
+        r#"
+        SELECT t.secret, t.last_used_step, u.username
+        FROM user_totp t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.user_id = $1 AND (t.enabled_at IS NOT NULL) = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(enabled)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    let totp = match build_totp(&row.secret, &row.username) {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!(user_id, error = %e, "stored TOTP secret is invalid");
+            return Ok(false);
+        }
+    };
+
+    let current = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / TOTP_STEP;
+
+    let matched = [current - 1, current, current + 1]
+        .into_iter()
+        .filter(|step| *step as i64 > row.last_used_step)
+        .find(|step| totp.check(code, step * TOTP_STEP));
+
+    let Some(step) = matched else {
+        return Ok(false);
+    };
+
+    // Conditional update so two concurrent requests with the same code can't both succeed.
+    let updated = sqlx::query(
+        "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1 AND last_used_step < $2",
+    )
+    .bind(user_id)
+    .bind(step as i64)
+    .execute(db)
+    .await?;
+
+    Ok(updated.rows_affected() == 1)
+}
+
+/// Checks `code` as a TOTP code for a user with MFA enabled, falling back to an unused
+/// recovery code. A recovery code is burned on first use.
+pub(super) async fn verify_code(db: &PgPool, user_id: i64, code: &str) -> Result<bool, sqlx::Error> {
+    let code = code.trim();
+    if is_totp_code(code) {
+        return verify_totp(db, user_id, code, true).await;
+    }
+
+    let used: Option<i64> = sqlx::query_scalar(
+        r#"
+        UPDATE user_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_recovery_code(code))
+    .fetch_optional(db)
+    .await?;
+
+    if used.is_some() {
+        tracing::info!(user_id, "recovery code consumed");
+    }
+    Ok(used.is_some())
+}
+
+/// Starts a second-factor challenge after a correct password. The returned handle is only
+/// good for `/internal/token/exchange/mfa` and expires after five minutes.
+pub(super) async fn create_challenge(db: &PgPool, user_id: i64) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO mfa_challenges (token, user_id) VALUES ($1, $2) RETURNING token")
+        .bind(ulid::Ulid::new().to_string())
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+}
+
+// ---- Second login step ----
+
+#[derive(Deserialize)]
+struct ExchangeMfaReq {
+    mfa_token: String,
+    code: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ChallengeRow {
+    user_id: i64,
+    username: String,
+}
+
+#[tracing::instrument(name = "token.exchange.mfa", skip_all)]
+async fn exchange_mfa(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<ExchangeMfaReq>,
+) -> impl IntoResponse {
+    // Count the attempt before checking the code so a challenge can't be brute-forced.
+    let challenge: Option<ChallengeRow> = sqlx::query_as(
+        r#"
+        UPDATE mfa_challenges c SET attempts = c.attempts + 1
+        FROM users u
+        WHERE c.token = $1 AND c.expires_at > NOW() AND c.attempts < $2 AND u.id = c.user_id
+          AND u.disabled_at IS NULL
+        RETURNING c.user_id, u.username
+        "#,
+    )
+    .bind(&req.mfa_token)
+    .bind(MAX_CHALLENGE_ATTEMPTS)
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
+
+    let Some(challenge) = challenge else {
+        telemetry::login_attempt("totp", "expired");
+        return (StatusCode::UNAUTHORIZED, "MFA challenge expired").into_response();
+    };
+
+    // The per-challenge cap alone doesn't stop someone who knows the password from asking for
+    // fresh challenges, so wrong codes also count towards the password login throttle.
+    let client_ip = login_throttle::forwarded_client_ip(&headers);
+    match login_throttle::check(&state.db, &challenge.username, client_ip.as_deref()).await {
+        Ok(Verdict::Allowed) => {}
+        Ok(Verdict::Throttled(retry_after) | Verdict::Locked(retry_after)) => {
+            tracing::warn!(user_id = challenge.user_id, ip = ?client_ip, retry_after, "mfa login throttled");
+            telemetry::login_attempt("totp", "throttled");
+            return login_throttle::too_many_attempts(retry_after);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to check login attempts");
+            telemetry::login_attempt("totp", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    match verify_code(&state.db, challenge.user_id, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => {
+            if let Err(e) =
+                login_throttle::record_failure(&state.db, &challenge.username, client_ip.as_deref()).await
+            {
+                tracing::error!(error = %e, "failed to record login attempt");
+            }
+            tracing::warn!(user_id = challenge.user_id, "mfa verification failed: invalid code");
+            telemetry::login_attempt("totp", "failure");
+            telemetry::token_operation("exchange", "failure");
+            return (StatusCode::UNAUTHORIZED, "Invalid code").into_response();
+        }
+        Err(e) => {
+            tracing::error!(user_id = challenge.user_id, error = %e, "mfa verification failed");
+            telemetry::login_attempt("totp", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let _ = sqlx::query("DELETE FROM mfa_challenges WHERE token = $1")
+        .bind(&req.mfa_token)
+        .execute(&state.db)
+        .await;
+    if let Err(e) = login_throttle::record_success(&state.db, &challenge.username).await {
+        tracing::error!(error = %e, "failed to reset login attempts");
+    }
+
+    match create_bff_token(&state, challenge.user_id, "password", &headers).await {
+        Ok(bff_token) => {
+            tracing::info!(user_id = challenge.user_id, username = %challenge.username, "mfa login succeeded");
+            telemetry::login_attempt("totp", "success");
+            telemetry::token_operation("exchange", "success");
+            Json(TokenResp {
+                token: bff_token.token,
+                username: challenge.username,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = challenge.user_id, error = %e, "failed to insert bff_token after mfa");
+            telemetry::token_operation("exchange", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---- Self-service enrollment (identified by the caller's opaque token) ----
+
+#[derive(Deserialize)]
+struct MfaTokenReq {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct MfaCodeReq {
+    token: String,
+    code: String,
+}
+
+#[derive(Serialize)]
+struct MfaStatusResp {
+    /// MFA is only offered to accounts that log in with a password.
+    available: bool,
+    enabled: bool,
+    recovery_codes_remaining: i64,
+}
+
+#[derive(Serialize)]
+struct MfaEnrollResp {
+    secret: String,
+    otpauth_uri: String,
+    qr_png_base64: String,
+}
+
+#[derive(Serialize)]
+struct MfaRecoveryCodesResp {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct MfaStatusRow {
+    available: bool,
+    enabled: bool,
+    recovery_codes_remaining: i64,
+}
+
+#[tracing::instrument(name = "mfa.status", skip_all)]
+async fn mfa_status(
+    State(state): State<InternalState>,
+    Json(req): Json<MfaTokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let row: Result<MfaStatusRow, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT
+            u.password IS NOT NULL AS available,
+            EXISTS (SELECT 1 FROM user_totp t WHERE t.user_id = u.id AND t.enabled_at IS NOT NULL) AS enabled,
+            (SELECT COUNT(*) FROM user_recovery_codes c WHERE c.user_id = u.id AND c.used_at IS NULL) AS recovery_codes_remaining
+        FROM users u
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user.user_id)
+    .fetch_one(&state.db)
+    .await;
+
+    match row {
+        Ok(r) => Json(MfaStatusResp {
+            available: r.available,
+            enabled: r.enabled,
+            recovery_codes_remaining: r.recovery_codes_remaining,
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_status: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "mfa.enroll", skip_all)]
+async fn mfa_enroll(
+    State(state): State<InternalState>,
+    Json(req): Json<MfaTokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let has_password: bool = sqlx::query_scalar("SELECT password IS NOT NULL FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(false);
+    if !has_password {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication is only available for password accounts",
+        )
+            .into_response();
+    }
+
+    let Secret::Encoded(secret) = Secret::generate_secret().to_encoded() else {
+        unreachable!("to_encoded always returns Secret::Encoded");
+    };
+
+    // Re-enrolling replaces a pending secret but never an active one.
+    let stored = sqlx::query(
+        r#"
+        INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+        SET secret = excluded.secret, last_used_step = 0, created_at = NOW()
+        WHERE user_totp.enabled_at IS NULL
+        "#,
+    )
+    .bind(user.user_id)
+    .bind(&secret)
+    .execute(&state.db)
+    .await;
+
+    match stored {
+        Ok(r) if r.rows_affected() == 0 => {
+            return (StatusCode::CONFLICT, "Two-factor authentication is already enabled")
+                .into_response();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_enroll: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let totp = match build_totp(&secret, &user.username) {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_enroll: could not build TOTP");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let qr_png_base64 = match totp.get_qr_base64() {
+        Ok(qr) => qr,
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_enroll: could not render QR code");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    tracing::info!(user_id = user.user_id, "mfa enrollment started");
+    Json(MfaEnrollResp {
+        otpauth_uri: totp.get_url(),
+        secret,
+        qr_png_base64,
+    })
+    .into_response()
+}
+
+#[tracing::instrument(name = "mfa.enroll_confirm", skip_all)]
+async fn mfa_enroll_confirm(
+    State(state): State<InternalState>,
+    Json(req): Json<MfaCodeReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let code = req.code.trim();
+    if !is_totp_code(code) {
+        return (StatusCode::UNAUTHORIZED, "Invalid code").into_response();
+    }
+    match verify_totp(&state.db, user.user_id, code, false).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::UNAUTHORIZED, "Invalid code").into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_enroll_confirm: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let codes = generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        sqlx::query("UPDATE user_totp SET enabled_at = NOW() WHERE user_id = $1")
+            .bind(user.user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user.user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO user_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::text[])",
+        )
+        .bind(user.user_id)
+        .bind(&hashes)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(user_id = user.user_id, "mfa enabled");
+            Json(MfaRecoveryCodesResp {
+                recovery_codes: codes,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_enroll_confirm: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn remove_mfa(db: &PgPool, user_id: i64) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM mfa_challenges WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+#[tracing::instrument(name = "mfa.disable", skip_all)]
+async fn mfa_disable(
+    State(state): State<InternalState>,
+    Json(req): Json<MfaCodeReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    match verify_code(&state.db, user.user_id, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::UNAUTHORIZED, "Invalid code").into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_disable: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    match remove_mfa(&state.db, user.user_id).await {
+        Ok(()) => {
+            tracing::info!(user_id = user.user_id, "mfa disabled by user");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "mfa_disable: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---- Admin reset ----
+
+#[tracing::instrument(name = "admin.reset_user_mfa", skip_all, fields(user_id))]
+async fn admin_reset_user_mfa(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    match remove_mfa(&state.db, user_id).await {
+        Ok(()) => {
+            audit::log(
+                &state.db,
+                Event::new("mfa.admin_reset", &headers)
+                    .actor(actor.user_id)
+                    .target(format!("user #{user_id}")),
+            )
+            .await;
+            tracing::info!(actor = actor.user_id, user_id, "reset user mfa");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, user_id, "admin_reset_user_mfa: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,668 @@
+//! OpenID Connect provider for other homelab apps.
+//!
+//! Relying parties registered in `oauth_clients` use the authorization code flow with PKCE
+//! (`S256` only). Users still log in through the frontend with a password, passkey, GitHub or
+//! Google; auth never shows a login page of its own. The flow is:
+//!
+//! 1. `/oauth2/authorize` validates the request, parks it in `oidc_authorization_requests` and
+//!    sends the browser to the frontend's `/oauth2/continue?request=…`.
+//! 2. The frontend makes sure the browser has a BFF session (logging in first if needed) and
+//!    calls `/internal/oidc/approve` with its opaque token, which turns the parked request into a
+//!    one-minute authorization code and returns the redirect back to the client.
+//! 3. The client trades the code at `/oauth2/token` for an access token and an ID token signed
+//!    with the same keys as introspection JWTs, then may call `/oauth2/userinfo`.
+//!
+//! Clients are first-party, so there is no consent screen. Native apps that can't receive a
+//! redirect use the device authorization grant instead; see [`super::device`].
+
+use axum::{
+    Form, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use oauth2::url::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use ulid::Ulid;
+
+use super::device;
+use super::internal::{InternalState, effective_permissions, resolve_token_user};
+use super::password_reset::{generate_token, hash_token};
+use super::telemetry;
+
+const ACCESS_TOKEN_TTL_SECS: i64 = 3600;
+const ID_TOKEN_TTL_SECS: i64 = 3600;
+const SCOPES: [&str; 4] = ["openid", "profile", "email", "permissions"];
+
+/// Public, browser- and client-facing endpoints.
+pub fn router(state: InternalState) -> Router<()> {
+    Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/oauth2/authorize", get(authorize))
+        .route("/oauth2/token", post(token))
+        .route("/oauth2/device_authorization", post(device::device_authorization))
+        .route("/oauth2/userinfo", get(userinfo).post(userinfo))
+        .with_state(state)
+}
+
+/// Service-token protected endpoints used by the frontend.
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new().route("/internal/oidc/approve", post(approve))
+}
+
+#[tracing::instrument(name = "oidc.discovery", skip_all)]
+async fn discovery(State(state): State<InternalState>) -> impl IntoResponse {
+    let issuer = &state.oidc_issuer;
+    (
+        [(header::CACHE_CONTROL, "public, max-age=3600")],
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/oauth2/authorize"),
+            "token_endpoint": format!("{issuer}/oauth2/token"),
+            "userinfo_endpoint": format!("{issuer}/oauth2/userinfo"),
+            "device_authorization_endpoint": format!("{issuer}/oauth2/device_authorization"),
+            "jwks_uri": format!("{issuer}/.well-known/jwks.json"),
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", device::GRANT_TYPE],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["EdDSA"],
+            "scopes_supported": SCOPES,
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "code_challenge_methods_supported": ["S256"],
+            "claims_supported": [
+                "iss", "sub", "aud", "exp", "iat", "auth_time", "nonce",
+                "preferred_username", "email", "email_verified", "permissions"
+            ],
+        })),
+    )
+}
+
+// ---- Authorization endpoint ----
+
+#[derive(Deserialize)]
+struct AuthorizeParams {
+    response_type: Option<String>,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(super) struct ClientRow {
+    pub client_id: String,
+    client_secret_hash: Option<String>,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Vec<String>,
+    /// May use the device authorization grant.
+    pub device_grant: bool,
+}
+
+async fn find_client(db: &PgPool, client_id: &str) -> Result<Option<ClientRow>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT client_id, client_secret_hash, redirect_uris, allowed_scopes, device_grant
+        FROM oauth_clients WHERE client_id = $1
+        "#,
+    )
+    .bind(client_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Redirects back to the client with an OAuth error, as RFC 6749 §4.1.2.1 asks once the
+/// redirect URI is known to be good.
+fn redirect_error(redirect_uri: &str, error: &str, description: &str, state: Option<&str>) -> Response {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        return (StatusCode::BAD_REQUEST, "Invalid redirect_uri").into_response();
+    };
+    {
+        let mut q = url.query_pairs_mut();
+        q.append_pair("error", error);
+        q.append_pair("error_description", description);
+        if let Some(state) = state {
+            q.append_pair("state", state);
+        }
+    }
+    Redirect::to(url.as_str()).into_response()
+}
+
+#[tracing::instrument(name = "oidc.authorize", skip_all)]
+async fn authorize(
+    State(state): State<InternalState>,
+    Query(params): Query<AuthorizeParams>,
+) -> impl IntoResponse {
+    // Until the client and redirect URI check out, errors go to the browser, never to the URI.
+    let (Some(client_id), Some(redirect_uri)) = (params.client_id, params.redirect_uri) else {
+        return (StatusCode::BAD_REQUEST, "client_id and redirect_uri are required").into_response();
+    };
+    let client = match find_client(&state.db, &client_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return (StatusCode::BAD_REQUEST, "Unknown client_id").into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "authorize: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if !client.redirect_uris.contains(&redirect_uri) {
+        tracing::warn!(%client_id, %redirect_uri, "authorize: unregistered redirect_uri");
+        return (StatusCode::BAD_REQUEST, "redirect_uri is not registered for this client").into_response();
+    }
+
+    let st = params.state.as_deref();
+    if params.response_type.as_deref() != Some("code") {
+        return redirect_error(&redirect_uri, "unsupported_response_type", "Only response_type=code is supported", st);
+    }
+    let scope = params.scope.unwrap_or_default();
+    let scopes: Vec<&str> = scope.split_whitespace().collect();
+    if !scopes.contains(&"openid") {
+        return redirect_error(&redirect_uri, "invalid_scope", "The openid scope is required", st);
+    }
+    if let Some(bad) = scopes.iter().find(|s| !client.allowed_scopes.iter().any(|a| a == *s)) {
+        return redirect_error(&redirect_uri, "invalid_scope", &format!("Scope {bad} is not allowed for this client"), st);
+    }
+    let Some(code_challenge) = params.code_challenge.filter(|c| !c.is_empty()) else {
+        return redirect_error(&redirect_uri, "invalid_request", "PKCE code_challenge is required", st);
+    };
+    if params.code_challenge_method.as_deref() != Some("S256") {
+        return redirect_error(&redirect_uri, "invalid_request", "code_challenge_method must be S256", st);
+    }
+
+    let id = Ulid::new().to_string();
+    let stored = sqlx::query(
+        r#"
+        INSERT INTO oidc_authorization_requests
+            (id, client_id, redirect_uri, scope, state, nonce, code_challenge)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(&id)
+    .bind(&client.client_id)
+    .bind(&redirect_uri)
+    .bind(scopes.join(" "))
+    .bind(&params.state)
+    .bind(&params.nonce)
+    .bind(&code_challenge)
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = stored {
+        tracing::error!(error = %e, "authorize: could not store request");
+        return redirect_error(&redirect_uri, "server_error", "Could not start sign-in", st);
+    }
+
+    let _ = sqlx::query("DELETE FROM oidc_authorization_requests WHERE expires_at < NOW()")
+        .execute(&state.db)
+        .await;
+
+    telemetry::token_operation("oidc_authorize", "success");
+    Redirect::to(&format!("{}/oauth2/continue?request={id}", state.public_url)).into_response()
+}
+
+// ---- Approval (from the frontend) ----
+
+#[derive(Deserialize)]
+struct ApproveReq {
+    token: String,
+    request_id: String,
+}
+
+#[derive(Serialize)]
+struct ApproveResp {
+    redirect_url: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct PendingRequest {
+    client_id: String,
+    redirect_uri: String,
+    scope: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: String,
+}
+
+#[tracing::instrument(name = "oidc.approve", skip_all)]
+async fn approve(
+    State(state): State<InternalState>,
+    Json(req): Json<ApproveReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let code = generate_token();
+    let result: Result<Option<PendingRequest>, sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        let pending: Option<PendingRequest> = sqlx::query_as(
+            r#"
+            DELETE FROM oidc_authorization_requests WHERE id = $1 AND expires_at > NOW()
+            RETURNING client_id, redirect_uri, scope, state, nonce, code_challenge
+            "#,
+        )
+        .bind(&req.request_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(p) = pending else {
+            return Ok(None);
+        };
+
+        // The session's login time is when the user last actually authenticated.
+        sqlx::query(
+            r#"
+            INSERT INTO oidc_authorization_codes
+                (code_hash, client_id, user_id, redirect_uri, scope, nonce, code_challenge, auth_time)
+            SELECT $1, $2, $3, $4, $5, $6, $7, created_at FROM bff_tokens WHERE token = $8
+            "#,
+        )
+        .bind(hash_token(&code))
+        .bind(&p.client_id)
+        .bind(user.user_id)
+        .bind(&p.redirect_uri)
+        .bind(&p.scope)
+        .bind(&p.nonce)
+        .bind(&p.code_challenge)
+        .bind(&req.token)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Some(p))
+    }
+    .await;
+
+    let pending = match result {
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            telemetry::token_operation("oidc_approve", "invalid");
+            return (StatusCode::NOT_FOUND, "This sign-in request is invalid or has expired").into_response();
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "approve: db error");
+            telemetry::token_operation("oidc_approve", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Ok(mut url) = Url::parse(&pending.redirect_uri) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    {
+        let mut q = url.query_pairs_mut();
+        q.append_pair("code", &code);
+        if let Some(s) = &pending.state {
+            q.append_pair("state", s);
+        }
+        q.append_pair("iss", &state.oidc_issuer);
+    }
+
+    tracing::info!(user_id = user.user_id, client_id = %pending.client_id, "oidc authorization approved");
+    telemetry::token_operation("oidc_approve", "success");
+    Json(ApproveResp {
+        redirect_url: url.into(),
+    })
+    .into_response()
+}
+
+// ---- Token endpoint ----
+
+#[derive(Deserialize)]
+struct TokenReq {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    code_verifier: Option<String>,
+    device_code: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct CodeRow {
+    client_id: String,
+    user_id: i64,
+    redirect_uri: String,
+    scope: String,
+    nonce: Option<String>,
+    code_challenge: String,
+    auth_time: chrono::DateTime<chrono::Utc>,
+}
+
+/// Claims about the user, filtered by the granted scopes. Shared by the ID token and userinfo.
+#[derive(Serialize)]
+struct UserClaims {
+    sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permissions: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct IdTokenClaims {
+    iss: String,
+    aud: String,
+    exp: i64,
+    iat: i64,
+    auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(flatten)]
+    user: UserClaims,
+}
+
+#[derive(Serialize)]
+struct TokenResp {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: i64,
+    id_token: String,
+    scope: String,
+}
+
+pub(super) fn token_error(status: StatusCode, error: &str, description: &str) -> Response {
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(json!({ "error": error, "error_description": description })),
+    )
+        .into_response()
+}
+
+/// Client credentials from HTTP Basic auth, falling back to the form body.
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Option<(String, Option<String>)> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|b| STANDARD.decode(b.trim()).ok())
+        .and_then(|b| String::from_utf8(b).ok());
+    if let Some((id, secret)) = basic.as_deref().and_then(|b| b.split_once(':')) {
+        return Some((id.to_string(), Some(secret.to_string())));
+    }
+    client_id.map(|id| (id.to_string(), client_secret.map(str::to_string)))
+}
+
+/// Authenticates the client making a token or device authorization request. Public clients
+/// only need to name themselves; `operation` labels the failure metric.
+pub(super) async fn authenticate_client(
+    db: &PgPool,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+    operation: &str,
+) -> Result<ClientRow, Response> {
+    let Some((client_id, secret)) = client_credentials(headers, client_id, client_secret) else {
+        return Err(token_error(StatusCode::UNAUTHORIZED, "invalid_client", "Client authentication required"));
+    };
+    let client = match find_client(db, &client_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return Err(token_error(StatusCode::UNAUTHORIZED, "invalid_client", "Unknown client")),
+        Err(e) => {
+            tracing::error!(error = %e, "{operation}: db error");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+    if let Some(expected) = &client.client_secret_hash
+        && secret.as_deref().map(hash_token).as_ref() != Some(expected)
+    {
+        tracing::warn!(%client_id, "{operation}: bad client secret");
+        telemetry::token_operation(operation, "invalid_client");
+        return Err(token_error(StatusCode::UNAUTHORIZED, "invalid_client", "Client authentication failed"));
+    }
+    Ok(client)
+}
+
+async fn user_claims(
+    state: &InternalState,
+    user_id: i64,
+    scope: &str,
+) -> Result<UserClaims, sqlx::Error> {
+    let (username, email): (String, Option<String>) =
+        sqlx::query_as("SELECT username, email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&state.db)
+            .await?;
+    let (permissions, email_verified) =
+        effective_permissions(&state.db, state.email_policy, user_id).await?;
+
+    let has = |s: &str| scope.split_whitespace().any(|g| g == s);
+    Ok(UserClaims {
+        sub: user_id.to_string(),
+        preferred_username: has("profile").then_some(username),
+        email_verified: (has("email") && email.is_some()).then_some(email_verified),
+        email: if has("email") { email } else { None },
+        permissions: has("permissions").then_some(permissions),
+    })
+}
+
+#[tracing::instrument(name = "oidc.token", skip_all)]
+async fn token(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Form(req): Form<TokenReq>,
+) -> impl IntoResponse {
+    if req.grant_type != "authorization_code" && req.grant_type != device::GRANT_TYPE {
+        return token_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "Only authorization_code and device_code are supported",
+        );
+    }
+    let client = match authenticate_client(
+        &state.db,
+        &headers,
+        req.client_id.as_deref(),
+        req.client_secret.as_deref(),
+        "oidc_token",
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    if req.grant_type == device::GRANT_TYPE {
+        return device::token(&state, &client, &headers, req.device_code.as_deref()).await;
+    }
+
+    let (Some(code), Some(verifier)) = (req.code.as_deref(), req.code_verifier.as_deref()) else {
+        return token_error(StatusCode::BAD_REQUEST, "invalid_request", "code and code_verifier are required");
+    };
+
+    // Codes are single use: deleting it up front means a replayed code always fails.
+    let row: Option<CodeRow> = match sqlx::query_as(
+        r#"
+        DELETE FROM oidc_authorization_codes c
+        USING users u
+        WHERE c.code_hash = $1 AND c.expires_at > NOW() AND u.id = c.user_id
+          AND u.disabled_at IS NULL
+        RETURNING c.client_id, c.user_id, c.redirect_uri, c.scope, c.nonce, c.code_challenge,
+                  c.auth_time
+        "#,
+    )
+    .bind(hash_token(code))
+    .fetch_optional(&state.db)
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!(error = %e, "token: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let Some(row) = row.filter(|r| {
+        r.client_id == client.client_id
+            && req.redirect_uri.as_deref() == Some(r.redirect_uri.as_str())
+            && r.code_challenge == challenge
+    }) else {
+        telemetry::token_operation("oidc_token", "invalid_grant");
+        return token_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid, expired or mismatched authorization code");
+    };
+
+    let claims = match user_claims(&state, row.user_id, &row.scope).await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!(user_id = row.user_id, error = %e, "token: could not load user claims");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let id_token = match state.jwt_keys.sign(&IdTokenClaims {
+        iss: state.oidc_issuer.clone(),
+        aud: client.client_id.clone(),
+        exp: now + ID_TOKEN_TTL_SECS,
+        iat: now,
+        auth_time: row.auth_time.timestamp(),
+        nonce: row.nonce,
+        user: claims,
+    }) {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!(error = %e, "token: id token signing failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let access_token = generate_token();
+    let stored = sqlx::query(
+        r#"
+        INSERT INTO oidc_access_tokens (token_hash, client_id, user_id, scope, expires_at)
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+        "#,
+    )
+    .bind(hash_token(&access_token))
+    .bind(&client.client_id)
+    .bind(row.user_id)
+    .bind(&row.scope)
+    .bind(ACCESS_TOKEN_TTL_SECS as f64)
+    .execute(&state.db)
+    .await;
+    if let Err(e) = stored {
+        tracing::error!(error = %e, "token: could not store access token");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let _ = sqlx::query("DELETE FROM oidc_access_tokens WHERE expires_at < NOW()")
+        .execute(&state.db)
+        .await;
+
+    tracing::info!(user_id = row.user_id, client_id = %client.client_id, "oidc tokens issued");
+    telemetry::token_operation("oidc_token", "success");
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(TokenResp {
+            access_token,
+            token_type: "Bearer",
+            expires_in: ACCESS_TOKEN_TTL_SECS,
+            id_token,
+            scope: row.scope,
+        }),
+    )
+        .into_response()
+}
+
+// ---- UserInfo endpoint ----
+
+fn invalid_token() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"")],
+    )
+        .into_response()
+}
+
+#[tracing::instrument(name = "oidc.userinfo", skip_all)]
+async fn userinfo(State(state): State<InternalState>, headers: HeaderMap) -> impl IntoResponse {
+    let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return invalid_token();
+    };
+
+    let grant: Option<(i64, String)> = match sqlx::query_as(
+        r#"
+        SELECT t.user_id, t.scope
+        FROM oidc_access_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1 AND t.expires_at > NOW() AND u.disabled_at IS NULL
+        "#,
+    )
+    .bind(hash_token(bearer))
+    .fetch_optional(&state.db)
+    .await
+    {
+        Ok(g) => g,
+        Err(e) => {
+            tracing::error!(error = %e, "userinfo: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Some((user_id, scope)) = grant else {
+        return invalid_token();
+    };
+
+    match user_claims(&state, user_id, &scope).await {
+        Ok(claims) => Json(claims).into_response(),
+        Err(e) => {
+            tracing::error!(user_id, error = %e, "userinfo: could not load user claims");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---- Client registration (CLI) ----
+
+/// Registers or replaces a relying party. Confidential clients get a freshly generated secret,
+/// which is returned once and only stored hashed. `device_grant` lets the client use the device
+/// authorization grant.
+pub async fn register_client(
+    db: &PgPool,
+    client_id: &str,
+    name: &str,
+    redirect_uris: &[String],
+    public: bool,
+    device_grant: bool,
+) -> Result<Option<String>, sqlx::Error> {
+    let secret = (!public).then(generate_token);
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_clients (client_id, name, client_secret_hash, redirect_uris, device_grant)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (client_id) DO UPDATE SET
+            name = EXCLUDED.name,
+            client_secret_hash = EXCLUDED.client_secret_hash,
+            redirect_uris = EXCLUDED.redirect_uris,
+            device_grant = EXCLUDED.device_grant
+        "#,
+    )
+    .bind(client_id)
+    .bind(name)
+    .bind(secret.as_deref().map(hash_token))
+    .bind(redirect_uris)
+    .bind(device_grant)
+    .execute(db)
+    .await?;
+    Ok(secret)
+}
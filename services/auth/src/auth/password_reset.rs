@@ -0,0 +1,178 @@
+//! Self-service password reset for password accounts.
+//!
+//! `/internal/password/forgot` mails a single-use link to the account's address;
+//! `/internal/password/reset` trades the token for a new password. Only a SHA-256 of the token is
+//! stored, and a successful reset signs the user out everywhere.
+
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::post};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::task;
+
+use super::internal::InternalState;
+use super::mail::Mail;
+use super::sessions;
+use super::telemetry;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/password/forgot", post(forgot_password))
+        .route("/internal/password/reset", post(reset_password))
+}
+
+pub(super) fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(super) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+#[derive(Deserialize)]
+struct ForgotReq {
+    email: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ResetUserRow {
+    id: i64,
+    username: String,
+    email: String,
+}
+
+/// Always answers `202 Accepted` so the endpoint can't be used to probe which emails exist.
+#[tracing::instrument(name = "password.forgot", skip_all)]
+async fn forgot_password(
+    State(state): State<InternalState>,
+    Json(req): Json<ForgotReq>,
+) -> impl IntoResponse {
+    let user: Option<ResetUserRow> = sqlx::query_as(
+        "SELECT id, username, email FROM users WHERE lower(email) = lower($1) AND password IS NOT NULL",
+    )
+    .bind(req.email.trim())
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
+
+    let Some(user) = user else {
+        tracing::info!("password reset requested for unknown email");
+        telemetry::token_operation("password_forgot", "unknown");
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    let token = generate_token();
+    // Only the newest link works; requesting another one invalidates older ones.
+    let stored: Result<(), sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO password_reset_tokens (token_hash, user_id) VALUES ($1, $2)")
+            .bind(hash_token(&token))
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = stored {
+        tracing::error!(user_id = user.id, error = %e, "forgot_password: db error");
+        telemetry::token_operation("password_forgot", "error");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    state.mailer.enqueue(Mail {
+        to: user.email,
+        subject: "Reset your milesstorm.com password".to_string(),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password for your account. \
+             If it was you, open this link within the next hour:\n\n{}/reset-password?token={}\n\n\
+             If you didn't ask for this, you can ignore this email.\n",
+            user.username, state.public_url, token
+        ),
+    });
+
+    tracing::info!(user_id = user.id, "password reset email queued");
+    telemetry::token_operation("password_forgot", "success");
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[derive(Deserialize)]
+struct ResetReq {
+    token: String,
+    password: String,
+}
+
+#[tracing::instrument(name = "password.reset", skip_all)]
+async fn reset_password(
+    State(state): State<InternalState>,
+    Json(req): Json<ResetReq>,
+) -> impl IntoResponse {
+    if req.password.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Password must not be empty").into_response();
+    }
+
+    let password = req.password;
+    let hashed = task::spawn_blocking(move || password_auth::generate_hash(password))
+        .await
+        .expect("password hashing failed");
+
+    let result: Result<Option<i64>, sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        let user_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+        )
+        .bind(hash_token(&req.token))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        // Following the emailed link also proves ownership of the address.
+        sqlx::query(
+            "UPDATE users SET password = $2, email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1",
+        )
+        .bind(user_id)
+        .bind(&hashed)
+        .execute(&mut *tx)
+        .await?;
+        // Anyone holding a session or a half-finished login for this account is logged out.
+        sessions::revoke_all_for_user(&mut *tx, user_id).await?;
+        sqlx::query("DELETE FROM mfa_challenges WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(user_id)) => {
+            tracing::info!(user_id, "password reset succeeded; all sessions revoked");
+            telemetry::token_operation("password_reset", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => {
+            tracing::warn!("password reset failed: invalid or expired token");
+            telemetry::token_operation("password_reset", "invalid");
+            (StatusCode::BAD_REQUEST, "This reset link is invalid or has expired").into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "reset_password: db error");
+            telemetry::token_operation("password_reset", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
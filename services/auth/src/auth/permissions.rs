@@ -131,7 +131,7 @@ mod get {
             Some(user) => {
                 tracing::info!("{:?} restarted valheim server", user);
 
-                match reqwest::get("http://192.168.1.21:9090/valheim").await {
+                match reqwest::get(format!("{}/valheim", auth_session.backend.ark_url())).await {
                     Ok(resp) => {
                         let json: Result<DockerRequestResponse, reqwest::Error> =
                             resp.json::<DockerRequestResponse>().await;
@@ -196,7 +196,7 @@ mod get {
             Some(user) => {
                 tracing::info!("{:?} {}ed ark server", user, op);
 
-                match reqwest::get(format!("http://192.168.1.21:9090/ark/{op}")).await {
+                match reqwest::get(format!("{}/ark/{op}", auth_session.backend.ark_url())).await {
                     Ok(resp) => {
                         let json: Result<DockerRequestResponse, reqwest::Error> =
                             resp.json::<DockerRequestResponse>().await;
@@ -251,10 +251,11 @@ impl AuthzBackend for Backend {
         info!("Getting permissions for user: {:?}", &user);
         let permissions: Vec<Self::Permission> = sqlx::query_as(
             r#"
-            SELECT permissions.name
+            SELECT DISTINCT permissions.name
             FROM users
-            JOIN user_roles ON users.id = user_roles.user_id
-            JOIN role_permissions ON user_roles.role_id = role_permissions.role_id
+            JOIN active_user_roles ON users.id = active_user_roles.user_id
+            JOIN role_ancestors ON active_user_roles.role_id = role_ancestors.role_id
+            JOIN role_permissions ON role_ancestors.ancestor_id = role_permissions.role_id
             JOIN permissions ON role_permissions.permission_id = permissions.id
             WHERE users.id = $1
             "#,
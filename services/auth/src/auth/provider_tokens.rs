@@ -0,0 +1,220 @@
+//! Envelope encryption of upstream provider access tokens.
+//!
+//! Each token is sealed with AES-256-GCM under a fresh data key, and the data key is itself
+//! sealed under a key-encryption key from `PROVIDER_TOKEN_KEYS`. Both live on the identity row
+//! the token belongs to, along with the id of the key-encryption key, so a database dump alone
+//! reveals no tokens. Rotating keys only re-seals the small data keys: put a new key first in
+//! `PROVIDER_TOKEN_KEYS`, keep the old one after it, run `auth rewrap-provider-tokens`, then drop
+//! the old key.
+
+use std::collections::HashMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use sqlx::PgPool;
+
+use super::identities::UpstreamIdentity;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenKeyError {
+    #[error("PROVIDER_TOKEN_KEYS must be set")]
+    Missing,
+
+    #[error("PROVIDER_TOKEN_KEYS entry {0:?} is not `<key id>:<base64 of 32 bytes>`")]
+    InvalidKey(String),
+
+    #[error("no key {0:?} in PROVIDER_TOKEN_KEYS")]
+    UnknownKey(String),
+
+    #[error("could not encrypt or decrypt a provider token")]
+    Crypto,
+
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Key-encryption keys by id. The first one listed seals new data keys.
+pub struct TokenKeys {
+    current: String,
+    keys: HashMap<String, LessSafeKey>,
+}
+
+impl std::fmt::Debug for TokenKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenKeys")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenKeys {
+    /// Parses `PROVIDER_TOKEN_KEYS`, a comma-separated list of `<key id>:<base64 key>`.
+    pub fn parse(raw: &str) -> Result<Self, TokenKeyError> {
+        let mut current = None;
+        let mut keys = HashMap::new();
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let invalid =
+                || TokenKeyError::InvalidKey(entry.split(':').next().unwrap_or("").into());
+            let (id, key) = entry.split_once(':').ok_or_else(invalid)?;
+            let key = STANDARD.decode(key).map_err(|_| invalid())?;
+            let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| invalid())?;
+            current.get_or_insert_with(|| id.to_string());
+            keys.insert(id.to_string(), LessSafeKey::new(key));
+        }
+
+        Ok(Self {
+            current: current.ok_or(TokenKeyError::Missing)?,
+            keys,
+        })
+    }
+
+    fn key(&self, id: &str) -> Result<&LessSafeKey, TokenKeyError> {
+        self.keys
+            .get(id)
+            .ok_or_else(|| TokenKeyError::UnknownKey(id.to_string()))
+    }
+}
+
+/// A sealed token as stored on `user_identities`.
+struct Sealed {
+    ciphertext: Vec<u8>,
+    data_key: Vec<u8>,
+    key_id: String,
+}
+
+/// AES-GCM with a random nonce, returned as `nonce || ciphertext || tag`.
+fn seal_with(key: &LessSafeKey, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, TokenKeyError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| TokenKeyError::Crypto)?;
+    let mut out = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut out,
+    )
+    .map_err(|_| TokenKeyError::Crypto)?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&out);
+    Ok(sealed)
+}
+
+fn open_with(key: &LessSafeKey, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, TokenKeyError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(TokenKeyError::Crypto);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| TokenKeyError::Crypto)?;
+    let mut buf = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(aad), &mut buf)
+        .map_err(|_| TokenKeyError::Crypto)?;
+    Ok(plaintext.to_vec())
+}
+
+/// Seals `token` for the identity row `identity_id`, which is bound in as associated data so a
+/// ciphertext copied onto another row does not decrypt.
+fn seal(keys: &TokenKeys, identity_id: i64, token: &str) -> Result<Sealed, TokenKeyError> {
+    let mut data_key = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut data_key)
+        .map_err(|_| TokenKeyError::Crypto)?;
+    let dek = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, &data_key).map_err(|_| TokenKeyError::Crypto)?,
+    );
+
+    let aad = identity_id.to_string();
+    Ok(Sealed {
+        ciphertext: seal_with(&dek, aad.as_bytes(), token.as_bytes())?,
+        data_key: seal_with(keys.key(&keys.current)?, keys.current.as_bytes(), &data_key)?,
+        key_id: keys.current.clone(),
+    })
+}
+
+async fn write(db: &PgPool, identity_id: i64, sealed: &Sealed) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE user_identities
+        SET access_token_ciphertext = $2, access_token_data_key = $3, access_token_key_id = $4,
+            legacy_access_token = NULL
+        WHERE id = $1
+        "#,
+    )
+    .bind(identity_id)
+    .bind(&sealed.ciphertext)
+    .bind(&sealed.data_key)
+    .bind(&sealed.key_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Stores the access token from a login or link on the identity it came with.
+pub async fn store(
+    db: &PgPool,
+    keys: &TokenKeys,
+    identity: &UpstreamIdentity,
+) -> Result<(), TokenKeyError> {
+    let identity_id: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM user_identities WHERE provider = $1 AND subject = $2")
+            .bind(&identity.provider)
+            .bind(&identity.subject)
+            .fetch_optional(db)
+            .await?;
+    let Some(identity_id) = identity_id else {
+        return Ok(());
+    };
+
+    let sealed = seal(keys, identity_id, &identity.access_token)?;
+    write(db, identity_id, &sealed).await?;
+    Ok(())
+}
+
+/// Encrypts plaintext tokens carried over from `users.access_token` by the migration that
+/// introduced encryption. Runs at startup; a no-op once they are all done.
+pub async fn encrypt_legacy(db: &PgPool, keys: &TokenKeys) -> Result<u64, TokenKeyError> {
+    let rows: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT id, legacy_access_token FROM user_identities WHERE legacy_access_token IS NOT NULL",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for (identity_id, token) in &rows {
+        let sealed = seal(keys, *identity_id, token)?;
+        write(db, *identity_id, &sealed).await?;
+    }
+    Ok(rows.len() as u64)
+}
+
+/// Re-seals every data key not already under the current key-encryption key. The tokens
+/// themselves are untouched.
+pub async fn rewrap(db: &PgPool, keys: &TokenKeys) -> Result<u64, TokenKeyError> {
+    let rows: Vec<(i64, Vec<u8>, String)> = sqlx::query_as(
+        r#"
+        SELECT id, access_token_data_key, access_token_key_id FROM user_identities
+        WHERE access_token_key_id IS NOT NULL AND access_token_key_id <> $1
+        "#,
+    )
+    .bind(&keys.current)
+    .fetch_all(db)
+    .await?;
+
+    let current = keys.key(&keys.current)?;
+    for (identity_id, data_key, key_id) in &rows {
+        let data_key = open_with(keys.key(key_id)?, key_id.as_bytes(), data_key)?;
+        let data_key = seal_with(current, keys.current.as_bytes(), &data_key)?;
+        sqlx::query(
+            "UPDATE user_identities SET access_token_data_key = $2, access_token_key_id = $3 WHERE id = $1",
+        )
+        .bind(identity_id)
+        .bind(&data_key)
+        .bind(&keys.current)
+        .execute(db)
+        .await?;
+    }
+    Ok(rows.len() as u64)
+}
@@ -0,0 +1,491 @@
+//! Upstream identity providers users can log in with.
+//!
+//! The set of providers comes from `OAUTH_PROVIDERS`, a comma-separated list of names. Each name
+//! is what the provider is called in URLs (`/oauth/start/<name>`) and in `bff_tokens.login_method`,
+//! and is configured by `OAUTH_<NAME>_*` variables (name upper-cased, `-` replaced by `_`).
+//! `github`, `google` and `discord` are built in and only need a client id and secret; any other
+//! name is a generic OpenID Connect provider whose endpoints are discovered from its issuer at
+//! startup. `gitlab` is a generic provider with `https://gitlab.com` as the default issuer.
+//!
+//! Every login uses PKCE. `/internal/oauth/start` keeps the verifier, and for OpenID Connect
+//! providers a nonce, in `oauth_login_requests` under the flow's `state`; the exchange has to
+//! present the same state to use them, so a code is only redeemed by the flow that asked for it.
+//! The nonce is checked against the ID token returned alongside the access token.
+
+use std::collections::HashMap;
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use oauth2::{
+    AuthUrl, ClientId, ClientSecret, CsrfToken, EndpointNotSet, EndpointSet, ExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, StandardRevocableToken,
+    StandardTokenResponse, TokenUrl,
+    basic::{
+        BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+        BasicTokenType,
+    },
+    reqwest::{self, Client},
+    url::Url,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::config::Secret;
+
+/// Providers that only need a client id and secret.
+pub(super) const BUILT_IN: [&str; 3] = ["github", "google", "discord"];
+
+/// One provider from `OAUTH_PROVIDERS` with its `OAUTH_<NAME>_*` settings.
+#[derive(Debug, Clone)]
+pub struct ProviderSettings {
+    pub name: String,
+    pub client_id: String,
+    pub client_secret: Secret,
+    /// Where a generic provider's endpoints are discovered from. `None` for the built-in ones.
+    pub issuer: Option<String>,
+    pub display_name: Option<String>,
+    pub scopes: Option<String>,
+    pub subject_claim: Option<String>,
+    pub username_claim: Option<String>,
+    pub email_claim: Option<String>,
+}
+
+/// The OpenID Connect ID token some providers return next to the access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenFields {
+    pub id_token: Option<String>,
+}
+
+impl ExtraTokenFields for IdTokenFields {}
+
+type UnconfiguredClient = oauth2::Client<
+    BasicErrorResponse,
+    StandardTokenResponse<IdTokenFields, BasicTokenType>,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// A `BasicClient` whose token responses keep the ID token, with auth and token URLs set.
+pub type ProviderClient = oauth2::Client<
+    BasicErrorResponse,
+    StandardTokenResponse<IdTokenFields, BasicTokenType>,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointSet,
+>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("{0} must be set")]
+    Missing(String),
+
+    #[error("{var} is not a valid URL: {source}")]
+    InvalidUrl {
+        var: String,
+        source: oauth2::url::ParseError,
+    },
+
+    #[error("could not discover the OpenID configuration of {name}: {source}")]
+    Discovery {
+        name: String,
+        source: reqwest::Error,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdTokenError {
+    #[error("the provider returned no ID token")]
+    Missing,
+
+    #[error(transparent)]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+
+    #[error("the ID token's nonce does not match the login request")]
+    NonceMismatch,
+}
+
+/// Where the fields we need live in the provider's userinfo response.
+#[derive(Debug, Clone)]
+pub struct ClaimMap {
+    /// The provider's stable account id.
+    pub subject: String,
+    pub username: String,
+    pub email: Option<String>,
+    /// Boolean claim saying whether `email` is verified. `None` trusts every email.
+    pub email_verified: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Provider {
+    pub name: String,
+    pub display_name: String,
+    client: ProviderClient,
+    scopes: Vec<String>,
+    pub userinfo_url: String,
+    pub claims: ClaimMap,
+    /// Issuer of the provider's ID tokens when it speaks OpenID Connect and `openid` is among the
+    /// scopes. Logins then carry a nonce.
+    id_token_issuer: Option<String>,
+}
+
+/// A login sent to the provider, to be stored until the browser comes back with a code.
+pub struct LoginRequest {
+    pub url: Url,
+    pub state: String,
+    pkce_verifier: String,
+    nonce: Option<String>,
+}
+
+/// Secrets of a stored [`LoginRequest`] that the code exchange needs.
+pub struct LoginSecrets {
+    pub pkce_verifier: PkceCodeVerifier,
+    pub nonce: Option<String>,
+    /// The invite code a signup was started with, redeemed if the login creates an account.
+    pub invite_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    nonce: Option<String>,
+}
+
+impl Provider {
+    pub fn client(&self) -> &ProviderClient {
+        &self.client
+    }
+
+    pub fn authorize_url(&self) -> LoginRequest {
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = self
+            .id_token_issuer
+            .as_ref()
+            .map(|_| CsrfToken::new_random().secret().clone());
+
+        let mut request = self
+            .client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(self.scopes.iter().cloned().map(Scope::new))
+            .set_pkce_challenge(challenge);
+        if let Some(nonce) = &nonce {
+            request = request.add_extra_param("nonce", nonce.clone());
+        }
+        let (url, state) = request.url();
+
+        LoginRequest {
+            url,
+            state: state.secret().clone(),
+            pkce_verifier: verifier.secret().clone(),
+            nonce,
+        }
+    }
+
+    /// Checks the ID token from a code exchange against the login's nonce. The token came
+    /// straight from the provider's token endpoint over TLS, so, as OpenID Connect Core 3.1.3.7
+    /// allows, its signature is not checked; issuer, audience and expiry are.
+    pub fn check_id_token(&self, id_token: Option<&str>, nonce: &str) -> Result<(), IdTokenError> {
+        let Some(issuer) = &self.id_token_issuer else {
+            return Ok(());
+        };
+        let id_token = id_token.ok_or(IdTokenError::Missing)?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.insecure_disable_signature_validation();
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[self.client.client_id().as_str()]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(
+            id_token,
+            &DecodingKey::from_secret(&[]),
+            &validation,
+        )?
+        .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(IdTokenError::NonceMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Remembers a login's PKCE verifier, nonce and invite code under its state, clearing out
+/// abandoned ones.
+pub async fn store_login_request(
+    db: &PgPool,
+    provider: &str,
+    request: &LoginRequest,
+    invite_code: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM oauth_login_requests WHERE expires_at <= NOW()")
+        .execute(db)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_login_requests (state, provider, pkce_verifier, nonce, invite_code)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(&request.state)
+    .bind(provider)
+    .bind(&request.pkce_verifier)
+    .bind(&request.nonce)
+    .bind(invite_code)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Consumes the login request started with `state` for `provider`. `None` if there is none, it
+/// expired, or it was already used.
+pub async fn take_login_request(
+    db: &PgPool,
+    provider: &str,
+    state: &str,
+) -> Result<Option<LoginSecrets>, sqlx::Error> {
+    let row: Option<(String, Option<String>, Option<String>)> = sqlx::query_as(
+        r#"
+        DELETE FROM oauth_login_requests
+        WHERE state = $1 AND provider = $2 AND expires_at > NOW()
+        RETURNING pkce_verifier, nonce, invite_code
+        "#,
+    )
+    .bind(state)
+    .bind(provider)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|(verifier, nonce, invite_code)| LoginSecrets {
+        pkce_verifier: PkceCodeVerifier::new(verifier),
+        nonce,
+        invite_code,
+    }))
+}
+
+/// What the BFF needs to render a login button.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub name: String,
+    pub display_name: String,
+}
+
+/// The configured providers, in the order they are listed in `OAUTH_PROVIDERS`.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Provider>,
+}
+
+impl ProviderRegistry {
+    /// Loads every configured provider, running OpenID discovery for the generic ones. Redirect
+    /// URIs point at the BFF's `/oauth/callback/<name>`.
+    pub async fn load(
+        settings: &[ProviderSettings],
+        bff_callback_url: &str,
+    ) -> Result<Self, ProviderError> {
+        let http = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Could not build http_Client");
+
+        let mut providers = Vec::new();
+        for settings in settings {
+            let provider = load_provider(&http, settings, bff_callback_url).await?;
+            tracing::info!(provider = %provider.name, "loaded oauth provider");
+            providers.push(provider);
+        }
+        Ok(Self { providers })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|p| p.name == name)
+    }
+
+    pub fn list(&self) -> Vec<ProviderInfo> {
+        self.providers
+            .iter()
+            .map(|p| ProviderInfo {
+                name: p.name.clone(),
+                display_name: p.display_name.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Endpoints of a built-in provider or a discovered OpenID Connect one.
+struct Endpoints {
+    auth_url: String,
+    token_url: String,
+    userinfo_url: String,
+    issuer: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+async fn load_provider(
+    http: &Client,
+    settings: &ProviderSettings,
+    bff_callback_url: &str,
+) -> Result<Provider, ProviderError> {
+    let name = settings.name.as_str();
+    let prefix = format!("OAUTH_{}_", name.to_uppercase().replace('-', "_"));
+
+    let (endpoints, default_scopes, default_display, mut claims) = match name {
+        "github" => (
+            Endpoints {
+                auth_url: "https://github.com/login/oauth/authorize".into(),
+                token_url: "https://github.com/login/oauth/access_token".into(),
+                userinfo_url: "https://api.github.com/user".into(),
+                issuer: None,
+            },
+            "read:user user:email",
+            "GitHub",
+            ClaimMap {
+                subject: "id".into(),
+                username: "login".into(),
+                email: None,
+                email_verified: None,
+            },
+        ),
+        "google" => (
+            Endpoints {
+                auth_url: "https://accounts.google.com/o/oauth2/auth".into(),
+                token_url: "https://oauth2.googleapis.com/token".into(),
+                userinfo_url: "https://www.googleapis.com/oauth2/v2/userinfo".into(),
+                issuer: Some("https://accounts.google.com".into()),
+            },
+            "profile email openid",
+            "Google",
+            ClaimMap {
+                subject: "id".into(),
+                username: "name".into(),
+                email: Some("email".into()),
+                email_verified: Some("verified_email".into()),
+            },
+        ),
+        "discord" => (
+            Endpoints {
+                auth_url: "https://discord.com/oauth2/authorize".into(),
+                token_url: "https://discord.com/api/oauth2/token".into(),
+                userinfo_url: "https://discord.com/api/users/@me".into(),
+                issuer: None,
+            },
+            "identify email",
+            "Discord",
+            ClaimMap {
+                subject: "id".into(),
+                username: "username".into(),
+                email: Some("email".into()),
+                email_verified: Some("verified".into()),
+            },
+        ),
+        _ => {
+            let issuer = settings
+                .issuer
+                .as_deref()
+                .ok_or_else(|| ProviderError::Missing(format!("{prefix}ISSUER")))?;
+            (
+                discover(http, name, issuer).await?,
+                "openid profile email",
+                if name == "gitlab" { "GitLab" } else { name },
+                ClaimMap {
+                    subject: "sub".into(),
+                    username: "preferred_username".into(),
+                    email: Some("email".into()),
+                    email_verified: Some("email_verified".into()),
+                },
+            )
+        }
+    };
+
+    if let Some(claim) = &settings.subject_claim {
+        claims.subject = claim.clone();
+    }
+    if let Some(claim) = &settings.username_claim {
+        claims.username = claim.clone();
+    }
+    if let Some(claim) = &settings.email_claim {
+        claims.email = Some(claim.clone());
+    }
+    let scopes: Vec<String> = settings
+        .scopes
+        .as_deref()
+        .unwrap_or(default_scopes)
+        .split([' ', ','])
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let url = |var: String, value: String| {
+        Url::parse(&value).map_err(|source| ProviderError::InvalidUrl { var, source })
+    };
+    let auth_url = AuthUrl::from_url(url(format!("{prefix}ISSUER"), endpoints.auth_url)?);
+    let token_url = TokenUrl::from_url(url(format!("{prefix}ISSUER"), endpoints.token_url)?);
+    let redirect_url = RedirectUrl::from_url(url(
+        "BFF_CALLBACK_URL".into(),
+        format!("{bff_callback_url}/oauth/callback/{name}"),
+    )?);
+
+    let client = UnconfiguredClient::new(ClientId::new(settings.client_id.clone()))
+        .set_client_secret(ClientSecret::new(settings.client_secret.expose().to_string()))
+        .set_auth_uri(auth_url)
+        .set_token_uri(token_url)
+        .set_redirect_uri(redirect_url);
+
+    Ok(Provider {
+        name: name.to_string(),
+        display_name: settings
+            .display_name
+            .clone()
+            .unwrap_or_else(|| default_display.to_string()),
+        client,
+        userinfo_url: endpoints.userinfo_url,
+        claims,
+        id_token_issuer: endpoints
+            .issuer
+            .filter(|_| scopes.iter().any(|s| s == "openid")),
+        scopes,
+    })
+}
+
+async fn discover(http: &Client, name: &str, issuer: &str) -> Result<Endpoints, ProviderError> {
+    let discovery_err = |source| ProviderError::Discovery {
+        name: name.to_string(),
+        source,
+    };
+    let doc: Discovery = http
+        .get(format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        ))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(discovery_err)?
+        .json()
+        .await
+        .map_err(discovery_err)?;
+
+    Ok(Endpoints {
+        auth_url: doc.authorization_endpoint,
+        token_url: doc.token_endpoint,
+        userinfo_url: doc.userinfo_endpoint,
+        issuer: Some(doc.issuer),
+    })
+}
+
+/// Reads a claim from a userinfo response as a string. Numeric ids are stringified.
+pub fn claim(info: &HashMap<String, serde_json::Value>, name: &str) -> Option<String> {
+    match info.get(name)? {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
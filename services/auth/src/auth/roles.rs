@@ -0,0 +1,737 @@
+//! Admin management of roles and permissions themselves: create, rename or re-describe, delete,
+//! and which roles a role inherits from. Assigning them to users and roles lives with the other
+//! admin endpoints in `internal`.
+//!
+//! A role grants its own permissions and, transitively, those of its parents; `role_ancestors`
+//! resolves that with a recursive CTE and every permission check goes through it. Adding a parent
+//! that already inherits from the role would make a cycle and is refused.
+//!
+//! Role grants may expire. Permission checks read `active_user_roles`, which already leaves out
+//! expired grants; [`sweep_expired_grants`] deletes them afterwards and audits each one.
+//!
+//! Two things are never allowed, whichever endpoint is used: deleting or renaming the `admin`
+//! role, and leaving no user with `manage_permissions`, which would lock everyone out of the admin
+//! panel. Every change is audited in the same transaction.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+
+use super::audit::{self, AdminActor, Event};
+use super::email_verification;
+use super::internal::{AdminRoleResp, InternalState, PermissionResp};
+
+/// The role the initial migrations grant every admin permission to.
+const ADMIN_ROLE: &str = "admin";
+const MAX_NAME_LEN: usize = 64;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/admin/roles", post(create_role))
+        .route(
+            "/internal/admin/roles/{role_id}",
+            patch(update_role).delete(delete_role),
+        )
+        .route(
+            "/internal/admin/roles/{role_id}/parents/{parent_id}",
+            post(assign_parent).delete(revoke_parent),
+        )
+        .route(
+            "/internal/admin/users/{user_id}/permissions",
+            get(user_effective_permissions),
+        )
+        .route("/internal/admin/permissions", post(create_permission))
+        .route(
+            "/internal/admin/permissions/{permission_id}",
+            patch(update_permission).delete(delete_permission),
+        )
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(super) enum RbacError {
+    #[error("The admin role cannot be renamed or deleted")]
+    AdminRole,
+
+    #[error("At least one user must keep the manage_permissions permission without an expiry")]
+    LastPermissionAdmin,
+
+    #[error("That name is already taken")]
+    NameTaken,
+
+    #[error("Names must be 1 to 64 characters of lowercase letters, digits and underscores")]
+    InvalidName,
+
+    #[error("That role already inherits from this one")]
+    Cycle,
+
+    #[error("Not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// The response for an [`RbacError`], logging database errors under `context`.
+pub(super) fn error_response(e: RbacError, context: &str) -> Response {
+    match e {
+        RbacError::NotFound => StatusCode::NOT_FOUND.into_response(),
+        RbacError::InvalidName => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        RbacError::AdminRole
+        | RbacError::LastPermissionAdmin
+        | RbacError::NameTaken
+        | RbacError::Cycle => {
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        RbacError::Db(e) => {
+            tracing::error!(error = %e, "{context}: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Serializes RBAC changes so two concurrent removals can't each leave the other's
+/// `manage_permissions` holder as the last one. Take it first thing in the transaction.
+pub(super) async fn lock_rbac_changes(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('rbac_changes'))")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Fails with [`RbacError::LastPermissionAdmin`] if no enabled user holds `manage_permissions`
+/// through a grant that never expires. Expiring grants don't count: the sweeper removes them
+/// without checking, which would leave nobody able to manage roles.
+/// Call after making a change, inside its transaction, before committing.
+pub(super) async fn ensure_permission_admin_remains(
+    conn: &mut PgConnection,
+) -> Result<(), RbacError> {
+    let holders: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(DISTINCT ur.user_id)
+        FROM user_roles ur
+        JOIN users u ON u.id = ur.user_id
+        JOIN role_ancestors ra ON ra.role_id = ur.role_id
+        JOIN role_permissions rp ON rp.role_id = ra.ancestor_id
+        JOIN permissions p ON p.id = rp.permission_id
+        WHERE p.name = 'manage_permissions' AND ur.expires_at IS NULL AND u.disabled_at IS NULL
+        "#,
+    )
+    .fetch_one(conn)
+    .await?;
+    if holders == 0 {
+        return Err(RbacError::LastPermissionAdmin);
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<(), RbacError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(RbacError::InvalidName)
+    }
+}
+
+fn name_taken(e: sqlx::Error) -> RbacError {
+    match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => RbacError::NameTaken,
+        e => RbacError::Db(e),
+    }
+}
+
+/// A blank description clears it.
+fn normalize_description(description: Option<String>) -> Option<String> {
+    description
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+}
+
+#[derive(Deserialize)]
+struct CreateReq {
+    name: String,
+    description: Option<String>,
+}
+
+/// Both fields are optional; omitted ones are left as they are.
+#[derive(Deserialize)]
+struct UpdateReq {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct NamedRow {
+    id: i32,
+    name: String,
+    description: Option<String>,
+}
+
+impl NamedRow {
+    fn snapshot(&self) -> serde_json::Value {
+        json!({ "name": self.name, "description": self.description })
+    }
+}
+
+// ---- Roles ----
+
+#[tracing::instrument(name = "admin.create_role", skip_all)]
+async fn create_role(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Json(req): Json<CreateReq>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.role.create", &headers).actor(actor.user_id);
+    let result = async {
+        let name = req.name.trim();
+        validate_name(name)?;
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let role: NamedRow = sqlx::query_as(
+            "INSERT INTO roles (name, description) VALUES ($1, $2) RETURNING id, name, description",
+        )
+        .bind(name)
+        .bind(normalize_description(req.description))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(name_taken)?;
+        let event = event
+            .target(format!("role {} (#{})", role.name, role.id))
+            .after(role.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok::<_, RbacError>(role)
+    }
+    .await;
+
+    match result {
+        Ok(role) => {
+            tracing::info!(actor = actor.user_id, role_id = role.id, name = %role.name, "created role");
+            (
+                StatusCode::CREATED,
+                Json(AdminRoleResp {
+                    id: role.id,
+                    name: role.name,
+                    description: role.description,
+                    permissions: vec![],
+                    parents: vec![],
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, "create_role"),
+    }
+}
+
+#[tracing::instrument(name = "admin.update_role", skip_all, fields(role_id))]
+async fn update_role(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(role_id): Path<i32>,
+    Json(req): Json<UpdateReq>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.role.update", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let before: NamedRow =
+            sqlx::query_as("SELECT id, name, description FROM roles WHERE id = $1 FOR UPDATE")
+                .bind(role_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RbacError::NotFound)?;
+
+        let name = match req.name.as_deref().map(str::trim) {
+            Some(name) if name != before.name => {
+                if before.name == ADMIN_ROLE {
+                    return Err(RbacError::AdminRole);
+                }
+                validate_name(name)?;
+                name.to_string()
+            }
+            _ => before.name.clone(),
+        };
+        let description = match req.description {
+            Some(d) => normalize_description(Some(d)),
+            None => before.description.clone(),
+        };
+
+        let after: NamedRow = sqlx::query_as(
+            "UPDATE roles SET name = $2, description = $3 WHERE id = $1 RETURNING id, name, description",
+        )
+        .bind(role_id)
+        .bind(&name)
+        .bind(&description)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(name_taken)?;
+
+        let event = event
+            .target(format!("role {} (#{role_id})", after.name))
+            .before(before.snapshot())
+            .after(after.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, role_id, "updated role");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "update_role"),
+    }
+}
+
+/// Deletes a role, taking it away from every user who had it.
+#[tracing::instrument(name = "admin.delete_role", skip_all, fields(role_id))]
+async fn delete_role(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(role_id): Path<i32>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.role.delete", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let role: NamedRow =
+            sqlx::query_as("DELETE FROM roles WHERE id = $1 RETURNING id, name, description")
+                .bind(role_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RbacError::NotFound)?;
+        if role.name == ADMIN_ROLE {
+            return Err(RbacError::AdminRole);
+        }
+        ensure_permission_admin_remains(&mut tx).await?;
+
+        let event = event
+            .target(format!("role {} (#{role_id})", role.name))
+            .before(role.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, role_id, "deleted role");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "delete_role"),
+    }
+}
+
+// ---- Inheritance ----
+
+#[tracing::instrument(name = "admin.assign_role_parent", skip_all, fields(role_id, parent_id))]
+async fn assign_parent(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path((role_id, parent_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.role_parent.assign", &headers).actor(actor.user_id);
+    match change_parents(&state.db, role_id, parent_id, event, true).await {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, role_id, parent_id, "added role parent");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "assign_role_parent"),
+    }
+}
+
+#[tracing::instrument(name = "admin.revoke_role_parent", skip_all, fields(role_id, parent_id))]
+async fn revoke_parent(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path((role_id, parent_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.role_parent.revoke", &headers).actor(actor.user_id);
+    match change_parents(&state.db, role_id, parent_id, event, false).await {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, role_id, parent_id, "removed role parent");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "revoke_role_parent"),
+    }
+}
+
+/// Adds or removes `parent_id` as a parent of `role_id` and audits the parent names before and
+/// after, in one transaction. No-op changes are not audited.
+async fn change_parents(
+    db: &PgPool,
+    role_id: i32,
+    parent_id: i32,
+    event: Event,
+    assign: bool,
+) -> Result<(), RbacError> {
+    let mut tx = db.begin().await?;
+    lock_rbac_changes(&mut tx).await?;
+    let names: Vec<(i32, String)> =
+        sqlx::query_as("SELECT id, name FROM roles WHERE id IN ($1, $2) FOR UPDATE")
+            .bind(role_id)
+            .bind(parent_id)
+            .fetch_all(&mut *tx)
+            .await?;
+    let Some((_, name)) = names.iter().find(|(id, _)| *id == role_id) else {
+        return Err(RbacError::NotFound);
+    };
+    if !names.iter().any(|(id, _)| *id == parent_id) {
+        return Err(RbacError::NotFound);
+    }
+
+    let before = parent_names(&mut tx, role_id).await?;
+    if assign {
+        let inherits: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM role_ancestors WHERE role_id = $1 AND ancestor_id = $2)",
+        )
+        .bind(parent_id)
+        .bind(role_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if inherits {
+            return Err(RbacError::Cycle);
+        }
+        sqlx::query(
+            "INSERT INTO role_parents (role_id, parent_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(role_id)
+        .bind(parent_id)
+        .execute(&mut *tx)
+        .await?;
+    } else {
+        sqlx::query("DELETE FROM role_parents WHERE role_id = $1 AND parent_id = $2")
+            .bind(role_id)
+            .bind(parent_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let after = parent_names(&mut tx, role_id).await?;
+
+    if before != after {
+        ensure_permission_admin_remains(&mut tx).await?;
+        let event = event
+            .target(format!("role {name} (#{role_id})"))
+            .before(json!({ "parents": before }))
+            .after(json!({ "parents": after }));
+        audit::record(&mut *tx, event).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn parent_names(conn: &mut PgConnection, role_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT r.name FROM role_parents rp JOIN roles r ON r.id = rp.parent_id WHERE rp.role_id = $1 ORDER BY r.name",
+    )
+    .bind(role_id)
+    .fetch_all(conn)
+    .await
+}
+
+#[derive(Serialize)]
+struct EffectivePermissionsResp {
+    email_verified: bool,
+    /// The email policy currently withholds every permission from this user.
+    withheld: bool,
+    permissions: Vec<EffectivePermission>,
+}
+
+#[derive(Serialize)]
+struct EffectivePermission {
+    name: String,
+    /// Each chain of roles granting it, from a role the user holds to the role that has the
+    /// permission, shortest first.
+    via: Vec<Vec<String>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct GrantRow {
+    name: String,
+    path: Vec<String>,
+}
+
+/// What a user can do and why: every permission they get through their roles, with the role path
+/// that granted it.
+#[tracing::instrument(name = "admin.user_effective_permissions", skip_all, fields(user_id))]
+async fn user_effective_permissions(
+    State(state): State<InternalState>,
+    _actor: AdminActor,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let result = async {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+            .bind(user_id)
+            .fetch_one(&state.db)
+            .await?;
+        if !exists {
+            return Err(RbacError::NotFound);
+        }
+        let email_verified = email_verification::is_verified(&state.db, user_id).await?;
+        let grants: Vec<GrantRow> = sqlx::query_as(
+            r#"
+            SELECT p.name, ra.path
+            FROM active_user_roles ur
+            JOIN role_ancestors ra ON ra.role_id = ur.role_id
+            JOIN role_permissions rp ON rp.role_id = ra.ancestor_id
+            JOIN permissions p ON p.id = rp.permission_id
+            WHERE ur.user_id = $1
+            ORDER BY p.name, cardinality(ra.path), ra.path
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&state.db)
+        .await?;
+
+        let mut permissions: Vec<EffectivePermission> = Vec::new();
+        for grant in grants {
+            match permissions.last_mut() {
+                Some(last) if last.name == grant.name => last.via.push(grant.path),
+                _ => permissions.push(EffectivePermission {
+                    name: grant.name,
+                    via: vec![grant.path],
+                }),
+            }
+        }
+        Ok(EffectivePermissionsResp {
+            email_verified,
+            withheld: !email_verified && state.email_policy.hides_permissions(),
+            permissions,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(resp) => Json(resp).into_response(),
+        Err(e) => error_response(e, "user_effective_permissions"),
+    }
+}
+
+// ---- Permissions ----
+
+#[tracing::instrument(name = "admin.create_permission", skip_all)]
+async fn create_permission(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Json(req): Json<CreateReq>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.permission.create", &headers).actor(actor.user_id);
+    let result = async {
+        let name = req.name.trim();
+        validate_name(name)?;
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let permission: NamedRow = sqlx::query_as(
+            "INSERT INTO permissions (name, description) VALUES ($1, $2) RETURNING id, name, description",
+        )
+        .bind(name)
+        .bind(normalize_description(req.description))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(name_taken)?;
+        let event = event
+            .target(format!("permission {} (#{})", permission.name, permission.id))
+            .after(permission.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok::<_, RbacError>(permission)
+    }
+    .await;
+
+    match result {
+        Ok(p) => {
+            tracing::info!(actor = actor.user_id, permission_id = p.id, name = %p.name, "created permission");
+            (
+                StatusCode::CREATED,
+                Json(PermissionResp {
+                    id: p.id,
+                    name: p.name,
+                    description: p.description,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, "create_permission"),
+    }
+}
+
+/// Renaming a permission changes what services see in the JWT, so callers checking the old name
+/// stop matching; the admin panel warns about this.
+#[tracing::instrument(name = "admin.update_permission", skip_all, fields(permission_id))]
+async fn update_permission(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(permission_id): Path<i32>,
+    Json(req): Json<UpdateReq>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.permission.update", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let before: NamedRow = sqlx::query_as(
+            "SELECT id, name, description FROM permissions WHERE id = $1 FOR UPDATE",
+        )
+        .bind(permission_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(RbacError::NotFound)?;
+
+        let name = match req.name.as_deref().map(str::trim) {
+            Some(name) if name != before.name => {
+                validate_name(name)?;
+                name.to_string()
+            }
+            _ => before.name.clone(),
+        };
+        let description = match req.description {
+            Some(d) => normalize_description(Some(d)),
+            None => before.description.clone(),
+        };
+
+        let after: NamedRow = sqlx::query_as(
+            "UPDATE permissions SET name = $2, description = $3 WHERE id = $1 RETURNING id, name, description",
+        )
+        .bind(permission_id)
+        .bind(&name)
+        .bind(&description)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(name_taken)?;
+        // Renaming manage_permissions away is the same as deleting it.
+        ensure_permission_admin_remains(&mut tx).await?;
+
+        let event = event
+            .target(format!("permission {} (#{permission_id})", after.name))
+            .before(before.snapshot())
+            .after(after.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, permission_id, "updated permission");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "update_permission"),
+    }
+}
+
+/// Deletes a permission, taking it away from every role that had it.
+#[tracing::instrument(name = "admin.delete_permission", skip_all, fields(permission_id))]
+async fn delete_permission(
+    State(state): State<InternalState>,
+    actor: AdminActor,
+    headers: HeaderMap,
+    Path(permission_id): Path<i32>,
+) -> impl IntoResponse {
+    let event = Event::new("rbac.permission.delete", &headers).actor(actor.user_id);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        lock_rbac_changes(&mut tx).await?;
+        let permission: NamedRow =
+            sqlx::query_as("DELETE FROM permissions WHERE id = $1 RETURNING id, name, description")
+                .bind(permission_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RbacError::NotFound)?;
+        ensure_permission_admin_remains(&mut tx).await?;
+
+        let event = event
+            .target(format!("permission {} (#{permission_id})", permission.name))
+            .before(permission.snapshot());
+        audit::record(&mut *tx, event).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(actor = actor.user_id, permission_id, "deleted permission");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error_response(e, "delete_permission"),
+    }
+}
+
+// ---- Expired grants ----
+
+#[derive(sqlx::FromRow)]
+struct ExpiredGrant {
+    user_id: i32,
+    username: String,
+    role_name: String,
+    granted_by: Option<i64>,
+    granted_at: Option<DateTime<Utc>>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Deletes role grants that have expired, once a minute, writing a `rbac.user_role.expire` audit
+/// event for each in the same transaction.
+pub(super) async fn sweep_expired_grants(db: PgPool) {
+    loop {
+        match delete_expired_grants(&db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!(expired = n, "removed expired role grants"),
+            Err(e) => tracing::warn!(error = %e, "failed to remove expired role grants"),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
+
+#[tracing::instrument(name = "rbac.sweep_expired_grants", skip_all)]
+async fn delete_expired_grants(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    lock_rbac_changes(&mut tx).await?;
+    let expired: Vec<ExpiredGrant> = sqlx::query_as(
+        r#"
+        DELETE FROM user_roles ur
+        USING users u, roles r
+        WHERE u.id = ur.user_id AND r.id = ur.role_id AND ur.expires_at <= NOW()
+        RETURNING ur.user_id, u.username, r.name AS role_name, ur.granted_by, ur.granted_at,
+                  ur.expires_at
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let headers = HeaderMap::new();
+    for grant in &expired {
+        let event = Event::new("rbac.user_role.expire", &headers)
+            .target(format!("user {} (#{})", grant.username, grant.user_id))
+            .before(json!({
+                "role": grant.role_name,
+                "granted_by": grant.granted_by,
+                "granted_at": grant.granted_at,
+                "expires_at": grant.expires_at,
+            }));
+        audit::record(&mut *tx, event).await?;
+    }
+    tx.commit().await?;
+    Ok(expired.len())
+}
@@ -0,0 +1,215 @@
+//! Authentication of the BFF's calls to `/internal/*`.
+//!
+//! Every request is signed with HMAC-SHA256 under a shared key instead of carrying the secret
+//! itself. The signature covers the method, path and query, a timestamp, a random nonce and a
+//! SHA-256 of the body:
+//!
+//! ```text
+//! x-bff-key-id:    <key id>
+//! x-bff-timestamp: <unix seconds>
+//! x-bff-nonce:     <16 to 64 alphanumeric characters>
+//! x-bff-signature: hex(HMAC-SHA256(key, "{METHOD}\n{path?query}\n{timestamp}\n{nonce}\n{hex(SHA-256(body))}"))
+//! ```
+//!
+//! Requests more than [`MAX_SKEW_SECS`] away from auth's clock are refused, and each nonce is
+//! accepted once: it is recorded in `service_request_nonces` until its timestamp falls out of the
+//! window, which every replica shares. Keys come from `BFF_SERVICE_KEYS`, a comma-separated list
+//! of `<key id>:<secret>`. Auth accepts all of them and the BFF signs with its first, so a key is
+//! rotated by adding the new one after the old on both sides, moving it to the front, and then
+//! dropping the old one, without a synchronized restart.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::State,
+    http::{HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ring::hmac;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use super::internal::InternalState;
+use super::telemetry;
+
+const KEY_ID_HEADER: &str = "x-bff-key-id";
+const TIMESTAMP_HEADER: &str = "x-bff-timestamp";
+const NONCE_HEADER: &str = "x-bff-nonce";
+const SIGNATURE_HEADER: &str = "x-bff-signature";
+
+/// How far a request's timestamp may be from auth's clock, either way.
+const MAX_SKEW_SECS: i64 = 300;
+/// Largest body auth buffers to check the signature. Internal requests are small JSON.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Key id given to `BFF_SERVICE_SECRET` when `BFF_SERVICE_KEYS` isn't set.
+const LEGACY_KEY_ID: &str = "default";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceKeyError {
+    #[error("BFF_SERVICE_KEYS (or BFF_SERVICE_SECRET) must be set")]
+    Missing,
+
+    #[error("BFF_SERVICE_KEYS entry {0:?} is not `<key id>:<secret>`")]
+    InvalidKey(String),
+}
+
+/// Keys the BFF may sign with, by id.
+#[derive(Clone)]
+pub struct ServiceKeys {
+    keys: Arc<HashMap<String, hmac::Key>>,
+}
+
+impl std::fmt::Debug for ServiceKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ids: Vec<&String> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("ServiceKeys")
+            .field("ids", &ids)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServiceKeys {
+    /// Parses `BFF_SERVICE_KEYS`, falling back to a single `BFF_SERVICE_SECRET` under the key id
+    /// `default` for deployments that haven't moved to key ids yet.
+    pub fn parse(keys: Option<&str>, legacy_secret: Option<&str>) -> Result<Self, ServiceKeyError> {
+        let raw = match (keys, legacy_secret) {
+            (Some(keys), _) => keys.to_string(),
+            (None, Some(secret)) => format!("{LEGACY_KEY_ID}:{secret}"),
+            (None, None) => return Err(ServiceKeyError::Missing),
+        };
+
+        let mut keys = HashMap::new();
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let invalid = || ServiceKeyError::InvalidKey(entry.split(':').next().unwrap_or("").into());
+            let (id, secret) = entry.split_once(':').ok_or_else(invalid)?;
+            if id.is_empty() || secret.is_empty() {
+                return Err(invalid());
+            }
+            keys.insert(id.to_string(), hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()));
+        }
+        if keys.is_empty() {
+            return Err(ServiceKeyError::Missing);
+        }
+        Ok(Self { keys: Arc::new(keys) })
+    }
+}
+
+/// What the signature covers; the BFF's signer builds the same string.
+fn string_to_sign(method: &str, path_and_query: &str, timestamp: &str, nonce: &str, body: &[u8]) -> String {
+    format!(
+        "{method}\n{path_and_query}\n{timestamp}\n{nonce}\n{:x}",
+        Sha256::digest(body)
+    )
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn reject(reason: &'static str) -> Response {
+    tracing::warn!(reason, "rejected internal request");
+    telemetry::service_auth_rejected(reason);
+    (StatusCode::UNAUTHORIZED, "Invalid service signature").into_response()
+}
+
+/// Middleware on every `/internal/*` route: checks the signature, the timestamp and that the
+/// nonce is fresh before letting the request through with its body intact.
+pub(super) async fn verify(
+    State(state): State<InternalState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let (parts, body) = req.into_parts();
+    let headers = &parts.headers;
+    let (Some(key_id), Some(timestamp), Some(nonce), Some(signature)) = (
+        header(headers, KEY_ID_HEADER),
+        header(headers, TIMESTAMP_HEADER),
+        header(headers, NONCE_HEADER),
+        header(headers, SIGNATURE_HEADER),
+    ) else {
+        return reject("missing");
+    };
+
+    let Some(key) = state.service_keys.keys.get(key_id) else {
+        return reject("unknown_key");
+    };
+    let Ok(signed_at) = timestamp.parse::<i64>() else {
+        return reject("bad_timestamp");
+    };
+    if (chrono::Utc::now().timestamp() - signed_at).abs() > MAX_SKEW_SECS {
+        return reject("clock_skew");
+    }
+    if !(16..=64).contains(&nonce.len()) || !nonce.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return reject("bad_nonce");
+    }
+    let Some(signature) = decode_hex(signature) else {
+        return reject("bad_signature");
+    };
+
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+    };
+    let path_and_query = parts.uri.path_and_query().map_or("/", |pq| pq.as_str());
+    let message = string_to_sign(parts.method.as_str(), path_and_query, timestamp, nonce, &body);
+    // ring compares the tags in constant time.
+    if hmac::verify(key, message.as_bytes(), &signature).is_err() {
+        return reject("bad_signature");
+    }
+
+    match remember_nonce(&state.db, key_id, nonce, signed_at).await {
+        Ok(true) => {}
+        Ok(false) => return reject("replayed"),
+        Err(e) => {
+            tracing::error!(error = %e, "could not record service request nonce");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+/// Records a nonce until its request can no longer pass the timestamp check. False if it was
+/// already used.
+async fn remember_nonce(db: &PgPool, key_id: &str, nonce: &str, signed_at: i64) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO service_request_nonces (key_id, nonce, expires_at)
+        VALUES ($1, $2, to_timestamp($3) + make_interval(secs => $4))
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(key_id)
+    .bind(nonce)
+    .bind(signed_at as f64)
+    .bind(MAX_SKEW_SECS as f64)
+    .execute(db)
+    .await?;
+    Ok(inserted.rows_affected() == 1)
+}
+
+/// Deletes nonces whose requests are past the skew window, once a minute.
+pub(super) async fn purge_expired_nonces(db: PgPool) {
+    loop {
+        if let Err(e) = sqlx::query("DELETE FROM service_request_nonces WHERE expires_at < NOW()")
+            .execute(&db)
+            .await
+        {
+            tracing::warn!(error = %e, "failed to purge service request nonces");
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
@@ -1,8 +1,11 @@
 use axum::response::IntoResponse;
 use axum_login::tower_sessions::Session;
 use serde::Deserialize;
+use std::time::Duration;
 use tokio::{signal, task::AbortHandle};
 
+use super::health::Health;
+
 const COUNTER_KEY: &str = "counter";
 
 #[derive(Debug, Deserialize, Default)]
@@ -24,7 +27,9 @@ pub async fn handler(session: Session) -> impl IntoResponse {
     tracing::trace!("Current count: {}", counter.0);
 }
 
-pub async fn shutdown_signal(deletion_task_abot_handle: AbortHandle) {
+/// Resolves on SIGINT or SIGTERM, which starts axum's graceful shutdown. On SIGTERM readiness
+/// fails for `drain` first, so the mesh stops sending requests before the listener closes.
+pub async fn shutdown_signal(deletion_task_abot_handle: AbortHandle, health: Health, drain: Duration) {
     let ctrl_c = async {
         signal::ctrl_c().await.expect("Failed to listen for ctrl+C");
     };
@@ -47,6 +52,7 @@ pub async fn shutdown_signal(deletion_task_abot_handle: AbortHandle) {
         }
         _ = terminate => {
             println!("Received SIGTERM signal");
+            health.drain(drain).await;
             deletion_task_abot_handle.abort()
         }
     }
@@ -0,0 +1,447 @@
+//! Ending BFF sessions server-side.
+//!
+//! Logging out marks the opaque token in `bff_tokens` as revoked rather than deleting it, so the
+//! sessions gauge can report revoked and expired sessions separately. Every lookup of a live token
+//! checks `revoked_at IS NULL`, so a revoked token stops working on the next request.
+//!
+//! Each token row also records when and how it was created and the client it was issued to, which
+//! backs the "Your sessions" list on the profile page. Sessions are referred to there by their
+//! numeric `id`; the token itself never leaves the BFF.
+//!
+//! Tokens expire after a period of inactivity ([`TokenLifetimes::idle`]), sliding forward each
+//! time they are used, and never outlive [`TokenLifetimes::absolute`]. The BFF calls
+//! `/internal/token/refresh` on page load; once the token value is older than
+//! [`TokenLifetimes::rotate_after`] that swaps it for a fresh one. The old value is kept as an
+//! alias row, valid for [`TokenLifetimes::rotation_grace`], so requests already in flight with it
+//! don't fail.
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use ulid::Ulid;
+
+use super::audit::{self, Event};
+use super::internal::{InternalState, resolve_token_user};
+use super::telemetry;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/token/revoke", post(revoke))
+        .route("/internal/token/revoke_all", post(revoke_all))
+        .route("/internal/token/refresh", post(refresh))
+        .route("/internal/sessions/list", post(list_sessions))
+        .route("/internal/sessions/revoke", post(revoke_session))
+        .route("/internal/sessions/revoke_others", post(revoke_other_sessions))
+}
+
+/// How long BFF tokens live, in seconds, from the `BFF_TOKEN_*_SECS` settings.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenLifetimes {
+    /// Hard cap from login, however active the session is.
+    pub absolute: i64,
+    /// Inactivity after which the token expires.
+    pub idle: i64,
+    /// Age of a token value after which a refresh replaces it.
+    pub rotate_after: i64,
+    /// How long a rotated-away value keeps working.
+    pub rotation_grace: i64,
+}
+
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Browser user agent forwarded by the BFF as `x-client-user-agent`, truncated for storage.
+pub(super) fn forwarded_user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-client-user-agent")
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.trim().chars().take(MAX_USER_AGENT_LEN).collect::<String>())
+        .filter(|ua| !ua.is_empty())
+}
+
+/// Records that `token` was just used and slides its idle expiry forward. Writes at most once a
+/// minute per token so busy sessions don't turn every introspection into an update. Rotated-away
+/// aliases are left alone so their grace window can't be extended.
+pub(super) async fn touch(
+    db: &PgPool,
+    lifetimes: &TokenLifetimes,
+    token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE bff_tokens
+        SET last_used_at = NOW(),
+            expires_at = LEAST(NOW() + make_interval(secs => $2), absolute_expires_at)
+        WHERE token = $1 AND rotated_at IS NULL
+          AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+        "#,
+    )
+    .bind(token)
+    .bind(lifetimes.idle as f64)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Replaces `token` with a fresh value if it is older than `rotate_after`, keeping the old value
+/// as a grace-window alias. Returns the new token, or `None` when no rotation was due (including
+/// when `token` is itself an alias).
+async fn rotate_if_due(
+    db: &PgPool,
+    lifetimes: &TokenLifetimes,
+    token: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let fresh = Ulid::new().to_string();
+    let mut tx = db.begin().await?;
+    let id: Option<i64> = sqlx::query_scalar(
+        r#"
+        UPDATE bff_tokens SET token = $2, issued_at = NOW()
+        WHERE token = $1 AND rotated_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+          AND issued_at < NOW() - make_interval(secs => $3)
+        RETURNING id
+        "#,
+    )
+    .bind(token)
+    .bind(&fresh)
+    .bind(lifetimes.rotate_after as f64)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(id) = id else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO bff_tokens (
+            token, user_id, login_method, client_ip, user_agent, created_at, last_used_at,
+            issued_at, absolute_expires_at, expires_at, rotated_at, rotated_into
+        )
+        SELECT $1, user_id, login_method, client_ip, user_agent, created_at, last_used_at,
+            NOW(), absolute_expires_at, LEAST(NOW() + make_interval(secs => $3), expires_at), NOW(), id
+        FROM bff_tokens WHERE id = $2
+        "#,
+    )
+    .bind(token)
+    .bind(id)
+    .bind(lifetimes.rotation_grace as f64)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(fresh))
+}
+
+/// Deletes rotated-away aliases once their grace window is over, and every other row a week
+/// after it stopped being valid. The week of history is what the ended-sessions gauge reports.
+pub(super) async fn purge_ended(db: &PgPool) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM bff_tokens
+        WHERE (rotated_at IS NOT NULL AND expires_at < NOW())
+           OR expires_at < NOW() - INTERVAL '7 days'
+        "#,
+    )
+    .execute(db)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Revokes every live token belonging to `user_id`. Returns how many were revoked.
+pub(super) async fn revoke_all_for_user(
+    db: impl PgExecutor<'_>,
+    user_id: i64,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        "UPDATE bff_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(user_id)
+    .execute(db)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+#[derive(Deserialize)]
+struct TokenReq {
+    token: String,
+}
+
+#[derive(Serialize)]
+struct RefreshResp {
+    username: String,
+    /// Replacement for the presented token, when it was due for rotation. The BFF must store it
+    /// in place of the old one, which stops working after the grace window.
+    rotated_token: Option<String>,
+}
+
+/// Confirms the token is still live, extends it, and rotates it when due. `401` means the
+/// session is over and the BFF should forget the token.
+#[tracing::instrument(name = "token.refresh", skip_all)]
+async fn refresh(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        telemetry::token_operation("refresh", "invalid");
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    if let Err(e) = touch(&state.db, &state.token_lifetimes, &req.token).await {
+        tracing::warn!(user_id = user.user_id, error = %e, "refresh: could not extend token");
+    }
+
+    match rotate_if_due(&state.db, &state.token_lifetimes, &req.token).await {
+        Ok(rotated_token) => {
+            if rotated_token.is_some() {
+                tracing::info!(user_id = user.user_id, "bff token rotated");
+                telemetry::token_operation("rotate", "success");
+            }
+            telemetry::token_operation("refresh", "success");
+            Json(RefreshResp {
+                username: user.username,
+                rotated_token,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "refresh: rotation failed");
+            telemetry::token_operation("rotate", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Revokes a single token, together with its rotation aliases or the session it was rotated
+/// into. Idempotent: unknown, expired and already revoked tokens also get `204`, so a logout never
+/// fails because the session had already ended.
+#[tracing::instrument(name = "token.revoke", skip_all)]
+async fn revoke(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let result = sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH target AS (
+            SELECT COALESCE(rotated_into, id) AS session_id FROM bff_tokens WHERE token = $1
+        )
+        UPDATE bff_tokens SET revoked_at = NOW()
+        WHERE (id IN (SELECT session_id FROM target) OR rotated_into IN (SELECT session_id FROM target))
+          AND revoked_at IS NULL
+        RETURNING user_id
+        "#,
+    )
+    .bind(&req.token)
+    .fetch_all(&state.db)
+    .await
+    .map(|ids| ids.into_iter().next());
+
+    match result {
+        Ok(Some(user_id)) => {
+            audit::log(&state.db, Event::new("auth.logout", &headers).actor(user_id)).await;
+            tracing::info!(user_id, "bff token revoked");
+            telemetry::token_operation("revoke", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => {
+            telemetry::token_operation("revoke", "noop");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "revoke: db error");
+            telemetry::token_operation("revoke", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Signs the token's owner out everywhere, including the session making the request.
+#[tracing::instrument(name = "token.revoke_all", skip_all)]
+async fn revoke_all(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        telemetry::token_operation("revoke_all", "invalid");
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    match revoke_all_for_user(&state.db, user.user_id).await {
+        Ok(count) => {
+            audit::log(
+                &state.db,
+                Event::new("auth.logout_all", &headers)
+                    .actor(user.user_id)
+                    .after(json!({ "sessions_revoked": count })),
+            )
+            .await;
+            tracing::info!(user_id = user.user_id, count, "all bff tokens revoked");
+            telemetry::token_operation("revoke_all", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "revoke_all: db error");
+            telemetry::token_operation("revoke_all", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---- Sessions list ----
+
+#[derive(sqlx::FromRow)]
+struct SessionRow {
+    id: i64,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    user_agent: Option<String>,
+    client_ip: Option<String>,
+    login_method: String,
+    current: bool,
+}
+
+#[derive(Serialize)]
+struct SessionResp {
+    id: i64,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    user_agent: Option<String>,
+    client_ip: Option<String>,
+    login_method: String,
+    /// True for the session making the request.
+    current: bool,
+}
+
+impl From<SessionRow> for SessionResp {
+    fn from(row: SessionRow) -> Self {
+        Self {
+            id: row.id,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            user_agent: row.user_agent,
+            client_ip: row.client_ip,
+            login_method: row.login_method,
+            current: row.current,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RevokeSessionReq {
+    token: String,
+    id: i64,
+}
+
+/// Live sessions of the token's owner, most recently used first.
+#[tracing::instrument(name = "sessions.list", skip_all)]
+async fn list_sessions(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let rows: Result<Vec<SessionRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT id, created_at, last_used_at, user_agent, client_ip, login_method,
+            id = (SELECT COALESCE(rotated_into, id) FROM bff_tokens WHERE token = $2) AS current
+        FROM bff_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL AND rotated_at IS NULL AND expires_at > NOW()
+        ORDER BY COALESCE(last_used_at, created_at) DESC
+        "#,
+    )
+    .bind(user.user_id)
+    .bind(&req.token)
+    .fetch_all(&state.db)
+    .await;
+
+    match rows {
+        Ok(rows) => Json(rows.into_iter().map(SessionResp::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "list_sessions: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Revokes one of the caller's own sessions by id.
+#[tracing::instrument(name = "sessions.revoke", skip_all)]
+async fn revoke_session(
+    State(state): State<InternalState>,
+    Json(req): Json<RevokeSessionReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let result = sqlx::query(
+        r#"
+        UPDATE bff_tokens SET revoked_at = NOW()
+        WHERE (id = $1 OR rotated_into = $1) AND user_id = $2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(req.id)
+    .bind(user.user_id)
+    .execute(&state.db)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Ok(_) => {
+            tracing::info!(user_id = user.user_id, session_id = req.id, "session revoked");
+            telemetry::token_operation("revoke", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "revoke_session: db error");
+            telemetry::token_operation("revoke", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Revokes every session of the caller except the one making the request.
+#[tracing::instrument(name = "sessions.revoke_others", skip_all)]
+async fn revoke_other_sessions(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let result = sqlx::query(
+        r#"
+        UPDATE bff_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+          AND COALESCE(rotated_into, id) <> (
+              SELECT COALESCE(rotated_into, id) FROM bff_tokens WHERE token = $2
+          )
+        "#,
+    )
+    .bind(user.user_id)
+    .bind(&req.token)
+    .execute(&state.db)
+    .await;
+
+    match result {
+        Ok(r) => {
+            tracing::info!(user_id = user.user_id, count = r.rows_affected(), "other sessions revoked");
+            telemetry::token_operation("revoke_others", "success");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "revoke_other_sessions: db error");
+            telemetry::token_operation("revoke_others", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
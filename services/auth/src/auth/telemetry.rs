@@ -16,6 +16,14 @@ pub fn token_operation(operation: &str, status: &str) {
     .increment(1);
 }
 
+pub fn service_auth_rejected(reason: &str) {
+    metrics::counter!(
+        "auth_service_auth_rejections_total",
+        "reason" => reason.to_string()
+    )
+    .increment(1);
+}
+
 pub fn ark_command(cmd: &str, status: &str) {
     metrics::counter!(
         "auth_ark_commands_total",
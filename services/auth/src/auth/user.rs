@@ -1,16 +1,25 @@
 use axum::Json;
+use axum::http::HeaderMap;
 use axum_login::{AuthUser, AuthnBackend, UserId};
 use chrono::{DateTime, Utc};
 use oauth2::{
-    AuthorizationCode, CsrfToken, EndpointNotSet, EndpointSet, RedirectUrl, Scope, TokenResponse,
-    basic::{BasicClient, BasicRequestTokenError},
+    AuthorizationCode, TokenResponse,
+    basic::BasicRequestTokenError,
     http::header::{AUTHORIZATION, USER_AGENT},
     reqwest::{self, Client},
 };
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use std::{collections::HashMap, sync::Arc};
 use tokio::task;
+use webauthn_rs::prelude::{PublicKeyCredential, Webauthn};
+
+use super::audit::{self, Event};
+use super::identities::{self, UpstreamIdentity};
+use super::invites::{self, InviteError, RegistrationMode};
+use super::provider_tokens::{self, TokenKeyError, TokenKeys};
+use super::providers::{IdTokenError, LoginSecrets, Provider, ProviderRegistry, claim};
 
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -18,7 +27,9 @@ pub struct User {
     pub username: String,
     email: Option<String>,
     password: Option<String>,
-    access_token: Option<String>,
+    session_key: String,
+    /// Set by an admin; see `accounts`. Disabled users can't log in and aren't loaded into sessions.
+    pub disabled_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -35,13 +46,6 @@ pub struct BffToken {
     pub expires_at: DateTime<Utc>,
 }
 
-#[derive(Clone, Serialize, Deserialize, FromRow)]
-pub struct GoogleUserInfo {
-    email: String,
-    name: Option<String>,
-    picture: Option<String>,
-}
-
 impl std::fmt::Debug for ClientUser {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ClientUser")
@@ -79,36 +83,28 @@ impl AuthUser for User {
     }
 
     /// The session auth hash is used to authenticate the session. This is used to verify that the
-    /// session is still valid.
+    /// session is still valid: changing the password, or `session_key` for accounts without one,
+    /// logs out every session.
     fn session_auth_hash(&self) -> &[u8] {
-        if let Some(access_token) = &self.access_token {
-            return access_token.as_bytes();
-        }
-
         if let Some(password) = &self.password {
             return password.as_bytes();
         }
 
-        &[]
+        self.session_key.as_bytes()
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub enum Credentials {
     Password(PasswordCreds),
-}
-
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum OAuthProvider {
-    Github,
-    Google,
+    Passkey(PasskeyCreds),
 }
 
 #[derive(Debug)]
 pub enum UserError {
     UserAlreadyExists,
     EmailAlreadyInUse,
+    RegistrationClosed,
     DatabaseError(sqlx::Error),
 }
 
@@ -117,6 +113,7 @@ impl std::fmt::Display for UserError {
         match self {
             UserError::UserAlreadyExists => write!(f, "User already exists"),
             UserError::EmailAlreadyInUse => write!(f, "Email already in use"),
+            UserError::RegistrationClosed => write!(f, "Registration is not open"),
             UserError::DatabaseError(err) => write!(f, "Database error: {}", err),
         }
     }
@@ -128,6 +125,7 @@ impl axum::response::IntoResponse for UserError {
             UserError::UserAlreadyExists | UserError::EmailAlreadyInUse => {
                 (axum::http::StatusCode::CONFLICT, self.to_string())
             }
+            UserError::RegistrationClosed => (axum::http::StatusCode::FORBIDDEN, self.to_string()),
             UserError::DatabaseError(_) => (
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal server error".to_string(),
@@ -144,6 +142,15 @@ pub struct PasswordCreds {
     pub username: String,
     pub password: String,
     pub next: Option<String>,
+    /// TOTP or recovery code, required when the account has MFA enabled.
+    pub code: Option<String>,
+}
+
+/// A WebAuthn assertion answering the challenge stored under `ceremony_id`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasskeyCreds {
+    pub ceremony_id: String,
+    pub credential: PublicKeyCredential,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -153,27 +160,43 @@ pub struct SignUpCreds {
     pub password: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct UserInfo {
-    login: String,
-}
-
 #[derive(Debug, thiserror::Error)]
 pub enum BackendError {
     #[error(transparent)]
     Sqlx(sqlx::Error),
 
-    #[error("An account with this email already exists and was created with a password")]
+    #[error("An account with this email already exists")]
     EmailAlreadyInUse,
 
+    #[error("An account with this username already exists")]
+    UsernameTaken,
+
+    #[error("This account has been disabled")]
+    AccountDisabled,
+
+    #[error(transparent)]
+    Invite(#[from] InviteError),
+
+    #[error("This is the account's last way to log in")]
+    LastLoginMethod,
+
+    #[error("The provider's userinfo response has no {0:?} claim")]
+    MissingClaim(String),
+
     #[error(transparent)]
     Reqwest(reqwest::Error),
 
+    #[error(transparent)]
+    IdToken(#[from] IdTokenError),
+
     #[error(transparent)]
     OAuth2(BasicRequestTokenError<<Client as oauth2::AsyncHttpClient<'static>>::Error>),
 
     #[error(transparent)]
     TaskJoin(#[from] tokio::task::JoinError),
+
+    #[error(transparent)]
+    TokenKey(#[from] TokenKeyError),
 }
 
 impl From<sqlx::Error> for BackendError {
@@ -185,176 +208,209 @@ impl From<sqlx::Error> for BackendError {
 #[derive(Debug, Clone)]
 pub struct Backend {
     pub db: sqlx::PgPool,
-    client: BasicClientSet,
-    g_client: BasicClientSet,
+    providers: Arc<ProviderRegistry>,
+    token_keys: Arc<TokenKeys>,
     http_client: Client,
+    pub webauthn: Arc<Webauthn>,
+    registration: RegistrationMode,
+    /// Base URL of the agent that starts and stops the game servers, from `ARK_HOST_URL`.
+    ark_url: Arc<str>,
 }
 
-pub type BasicClientSet =
-    BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
-
 impl Backend {
-    pub fn new(db: sqlx::PgPool, client: BasicClientSet, g_client: BasicClientSet) -> Self {
+    pub fn new(
+        db: sqlx::PgPool,
+        providers: ProviderRegistry,
+        token_keys: TokenKeys,
+        registration: RegistrationMode,
+        bff_callback_url: &str,
+        ark_url: &str,
+    ) -> Self {
         let http_client = reqwest::ClientBuilder::new()
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .expect("Could not build http_Client");
-        let bff_callback_url = std::env::var("BFF_CALLBACK_URL")
-            .unwrap_or_else(|_| "http://localhost:8080".to_string());
-        let g_client = g_client.set_redirect_uri(
-            RedirectUrl::new(format!("{bff_callback_url}/oauth/callback/google"))
-                .expect("invalid redirect uri"),
-        );
-        let client = client.set_redirect_uri(
-            RedirectUrl::new(format!("{bff_callback_url}/oauth/callback/github"))
-                .expect("invalid redirect uri"),
-        );
+        // Checked when the configuration was loaded.
+        let webauthn = super::webauthn::relying_party(bff_callback_url)
+            .expect("BFF_CALLBACK_URL is not a valid WebAuthn origin");
 
         Self {
             db,
-            client,
-            g_client,
+            providers: Arc::new(providers),
+            token_keys: Arc::new(token_keys),
             http_client,
+            webauthn: Arc::new(webauthn),
+            registration,
+            ark_url: ark_url.into(),
+        }
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&Provider> {
+        self.providers.get(name)
+    }
+
+    pub fn providers(&self) -> &ProviderRegistry {
+        &self.providers
+    }
+
+    pub fn registration(&self) -> RegistrationMode {
+        self.registration
+    }
+
+    pub fn ark_url(&self) -> &str {
+        &self.ark_url
+    }
+
+    /// Encrypts and keeps the access token an upstream account just handed us, replacing the one
+    /// from its previous login.
+    pub async fn store_provider_token(
+        &self,
+        identity: &UpstreamIdentity,
+    ) -> Result<(), BackendError> {
+        provider_tokens::store(&self.db, &self.token_keys, identity).await?;
+        Ok(())
+    }
+
+    /// Exchanges an authorization code with `provider`, using the PKCE verifier and nonce of the
+    /// login request it answers, and reads the account it belongs to.
+    pub async fn fetch_identity(
+        &self,
+        provider: &Provider,
+        code: String,
+        secrets: LoginSecrets,
+    ) -> Result<UpstreamIdentity, BackendError> {
+        let token_res = provider
+            .client()
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(secrets.pkce_verifier)
+            .request_async(&self.http_client)
+            .await
+            .map_err(BackendError::OAuth2)?;
+        if let Some(nonce) = &secrets.nonce {
+            provider.check_id_token(token_res.extra_fields().id_token.as_deref(), nonce)?;
         }
+        let access_token = token_res.access_token().secret().to_string();
+
+        let user_info = reqwest::Client::new()
+            .get(&provider.userinfo_url)
+            .header(USER_AGENT.as_str(), "milesstorm-auth")
+            .header(AUTHORIZATION.as_str(), format!("Bearer {access_token}"))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(BackendError::Reqwest)?
+            .json::<HashMap<String, serde_json::Value>>()
+            .await
+            .map_err(BackendError::Reqwest)?;
+
+        let claims = &provider.claims;
+        let subject = claim(&user_info, &claims.subject)
+            .ok_or_else(|| BackendError::MissingClaim(claims.subject.clone()))?;
+        let email = claims.email.as_deref().and_then(|c| claim(&user_info, c));
+        let email_verified = match &claims.email_verified {
+            Some(c) => user_info.get(c).and_then(|v| v.as_bool()) == Some(true),
+            None => true,
+        };
+
+        Ok(UpstreamIdentity {
+            provider: provider.name.clone(),
+            subject,
+            username: claim(&user_info, &claims.username),
+            email,
+            email_verified,
+            access_token,
+        })
     }
 
+    /// Logs in with an upstream account, creating a user the first time it is seen. Accounts are
+    /// matched by the provider's account id only; an existing user who wants to log in with
+    /// another provider links it from their profile.
     pub async fn complete_oauth(
         &self,
-        provider: OAuthProvider,
+        provider: &Provider,
         code: String,
+        mut secrets: LoginSecrets,
+        headers: &HeaderMap,
     ) -> Result<User, BackendError> {
-        match provider {
-            OAuthProvider::Github => {
-                let token_res = self
-                    .client
-                    .exchange_code(AuthorizationCode::new(code))
-                    .request_async(&self.http_client)
-                    .await
-                    .map_err(BackendError::OAuth2)?;
-
-                let user_info = reqwest::Client::new()
-                    .get("https://api.github.com/user")
-                    .header(USER_AGENT.as_str(), "milesstorm-auth")
-                    .header(
-                        AUTHORIZATION.as_str(),
-                        format!("Bearer {}", token_res.access_token().secret()),
-                    )
-                    .send()
-                    .await
-                    .map_err(BackendError::Reqwest)?
-                    .json::<UserInfo>()
-                    .await
-                    .map_err(BackendError::Reqwest)?;
-
-                // The `WHERE users.password IS NULL` guards against account takeover:
-                // if a password account already owns this username, the conflict update is
-                // skipped, RETURNING yields no row, and we treat that as an account collision.
-                let user: Option<User> = sqlx::query_as(
-                    r#"
-                    insert into users (username, access_token)
-                    values ($1, $2)
-                    on conflict(username) do update
-                    set access_token = excluded.access_token
-                    where users.password is null
-                    returning *
-                    "#,
-                )
-                .bind(user_info.login)
-                .bind(token_res.access_token().secret())
-                .fetch_optional(&self.db)
-                .await?;
+        let invite_code = secrets.invite_code.take();
+        let identity = self.fetch_identity(provider, code, secrets).await?;
 
-                user.ok_or(BackendError::EmailAlreadyInUse)
+        if let Some(user_id) = identities::find_user(&self.db, &identity).await? {
+            let user: User = sqlx::query_as("select * from users where id = $1")
+                .bind(user_id)
+                .fetch_one(&self.db)
+                .await?;
+            if user.disabled_at.is_some() {
+                return Err(BackendError::AccountDisabled);
             }
-            OAuthProvider::Google => {
-                let token_res = self
-                    .g_client
-                    .exchange_code(AuthorizationCode::new(code))
-                    .request_async(&self.http_client)
-                    .await
-                    .map_err(BackendError::OAuth2)?;
-
-                let user_info = reqwest::Client::new()
-                    .get("https://www.googleapis.com/oauth2/v2/userinfo")
-                    .header(USER_AGENT.as_str(), "milesstorm-auth")
-                    .header(
-                        AUTHORIZATION.as_str(),
-                        format!("Bearer {}", token_res.access_token().secret()),
-                    )
-                    .send()
-                    .await
-                    .map_err(BackendError::Reqwest)?
-                    .json::<GoogleUserInfo>()
-                    .await
-                    .map_err(BackendError::Reqwest)?;
-
-                // Identify Google users by email (unique on Google's side and in our schema).
-                // Matching on `username` would let two Googlers with the same display name
-                // overwrite each other's row.
-                let existing: Option<User> = sqlx::query_as("select * from users where email = $1")
-                    .bind(&user_info.email)
-                    .fetch_optional(&self.db)
-                    .await?;
+            self.store_provider_token(&identity).await?;
+            return Ok(user);
+        }
 
-                if let Some(existing) = existing {
-                    if existing.password.is_some() {
-                        return Err(BackendError::EmailAlreadyInUse);
-                    }
-                    // Returning Google user — refresh the access token by id (stable).
-                    let user = sqlx::query_as(
-                        "update users set access_token = $1 where id = $2 returning *",
-                    )
-                    .bind(token_res.access_token().secret())
-                    .bind(existing.id)
+        // First-time login. Never attach the upstream account to an existing user by email or
+        // username: that user has to link it themselves.
+        invites::check_allowed(self.registration, invite_code.as_deref())?;
+        if let Some(email) = &identity.email {
+            let taken: bool =
+                sqlx::query_scalar("select exists (select 1 from users where email = $1)")
+                    .bind(email)
                     .fetch_one(&self.db)
                     .await?;
-                    return Ok(user);
-                }
-
-                // First-time Google login. A username collision with another account would
-                // surface as a unique-violation Sqlx error rather than silently overwriting.
-                let username = user_info.name.clone().unwrap_or_else(|| {
-                    user_info
-                        .email
-                        .split('@')
-                        .next()
-                        .unwrap_or("user")
-                        .to_string()
-                });
-
-                let user = sqlx::query_as(
-                    r#"
-                    insert into users (username, email, access_token)
-                    values ($1, $2, $3)
-                    returning *
-                    "#,
-                )
-                .bind(&username)
-                .bind(&user_info.email)
-                .bind(token_res.access_token().secret())
-                .fetch_one(&self.db)
-                .await?;
-
-                Ok(user)
+            if taken {
+                return Err(BackendError::EmailAlreadyInUse);
             }
         }
-    }
 
-    pub fn authorize_url(&self) -> (reqwest::Url, CsrfToken) {
-        self.client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new(String::from("read:user")))
-            .add_scope(Scope::new(String::from("user:email")))
-            .url()
-    }
+        let username = identity.username.clone().unwrap_or_else(|| {
+            identity
+                .email
+                .as_deref()
+                .and_then(|e| e.split('@').next())
+                .unwrap_or("user")
+                .to_string()
+        });
+
+        let mut tx = self.db.begin().await?;
+        let user: User = sqlx::query_as(
+            r#"
+            insert into users (username, email, email_verified_at)
+            values ($1, $2, case when $3 then now() end)
+            returning *
+            "#,
+        )
+        .bind(&username)
+        .bind(&identity.email)
+        .bind(identity.email.is_some() && identity.email_verified)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some("users_username_key") => {
+                BackendError::UsernameTaken
+            }
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some("users_email_key") => {
+                BackendError::EmailAlreadyInUse
+            }
+            e => BackendError::Sqlx(e),
+        })?;
+        identities::insert(&mut *tx, user.id, &identity).await?;
+        let event = Event::new("auth.register", headers)
+            .actor(user.id)
+            .target(format!("user {} (#{})", user.username, user.id))
+            .after(serde_json::json!({
+                "username": user.username,
+                "email": identity.email,
+                "provider": identity.provider,
+            }));
+        audit::record(&mut *tx, event).await?;
+        if let Some(code) = invite_code.as_deref().filter(|c| !c.trim().is_empty()) {
+            let event = Event::new("invite.redeem", headers);
+            invites::redeem(&mut tx, code, user.id, &username, identity.email.as_deref(), event)
+                .await?;
+        }
+        tx.commit().await?;
+        self.store_provider_token(&identity).await?;
 
-    pub fn authorize_g_url(&self) -> (reqwest::Url, CsrfToken) {
-        self.g_client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new(String::from("profile")))
-            .add_scope(Scope::new(String::from("email")))
-            .add_scope(Scope::new(String::from("openid")))
-            .url()
+        Ok(user)
     }
 
     pub async fn register_user(
@@ -363,6 +419,10 @@ impl Backend {
         email: &str,
         password: &str,
     ) -> Result<User, UserError> {
+        if self.registration != RegistrationMode::Open {
+            return Err(UserError::RegistrationClosed);
+        }
+
         // insert into database and return the new user, if the user already exists,
         // return an error indicating if the username or email already exists
 
@@ -407,10 +467,17 @@ impl AuthnBackend for Backend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let Credentials::Password(password_cred) = creds;
+        let password_cred = match creds {
+            Credentials::Password(password_cred) => password_cred,
+            Credentials::Passkey(passkey_cred) => {
+                return Ok(super::webauthn::authenticate(&self.db, &self.webauthn, passkey_cred).await?);
+            }
+        };
 
         let user: Option<Self::User> =
-            sqlx::query_as("select * from users where username = $1 and password is not null")
+            sqlx::query_as(
+                "select * from users where username = $1 and password is not null and disabled_at is null",
+            )
                 .bind(password_cred.username)
                 .fetch_optional(&self.db)
                 .await?;
@@ -429,7 +496,8 @@ impl AuthnBackend for Backend {
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        Ok(sqlx::query_as("select * from users where id = $1")
+        // A session whose user has since been disabled loads no user, which logs it out.
+        Ok(sqlx::query_as("select * from users where id = $1 and disabled_at is null")
             .bind(user_id)
             .fetch_optional(&self.db)
             .await?)
@@ -0,0 +1,565 @@
+//! WebAuthn passkeys as a first-factor login method.
+//!
+//! Both ceremonies are split into a `start` call that returns the options for
+//! `navigator.credentials.create()` / `.get()` plus a `ceremony_id`, and a `finish` call that
+//! takes the browser's response. The challenge state lives in `webauthn_ceremonies`; the BFF
+//! only holds the id. Login uses discoverable credentials, so no username is asked for.
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+};
+use axum_login::AuthnBackend;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgPool, types::Json as SqlJson};
+use webauthn_rs::prelude::{
+    CredentialID, DiscoverableAuthentication, DiscoverableKey, Passkey, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, Url, Uuid, Webauthn, WebauthnBuilder,
+    WebauthnError,
+};
+
+use super::identities;
+use super::internal::{InternalState, TokenResp, create_bff_token, resolve_token_user};
+use super::telemetry;
+use super::user::{BackendError, Credentials, PasskeyCreds, User};
+
+const RP_NAME: &str = "milesstorm.com";
+const MAX_NAME_LEN: usize = 64;
+
+pub(super) fn routes() -> Router<InternalState> {
+    Router::new()
+        .route("/internal/webauthn/register/start", post(register_start))
+        .route("/internal/webauthn/register/finish", post(register_finish))
+        .route("/internal/webauthn/login/start", post(login_start))
+        .route("/internal/webauthn/login/finish", post(login_finish))
+        .route("/internal/webauthn/credentials/list", post(list_credentials))
+        .route("/internal/webauthn/credentials/rename", post(rename_credential))
+        .route("/internal/webauthn/credentials/delete", post(delete_credential))
+}
+
+/// Builds the relying party from the public frontend URL: its host is the RP ID and the URL
+/// itself the only accepted origin.
+pub(super) fn relying_party(public_url: &str) -> Result<Webauthn, WebauthnError> {
+    let origin = Url::parse(public_url).map_err(|_| WebauthnError::Configuration)?;
+    let rp_id = origin
+        .host_str()
+        .ok_or(WebauthnError::Configuration)?
+        .to_string();
+    WebauthnBuilder::new(&rp_id, &origin)?
+        .rp_name(RP_NAME)
+        .build()
+}
+
+// ---- Ceremony state ----
+
+async fn store_ceremony<T: Serialize>(
+    db: &PgPool,
+    kind: &str,
+    user_id: Option<i64>,
+    state: &T,
+) -> Result<String, sqlx::Error> {
+    // Abandoned ceremonies are cleaned up lazily; nothing else reads expired rows.
+    sqlx::query("DELETE FROM webauthn_ceremonies WHERE expires_at <= NOW()")
+        .execute(db)
+        .await?;
+    sqlx::query_scalar(
+        "INSERT INTO webauthn_ceremonies (id, kind, user_id, state) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(ulid::Ulid::new().to_string())
+    .bind(kind)
+    .bind(user_id)
+    .bind(SqlJson(state))
+    .fetch_one(db)
+    .await
+}
+
+/// Removes and returns a live ceremony. Every ceremony is single-use, whether or not the
+/// browser response then verifies.
+async fn take_ceremony<T: for<'de> Deserialize<'de> + Send + Unpin + 'static>(
+    db: &PgPool,
+    id: &str,
+    kind: &str,
+    user_id: Option<i64>,
+) -> Result<Option<T>, sqlx::Error> {
+    let state: Option<SqlJson<T>> = sqlx::query_scalar(
+        r#"
+        DELETE FROM webauthn_ceremonies
+        WHERE id = $1 AND kind = $2 AND user_id IS NOT DISTINCT FROM $3 AND expires_at > NOW()
+        RETURNING state
+        "#,
+    )
+    .bind(id)
+    .bind(kind)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(state.map(|s| s.0))
+}
+
+#[derive(Serialize)]
+struct CeremonyResp {
+    ceremony_id: String,
+    /// `CredentialCreationOptions` / `CredentialRequestOptions` with binary fields base64url-encoded.
+    options: Value,
+}
+
+// ---- Registration (adding a passkey to the logged-in account) ----
+
+#[derive(Deserialize)]
+struct TokenReq {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct RegisterFinishReq {
+    token: String,
+    ceremony_id: String,
+    name: String,
+    credential: RegisterPublicKeyCredential,
+}
+
+#[tracing::instrument(name = "webauthn.register_start", skip_all)]
+async fn register_start(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let prepared: Result<(Uuid, Vec<Vec<u8>>), sqlx::Error> = async {
+        let handle = sqlx::query_scalar(
+            "UPDATE users SET webauthn_user_id = COALESCE(webauthn_user_id, $2) WHERE id = $1 RETURNING webauthn_user_id",
+        )
+        .bind(user.user_id)
+        .bind(Uuid::new_v4())
+        .fetch_one(&state.db)
+        .await?;
+        let existing = sqlx::query_scalar("SELECT credential_id FROM webauthn_credentials WHERE user_id = $1")
+            .bind(user.user_id)
+            .fetch_all(&state.db)
+            .await?;
+        Ok((handle, existing))
+    }
+    .await;
+
+    let (handle, existing) = match prepared {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "register_start: db error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let exclude: Vec<CredentialID> = existing.into_iter().map(CredentialID::from).collect();
+    let (ccr, registration) = match state.backend.webauthn.start_passkey_registration(
+        handle,
+        &user.username,
+        &user.username,
+        Some(exclude),
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "register_start: could not build challenge");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // webauthn-rs leaves residentKey at "discouraged" for passkeys, but login is usernameless
+    // and only works with discoverable credentials.
+    let mut options = serde_json::to_value(&ccr).unwrap_or_default();
+    if let Some(selection) = options.pointer_mut("/publicKey/authenticatorSelection") {
+        selection["residentKey"] = "required".into();
+        selection["requireResidentKey"] = true.into();
+    }
+
+    match store_ceremony(&state.db, "registration", Some(user.user_id), &registration).await {
+        Ok(ceremony_id) => Json(CeremonyResp {
+            ceremony_id,
+            options,
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "register_start: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "webauthn.register_finish", skip_all)]
+async fn register_finish(
+    State(state): State<InternalState>,
+    Json(req): Json<RegisterFinishReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return (StatusCode::BAD_REQUEST, "Passkey name must be 1-64 characters").into_response();
+    }
+
+    let registration: PasskeyRegistration =
+        match take_ceremony(&state.db, &req.ceremony_id, "registration", Some(user.user_id)).await {
+            Ok(Some(r)) => r,
+            Ok(None) => {
+                return (StatusCode::BAD_REQUEST, "Passkey registration expired").into_response();
+            }
+            Err(e) => {
+                tracing::error!(user_id = user.user_id, error = %e, "register_finish: db error");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+    let passkey = match state
+        .backend
+        .webauthn
+        .finish_passkey_registration(&req.credential, &registration)
+    {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::warn!(user_id = user.user_id, error = %e, "passkey registration rejected");
+            return (StatusCode::BAD_REQUEST, "Passkey could not be verified").into_response();
+        }
+    };
+
+    let inserted = sqlx::query_as::<_, CredentialRow>(
+        r#"
+        INSERT INTO webauthn_credentials (user_id, credential_id, name, passkey)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, name, created_at, last_used_at
+        "#,
+    )
+    .bind(user.user_id)
+    .bind(passkey.cred_id().as_ref())
+    .bind(name)
+    .bind(SqlJson(&passkey))
+    .fetch_one(&state.db)
+    .await;
+
+    match inserted {
+        Ok(row) => {
+            tracing::info!(user_id = user.user_id, credential = row.id, "passkey registered");
+            Json(CredentialResp::from(row)).into_response()
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            (StatusCode::CONFLICT, "This passkey is already registered").into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "register_finish: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---- Authentication ----
+
+#[derive(Deserialize)]
+struct LoginFinishReq {
+    ceremony_id: String,
+    credential: PublicKeyCredential,
+}
+
+#[tracing::instrument(name = "webauthn.login_start", skip_all)]
+async fn login_start(State(state): State<InternalState>) -> impl IntoResponse {
+    let (rcr, authentication) = match state.backend.webauthn.start_discoverable_authentication() {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!(error = %e, "login_start: could not build challenge");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The Login view triggers this from a button, not from autofill, so drop the
+    // conditional-mediation hint webauthn-rs adds.
+    let mut options = serde_json::to_value(&rcr).unwrap_or_default();
+    if let Some(obj) = options.as_object_mut() {
+        obj.remove("mediation");
+    }
+
+    match store_ceremony(&state.db, "authentication", None, &authentication).await {
+        Ok(ceremony_id) => Json(CeremonyResp {
+            ceremony_id,
+            options,
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "login_start: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "token.exchange.passkey", skip_all)]
+async fn login_finish(
+    State(state): State<InternalState>,
+    headers: HeaderMap,
+    Json(req): Json<LoginFinishReq>,
+) -> impl IntoResponse {
+    let creds = Credentials::Passkey(PasskeyCreds {
+        ceremony_id: req.ceremony_id,
+        credential: req.credential,
+    });
+
+    let user = match state.backend.authenticate(creds).await {
+        Ok(Some(u)) => u,
+        Ok(None) => {
+            telemetry::login_attempt("passkey", "failure");
+            telemetry::token_operation("exchange", "failure");
+            return (StatusCode::UNAUTHORIZED, "Passkey not recognised").into_response();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "passkey login failed");
+            telemetry::login_attempt("passkey", "error");
+            telemetry::token_operation("exchange", "error");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // No TOTP step: a passkey ceremony already requires user verification on the device.
+    match create_bff_token(&state, user.id, "passkey", &headers).await {
+        Ok(bff_token) => {
+            tracing::info!(user_id = user.id, username = %user.username, "passkey login succeeded");
+            telemetry::login_attempt("passkey", "success");
+            telemetry::token_operation("exchange", "success");
+            Json(TokenResp {
+                token: bff_token.token,
+                username: user.username,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!(user_id = user.id, error = %e, "failed to insert bff_token after passkey login");
+            telemetry::token_operation("exchange", "error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredPasskeyRow {
+    id: i64,
+    user_id: i64,
+    passkey: SqlJson<Passkey>,
+}
+
+/// Verifies a discoverable-credential assertion for `Credentials::Passkey`. Assertions that
+/// don't verify come back as `Ok(None)`, like a wrong password.
+pub(super) async fn authenticate(
+    db: &PgPool,
+    webauthn: &Webauthn,
+    creds: PasskeyCreds,
+) -> Result<Option<User>, sqlx::Error> {
+    let Some(authentication) =
+        take_ceremony::<DiscoverableAuthentication>(db, &creds.ceremony_id, "authentication", None)
+            .await?
+    else {
+        tracing::warn!("passkey login: ceremony expired or unknown");
+        return Ok(None);
+    };
+
+    let (handle, cred_id) = match webauthn.identify_discoverable_authentication(&creds.credential) {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::warn!(error = %e, "passkey login: assertion has no user handle");
+            return Ok(None);
+        }
+    };
+
+    let stored: Option<StoredPasskeyRow> = sqlx::query_as(
+        r#"
+        SELECT c.id, c.user_id, c.passkey
+        FROM webauthn_credentials c
+        JOIN users u ON u.id = c.user_id
+        WHERE c.credential_id = $1 AND u.webauthn_user_id = $2
+        "#,
+    )
+    .bind(cred_id)
+    .bind(handle)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(stored) = stored else {
+        tracing::warn!("passkey login: unknown credential");
+        return Ok(None);
+    };
+    let mut passkey = stored.passkey.0;
+
+    let result = match webauthn.finish_discoverable_authentication(
+        &creds.credential,
+        authentication,
+        &[DiscoverableKey::from(&passkey)],
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!(user_id = stored.user_id, error = %e, "passkey login: assertion rejected");
+            return Ok(None);
+        }
+    };
+
+    // Persist the new signature counter / backup state so a cloned authenticator is caught.
+    passkey.update_credential(&result);
+    sqlx::query("UPDATE webauthn_credentials SET passkey = $2, last_used_at = NOW() WHERE id = $1")
+        .bind(stored.id)
+        .bind(SqlJson(&passkey))
+        .execute(db)
+        .await?;
+
+    sqlx::query_as("select * from users where id = $1 and disabled_at is null")
+        .bind(stored.user_id)
+        .fetch_optional(db)
+        .await
+}
+
+// ---- Self-service management ----
+
+#[derive(sqlx::FromRow)]
+struct CredentialRow {
+    id: i64,
+    name: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct CredentialResp {
+    id: i64,
+    name: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<CredentialRow> for CredentialResp {
+    fn from(row: CredentialRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RenameReq {
+    token: String,
+    id: i64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteReq {
+    token: String,
+    id: i64,
+}
+
+#[tracing::instrument(name = "webauthn.list_credentials", skip_all)]
+async fn list_credentials(
+    State(state): State<InternalState>,
+    Json(req): Json<TokenReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let rows: Result<Vec<CredentialRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, name, created_at, last_used_at FROM webauthn_credentials WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user.user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match rows {
+        Ok(rows) => Json(rows.into_iter().map(CredentialResp::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "list_credentials: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "webauthn.rename_credential", skip_all)]
+async fn rename_credential(
+    State(state): State<InternalState>,
+    Json(req): Json<RenameReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return (StatusCode::BAD_REQUEST, "Passkey name must be 1-64 characters").into_response();
+    }
+
+    let updated = sqlx::query("UPDATE webauthn_credentials SET name = $3 WHERE id = $1 AND user_id = $2")
+        .bind(req.id)
+        .bind(user.user_id)
+        .bind(name)
+        .execute(&state.db)
+        .await;
+
+    match updated {
+        Ok(r) if r.rows_affected() == 0 => StatusCode::NOT_FOUND.into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "rename_credential: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "webauthn.delete_credential", skip_all)]
+async fn delete_credential(
+    State(state): State<InternalState>,
+    Json(req): Json<DeleteReq>,
+) -> impl IntoResponse {
+    let Some(user) = resolve_token_user(&state.db, &req.token).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response();
+    };
+
+    match delete(&state.db, user.user_id, req.id).await {
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Ok(true) => {
+            tracing::info!(user_id = user.user_id, credential = req.id, "passkey deleted");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(BackendError::LastLoginMethod) => (
+            StatusCode::CONFLICT,
+            "This is your only way to log in. Add a password, passkey or another login first.",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(user_id = user.user_id, error = %e, "delete_credential: db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Deletes a passkey unless it is the user's last way to log in. Returns whether it existed.
+async fn delete(db: &PgPool, user_id: i64, id: i64) -> Result<bool, BackendError> {
+    let mut tx = db.begin().await?;
+    identities::lock_login_methods(&mut tx, user_id).await?;
+
+    let deleted = sqlx::query("DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Ok(false);
+    }
+    if identities::login_method_count(&mut tx, user_id).await? == 0 {
+        return Err(BackendError::LastLoginMethod);
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
@@ -13,6 +13,38 @@ use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitEx
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let print_config = args.iter().any(|a| a == "--print-config");
+    args.retain(|a| a != "--print-config");
+    let config_file = match args.iter().position(|a| a == "--config") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.remove(i + 1);
+            args.remove(i);
+            Some(path)
+        }
+        Some(_) => return Err("--config needs a path".into()),
+        None => None,
+    };
+
+    let dotenv_loaded = match dotenvy::dotenv() {
+        Ok(_) => true,
+        Err(_) if !cfg!(debug_assertions) => false,
+        Err(e) => panic!("could not load .env: {e}"),
+    };
+
+    // Every setting is checked before anything starts, and all problems are reported together.
+    let config = match auth::config::Config::load(config_file.as_deref()) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprintln!("{errors}");
+            std::process::exit(1);
+        }
+    };
+    if print_config {
+        println!("{config:#?}");
+        return Ok(());
+    }
+
     // Only init OTLP when the endpoint is explicitly configured. In dev (no env var) the
     // layers are None and tracing-subscriber skips them, so there are no connection errors.
     // Always register W3C trace-context propagator so OtelAxumLayer can extract
@@ -26,8 +58,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Option<_>,
         Option<SdkTracerProvider>,
         Option<SdkLoggerProvider>,
-    ) = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
-            Ok(endpoint) => {
+    ) = match config.otel_endpoint.clone() {
+            Some(endpoint) => {
                 let exporter = opentelemetry_otlp::SpanExporter::builder()
                     .with_tonic()
                     .with_endpoint(endpoint.clone())
@@ -62,32 +94,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     Some(log_provider),
                 )
             }
-            Err(_) => (None, None, None, None),
+            None => (None, None, None, None),
         };
 
     // JSON structured logging — one object per line, parsed by Loki / any log aggregator.
     // The OTel log bridge additionally ships log events via OTLP so Loki entries carry
     // trace_id/span_id, enabling Tempo → Loki correlation.
     tracing_subscriber::registry()
-        .with(EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(
-            |_| "info,sqlx=warn,tower_sessions=warn".into(),
-        )))
+        .with(EnvFilter::new(&config.log_filter))
         .with(tracing_subscriber::fmt::layer().json())
         .with(otel_layer)
         .with(otel_log_layer)
         .try_init()?;
 
-    match dotenvy::dotenv() {
-        Ok(_) => tracing::debug!("loaded .env file"),
-        Err(_) if !cfg!(debug_assertions) => {
-            tracing::debug!("no .env file found, using environment variables");
-        }
-        Err(e) => panic!("could not load .env: {e}"),
+    if dotenv_loaded {
+        tracing::debug!("loaded .env file");
+    } else {
+        tracing::debug!("no .env file found, using environment variables");
     }
 
-    tracing::info!("starting auth service");
-
-    let result = auth::Auth::new().await?.server().await;
+    let result = match args.first().map(String::as_str) {
+        Some("rotate-jwt-key") => auth::rotate_jwt_key(&config).await,
+        Some("register-oauth-client") => auth::register_oauth_client(&config, &args[1..]).await,
+        Some("rewrap-provider-tokens") => auth::rewrap_provider_tokens(&config).await,
+        Some(other) => Err(format!(
+            "unknown command {other:?} (expected rotate-jwt-key, register-oauth-client or rewrap-provider-tokens)"
+        )
+        .into()),
+        None => {
+            tracing::info!("starting auth service");
+            auth::Auth::new(config).await?.server().await
+        }
+    };
 
     // Flush buffered spans and log records before exit.
     if let Some(provider) = otel_provider {
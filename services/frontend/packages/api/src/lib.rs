@@ -3,7 +3,11 @@
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 
-pub use ui::data_dir::{AdminPermission, AdminRole, AdminUser, AdminUserRole, CommandResult, LoginStatus, PagedResult};
+pub use ui::data_dir::{
+    AccessTokenInfo, AdminInvite, AdminPermission, AdminRole, AdminUser, AdminUserRole, AuditEvent, CommandResult,
+    DeviceRequest, EmailStatus, GrantDuration, InviteRedemption, LinkedIdentity, LoginOutcome, LoginProvider, LoginStatus,
+    MfaEnrollment, MfaStatus, NewInvite, PagedResult, PasskeyInfo, RegistrationMode, SessionInfo,
+};
 
 /// Request extension that carries the serialised W3C `traceparent` captured
 /// before Dioxus's SSR dispatcher spawns server-function tasks.
@@ -12,6 +16,24 @@ pub use ui::data_dir::{AdminPermission, AdminRole, AdminUser, AdminUserRole, Com
 #[derive(Clone)]
 pub struct IncomingTraceparent(pub String);
 
+/// Request extension describing the browser, set by the `capture_client_info` middleware
+/// in the `web` crate. The IP is resolved from the edge proxy headers. Forwarded to auth
+/// as `x-client-ip` and `x-client-user-agent` for login throttling and the sessions list.
+#[cfg(feature = "server")]
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Request extension holding a BFF token presented as `Authorization: Bearer …` instead of a
+/// session cookie, as the desktop and mobile apps do after a device login. Set by the
+/// `capture_bearer_token` middleware in the `web` crate; server functions fall back to it when
+/// the request has no logged-in session.
+#[cfg(feature = "server")]
+#[derive(Clone)]
+pub struct BearerToken(pub String);
+
 // ---- Session helpers (server-only) ----
 
 #[cfg(feature = "server")]
@@ -25,13 +47,79 @@ mod session {
         parts.extensions.get::<Session>().cloned()
     }
 
+    /// The opaque BFF token of the logged-in user: the session's, or else one a native client
+    /// presented as a bearer token.
+    pub async fn session_token() -> Result<Option<String>, dioxus::prelude::ServerFnError> {
+        use dioxus::prelude::ServerFnError;
+
+        if let Some(sess) = get_session() {
+            let token: Option<String> = sess
+                .get("opaque_token")
+                .await
+                .map_err(|e| ServerFnError::new(e.to_string()))?;
+            if token.is_some() {
+                return Ok(token);
+            }
+        }
+        Ok(bearer_token())
+    }
+
+    /// The BFF token a native client presented as `Authorization: Bearer …`, if any.
+    pub fn bearer_token() -> Option<String> {
+        let ctx = FullstackContext::current()?;
+        let parts = ctx.parts_mut();
+        parts.extensions.get::<crate::BearerToken>().map(|t| t.0.clone())
+    }
+
+    /// The opaque BFF token of the logged-in user, or "Not authenticated".
+    pub async fn require_token() -> Result<String, dioxus::prelude::ServerFnError> {
+        use dioxus::prelude::ServerFnError;
+
+        session_token()
+            .await?
+            .ok_or_else(|| ServerFnError::new("Not authenticated"))
+    }
+
     pub fn auth_url() -> String {
         std::env::var("AUTH_SERVICE_URL")
             .unwrap_or_else(|_| "http://localhost:7070".to_string())
     }
 
-    pub fn service_secret() -> String {
-        std::env::var("BFF_SERVICE_SECRET").expect("BFF_SERVICE_SECRET must be set")
+    /// The key calls to auth are signed with: the first entry of `BFF_SERVICE_KEYS`
+    /// (`<key id>:<secret>,…`), or `BFF_SERVICE_SECRET` under the key id `default`.
+    pub fn service_key() -> &'static (String, ring::hmac::Key) {
+        use std::sync::OnceLock;
+        static KEY: OnceLock<(String, ring::hmac::Key)> = OnceLock::new();
+        KEY.get_or_init(|| {
+            let entry = match std::env::var("BFF_SERVICE_KEYS") {
+                Ok(keys) => keys
+                    .split(',')
+                    .map(str::trim)
+                    .find(|e| !e.is_empty())
+                    .expect("BFF_SERVICE_KEYS must not be empty")
+                    .to_string(),
+                Err(_) => format!(
+                    "default:{}",
+                    std::env::var("BFF_SERVICE_SECRET")
+                        .expect("BFF_SERVICE_KEYS or BFF_SERVICE_SECRET must be set")
+                ),
+            };
+            let (id, secret) = entry
+                .split_once(':')
+                .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+                .expect("BFF_SERVICE_KEYS entries must be `<key id>:<secret>`");
+            (id.to_string(), ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes()))
+        })
+    }
+
+    /// Header carrying the acting user's BFF token on `/internal/admin/*` calls, so auth can
+    /// check their permission itself and record them in the audit log.
+    pub const ACTING_USER_HEADER: &str = "x-acting-user-token";
+
+    pub fn client_info() -> Option<crate::ClientInfo> {
+        let ctx = FullstackContext::current()?;
+        let parts = ctx.parts_mut();
+        parts.extensions.get::<crate::ClientInfo>().cloned()
     }
 
     /// Returns the W3C `traceparent` captured before any Dioxus spawn, or `None`
@@ -51,10 +139,37 @@ fn http_client() -> &'static reqwest_middleware::ClientWithMiddleware {
     CLIENT.get_or_init(|| {
         ClientBuilder::new(reqwest::Client::new())
             .with(PropagateTraceContext)
+            .with(ForwardClientInfo)
+            .with(SignServiceRequest)
             .build()
     })
 }
 
+/// Whether auth answers its liveness probe, for the frontend's own `/readyz`. Uses a plain
+/// client: the probe needs no signature and shouldn't show up in traces every few seconds.
+#[cfg(feature = "server")]
+pub async fn auth_alive() -> Result<(), String> {
+    use std::sync::OnceLock;
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(2))
+            .build()
+            .expect("could not build the health check client")
+    });
+
+    let resp = client
+        .get(format!("{}/healthz", session::auth_url()))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("auth returned {}", resp.status()))
+    }
+}
+
 /// Injects the W3C `traceparent` header into every outbound BFF→auth request.
 ///
 /// Session path (both SSR and client-triggered): `capture_traceparent` Axum middleware
@@ -120,17 +235,115 @@ impl reqwest_middleware::Middleware for PropagateTraceContext {
     }
 }
 
+/// Adds `x-client-ip` and `x-client-user-agent` to outbound BFF→auth requests made while
+/// serving a browser request.
+///
+/// Server functions pick the `ClientInfo` up from the fullstack context. Plain Axum handlers
+/// (the OAuth callback) have no such context and attach it as a request extension instead.
+#[cfg(feature = "server")]
+struct ForwardClientInfo;
+
+#[cfg(feature = "server")]
+#[async_trait::async_trait]
+impl reqwest_middleware::Middleware for ForwardClientInfo {
+    async fn handle(
+        &self,
+        mut req: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: reqwest_middleware::Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let client = session::client_info().or_else(|| extensions.get::<ClientInfo>().cloned());
+        if let Some(client) = client {
+            let headers = [("x-client-ip", client.ip), ("x-client-user-agent", client.user_agent)];
+            for (name, value) in headers {
+                if let Some(val) = value.and_then(|v| reqwest::header::HeaderValue::from_str(&v).ok()) {
+                    req.headers_mut().insert(name, val);
+                }
+            }
+        }
+        next.run(req, extensions).await
+    }
+}
+
+/// Signs every call to auth with the current service key, in place of sending the shared
+/// secret. Auth checks the signature, a five-minute clock window and that the nonce is unused:
+///
+/// `x-bff-signature` = hex HMAC-SHA256 of `"{METHOD}\n{path?query}\n{timestamp}\n{nonce}\n{hex SHA-256 of body}"`,
+/// next to `x-bff-key-id`, `x-bff-timestamp` (unix seconds) and `x-bff-nonce`.
+///
+/// Registered last, so it signs the request exactly as it is sent.
+#[cfg(feature = "server")]
+struct SignServiceRequest;
+
+#[cfg(feature = "server")]
+#[async_trait::async_trait]
+impl reqwest_middleware::Middleware for SignServiceRequest {
+    async fn handle(
+        &self,
+        mut req: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: reqwest_middleware::Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        use reqwest::header::HeaderValue;
+        use ring::{digest, hmac};
+
+        fn hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("{b:02x}")).collect()
+        }
+
+        let (key_id, key) = session::service_key();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+            .to_string();
+        let nonce = ulid::Ulid::new().to_string();
+        let body = match req.body() {
+            Some(body) => body.as_bytes().unwrap_or_else(|| {
+                tracing::warn!("cannot sign a streaming body; auth will reject the request");
+                &[]
+            }),
+            None => &[],
+        };
+        let url = req.url();
+        let path_and_query = match url.query() {
+            Some(q) => format!("{}?{q}", url.path()),
+            None => url.path().to_string(),
+        };
+        let message = format!(
+            "{}\n{path_and_query}\n{timestamp}\n{nonce}\n{}",
+            req.method(),
+            hex(digest::digest(&digest::SHA256, body).as_ref())
+        );
+        let signature = hex(hmac::sign(key, message.as_bytes()).as_ref());
+
+        let headers = [
+            ("x-bff-key-id", key_id.clone()),
+            ("x-bff-timestamp", timestamp),
+            ("x-bff-nonce", nonce),
+            ("x-bff-signature", signature),
+        ];
+        for (name, value) in headers {
+            if let Ok(val) = HeaderValue::from_str(&value) {
+                req.headers_mut().insert(name, val);
+            }
+        }
+        next.run(req, extensions).await
+    }
+}
+
 // ---- Plain async helpers for Axum OAuth handlers in the web crate ----
 
 /// Ask the auth service to begin an OAuth flow. Returns `(auth_url, csrf_state)`.
 #[cfg(feature = "server")]
 #[tracing::instrument(name = "bff.start_oauth", skip_all, fields(provider = %provider))]
-pub async fn start_oauth(provider: &str) -> Result<(String, String), String> {
-    use session::{auth_url, service_secret};
+pub async fn start_oauth(provider: &str, invite_code: Option<&str>) -> Result<(String, String), String> {
+    use session::auth_url;
 
     #[derive(Serialize)]
     struct Req<'a> {
         provider: &'a str,
+        invite_code: Option<&'a str>,
     }
     #[derive(Deserialize)]
     struct Resp {
@@ -140,12 +353,14 @@ pub async fn start_oauth(provider: &str) -> Result<(String, String), String> {
 
     let resp = http_client()
         .post(format!("{}/internal/oauth/start", auth_url()))
-        .header("x-service-token", service_secret())
-        .json(&Req { provider })
+        .json(&Req { provider, invite_code })
         .send()
         .await
         .map_err(|e| e.to_string())?;
 
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err("unknown_provider".to_string());
+    }
     if !resp.status().is_success() {
         return Err(format!("oauth start failed: {}", resp.status()));
     }
@@ -154,19 +369,23 @@ pub async fn start_oauth(provider: &str) -> Result<(String, String), String> {
     Ok((data.auth_url, data.state))
 }
 
-/// Exchange an OAuth authorization code for a BFF opaque token + username.
+/// Exchange an OAuth authorization code for a BFF opaque token + username. `state` must be the
+/// one `start_oauth` returned; auth keeps the login's PKCE verifier under it.
 #[cfg(feature = "server")]
 #[tracing::instrument(name = "bff.exchange_oauth_code", skip_all, fields(provider = %provider))]
 pub async fn exchange_oauth_code(
     provider: &str,
     code: &str,
+    state: &str,
+    client: ClientInfo,
 ) -> Result<(String, String), String> {
-    use session::{auth_url, service_secret};
+    use session::auth_url;
 
     #[derive(Serialize)]
     struct Req<'a> {
         provider: &'a str,
         code: &'a str,
+        state: &'a str,
     }
     #[derive(Deserialize)]
     struct Resp {
@@ -176,8 +395,12 @@ pub async fn exchange_oauth_code(
 
     let resp = http_client()
         .post(format!("{}/internal/oauth/exchange", auth_url()))
-        .header("x-service-token", service_secret())
-        .json(&Req { provider, code })
+        .with_extension(client)
+        .json(&Req {
+            provider,
+            code,
+            state,
+        })
         .send()
         .await
         .map_err(|e| e.to_string())?;
@@ -193,6 +416,99 @@ pub async fn exchange_oauth_code(
     Ok((data.token, data.username))
 }
 
+/// Link the upstream account behind an OAuth authorization code to the logged-in user.
+/// Errors with `identity_in_use` when that account already belongs to someone else.
+#[cfg(feature = "server")]
+#[tracing::instrument(name = "bff.link_oauth_identity", skip_all, fields(provider = %provider))]
+pub async fn link_oauth_identity(
+    token: &str,
+    provider: &str,
+    code: &str,
+    state: &str,
+) -> Result<(), String> {
+    use session::auth_url;
+
+    #[derive(Serialize)]
+    struct Req<'a> {
+        token: &'a str,
+        provider: &'a str,
+        code: &'a str,
+        state: &'a str,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/identities/link", auth_url()))
+        .json(&Req {
+            token,
+            provider,
+            code,
+            state,
+        })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(resp.text().await.unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// Session key for an OpenID Connect authorization request that is waiting for the browser
+/// to log in. Set by the `/oauth2/continue` handler in the `web` crate and picked up again
+/// once a login completes.
+#[cfg(feature = "server")]
+pub const OIDC_PENDING_REQUEST_KEY: &str = "oidc_pending_request";
+
+/// Outcome of approving an OpenID Connect authorization request.
+#[cfg(feature = "server")]
+pub enum OidcApproval {
+    /// Send the browser back to the relying party.
+    Redirect(String),
+    /// The session's token is missing or no longer valid.
+    LoginRequired,
+    Failed(String),
+}
+
+/// Ask auth to issue an authorization code for a parked `/oauth2/authorize` request on
+/// behalf of the session's user.
+#[cfg(feature = "server")]
+#[tracing::instrument(name = "bff.approve_oidc_request", skip_all)]
+pub async fn approve_oidc_request(token: &str, request_id: &str) -> OidcApproval {
+    use session::auth_url;
+
+    #[derive(Serialize)]
+    struct Req<'a> {
+        token: &'a str,
+        request_id: &'a str,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        redirect_url: String,
+    }
+
+    let resp = match http_client()
+        .post(format!("{}/internal/oidc/approve", auth_url()))
+        .json(&Req { token, request_id })
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => return OidcApproval::Failed(e.to_string()),
+    };
+
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return OidcApproval::LoginRequired;
+    }
+    if !resp.status().is_success() {
+        return OidcApproval::Failed(resp.text().await.unwrap_or_default());
+    }
+    match resp.json::<Resp>().await {
+        Ok(r) => OidcApproval::Redirect(r.redirect_url),
+        Err(e) => OidcApproval::Failed(e.to_string()),
+    }
+}
+
 // ---- Shared response types ----
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -203,13 +519,15 @@ pub struct DockerRequestResponse {
 
 // ---- Server functions ----
 
-/// Log in with username + password.
+/// Log in with username + password. Accounts with TOTP enabled return
+/// `LoginOutcome::MfaRequired`; the pending challenge is kept in the BFF session
+/// and completed by [`login_mfa`].
 #[server(prefix = "/bff")]
 #[tracing::instrument(name = "bff.login_password", skip_all, fields(username = %username))]
 pub async fn login_password(
     username: String,
     password: String,
-) -> Result<LoginStatus, ServerFnError> {
+) -> Result<LoginOutcome, ServerFnError> {
     use session::*;
 
     #[derive(Serialize)]
@@ -222,27 +540,64 @@ pub async fn login_password(
         token: String,
         username: String,
     }
+    #[derive(Deserialize)]
+    struct MfaResp {
+        mfa_token: String,
+    }
 
     let resp = http_client()
         .post(format!("{}/internal/token/exchange", auth_url()))
-        .header("x-service-token", service_secret())
         .json(&Req { username: username.clone(), password })
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60);
+        tracing::warn!(username = %username, retry_after, "password login throttled");
+        metrics::counter!("bff_login_attempts_total", "method" => "password", "status" => "throttled").increment(1);
+        return Err(ServerFnError::new(format!(
+            "Too many failed attempts. Try again in {retry_after} seconds."
+        )));
+    }
+
+    if resp.status() == reqwest::StatusCode::FORBIDDEN {
+        tracing::warn!(username = %username, "password login refused: account disabled");
+        metrics::counter!("bff_login_attempts_total", "method" => "password", "status" => "disabled").increment(1);
+        return Err(ServerFnError::new("This account has been disabled"));
+    }
+
     if !resp.status().is_success() {
         tracing::warn!(username = %username, "password login failed: invalid credentials");
         metrics::counter!("bff_login_attempts_total", "method" => "password", "status" => "failure").increment(1);
         return Err(ServerFnError::new("Invalid credentials"));
     }
 
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+
+    if resp.status() == reqwest::StatusCode::ACCEPTED {
+        let data: MfaResp = resp
+            .json()
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        sess.insert("mfa_token", data.mfa_token)
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        tracing::info!(username = %username, "password accepted, mfa required");
+        metrics::counter!("bff_login_attempts_total", "method" => "password", "status" => "mfa_required").increment(1);
+        return Ok(LoginOutcome::MfaRequired);
+    }
+
     let data: Resp = resp
         .json()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
     sess.insert("opaque_token", data.token)
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -252,62 +607,20 @@ pub async fn login_password(
 
     tracing::info!(username = %data.username, "password login succeeded");
     metrics::counter!("bff_login_attempts_total", "method" => "password", "status" => "success").increment(1);
-    Ok(LoginStatus::LoggedIn(data.username))
-}
-
-/// Clear the current session.
-#[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.logout", skip_all)]
-pub async fn logout() -> Result<(), ServerFnError> {
-    use session::*;
-
-    if let Some(sess) = get_session() {
-        let username: Option<String> = sess.get("username").await.ok().flatten();
-        sess.flush()
-            .await
-            .map_err(|e| ServerFnError::new(e.to_string()))?;
-        tracing::info!(username = ?username, "user logged out");
-    }
-    Ok(())
-}
-
-/// Check the current login status from the BFF session.
-#[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.check_login_status", skip_all)]
-pub async fn check_login_status() -> Result<LoginStatus, ServerFnError> {
-    use session::*;
-
-    let sess = match get_session() {
-        Some(s) => s,
-        None => return Ok(LoginStatus::LoggedOut),
-    };
-
-    let username: Option<String> = sess
-        .get("username")
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
-
-    Ok(match username {
-        Some(u) => LoginStatus::LoggedIn(u),
-        None => LoginStatus::LoggedOut,
-    })
+    Ok(LoginOutcome::Complete(LoginStatus::LoggedIn(data.username)))
 }
 
-/// Register a new account and auto-login on success.
+/// Finish a password login that returned `LoginOutcome::MfaRequired` with a TOTP
+/// or recovery code.
 #[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.register_password", skip_all, fields(username = %username))]
-pub async fn register_password(
-    username: String,
-    email: String,
-    password: String,
-) -> Result<LoginStatus, ServerFnError> {
+#[tracing::instrument(name = "bff.login_mfa", skip_all)]
+pub async fn login_mfa(code: String) -> Result<LoginStatus, ServerFnError> {
     use session::*;
 
     #[derive(Serialize)]
     struct Req {
-        username: String,
-        email: String,
-        password: String,
+        mfa_token: String,
+        code: String,
     }
     #[derive(Deserialize)]
     struct Resp {
@@ -315,33 +628,33 @@ pub async fn register_password(
         username: String,
     }
 
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    let mfa_token: Option<String> = sess
+        .get("mfa_token")
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let mfa_token = mfa_token.ok_or_else(|| ServerFnError::new("No login in progress"))?;
+
     let resp = http_client()
-        .post(format!("{}/internal/register", auth_url()))
-        .header("x-service-token", service_secret())
-        .json(&Req { username: username.clone(), email, password })
+        .post(format!("{}/internal/token/exchange/mfa", auth_url()))
+        .json(&Req { mfa_token, code })
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    if resp.status() == reqwest::StatusCode::CONFLICT {
+    if !resp.status().is_success() {
         let body = resp.text().await.unwrap_or_default();
-        tracing::warn!(username = %username, reason = %body, "registration conflict");
-        metrics::counter!("bff_register_attempts_total", "status" => "conflict").increment(1);
+        tracing::warn!(reason = %body, "mfa login failed");
+        metrics::counter!("bff_login_attempts_total", "method" => "totp", "status" => "failure").increment(1);
         return Err(ServerFnError::new(body));
     }
 
-    if !resp.status().is_success() {
-        tracing::error!(username = %username, status = %resp.status(), "registration failed");
-        metrics::counter!("bff_register_attempts_total", "status" => "error").increment(1);
-        return Err(ServerFnError::new("Registration failed"));
-    }
-
     let data: Resp = resp
         .json()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    let _ = sess.remove::<String>("mfa_token").await;
     sess.insert("opaque_token", data.token)
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -349,123 +662,1239 @@ pub async fn register_password(
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    tracing::info!(username = %data.username, "registration succeeded");
-    metrics::counter!("bff_register_attempts_total", "status" => "success").increment(1);
+    tracing::info!(username = %data.username, "mfa login succeeded");
+    metrics::counter!("bff_login_attempts_total", "method" => "totp", "status" => "success").increment(1);
     Ok(LoginStatus::LoggedIn(data.username))
 }
 
-/// Returns the list of permission names held by the current session's user.
+/// Revoke the session's BFF token with the auth service and clear the current session. A
+/// desktop app's bearer token is revoked the same way.
 #[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.get_my_permissions", skip_all)]
-pub async fn get_my_permissions() -> Result<Vec<String>, ServerFnError> {
+#[tracing::instrument(name = "bff.logout", skip_all)]
+pub async fn logout() -> Result<(), ServerFnError> {
     use session::*;
 
-    let sess = match get_session() {
-        Some(s) => s,
-        None => return Ok(vec![]),
-    };
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
 
-    let token: Option<String> = sess
-        .get("opaque_token")
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    // The local session is cleared even if revocation fails; the token then lapses on its own
+    // when it expires.
+    async fn revoke(token: String) {
+        match http_client()
+            .post(format!("{}/internal/token/revoke", auth_url()))
+            .json(&Req { token })
+            .send()
+            .await
+        {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => tracing::error!(status = %r.status(), "logout: token revocation failed"),
+            Err(e) => tracing::error!(error = %e, "logout: token revocation request failed"),
+        }
+    }
 
-    let token = match token {
-        Some(t) => t,
-        None => return Ok(vec![]),
-    };
+    if let Some(sess) = get_session() {
+        let username: Option<String> = sess.get("username").await.ok().flatten();
+        let token: Option<String> = sess.get("opaque_token").await.ok().flatten();
+        if let Some(token) = token {
+            revoke(token).await;
+        }
+        sess.flush()
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        tracing::info!(username = ?username, "user logged out");
+    }
+    // A desktop or mobile app signing out ends its device login.
+    if let Some(token) = bearer_token() {
+        revoke(token).await;
+        tracing::info!("device login signed out");
+    }
+    Ok(())
+}
+
+/// Revoke every BFF token of the current user, signing them out on all devices, and clear
+/// the current session.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.logout_everywhere", skip_all)]
+pub async fn logout_everywhere() -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
 
     #[derive(Serialize)]
     struct Req {
         token: String,
     }
-    #[derive(Deserialize)]
-    struct Resp {
-        permissions: Vec<String>,
-    }
 
     let resp = http_client()
-        .post(format!("{}/internal/token/introspect", auth_url()))
-        .header("x-service-token", service_secret())
+        .post(format!("{}/internal/token/revoke_all", auth_url()))
         .json(&Req { token })
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
     if !resp.status().is_success() {
-        return Ok(vec![]);
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
     }
 
-    let data: Resp = resp
-        .json()
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
-
-    Ok(data.permissions)
+    if let Some(sess) = get_session() {
+        let username: Option<String> = sess.get("username").await.ok().flatten();
+        sess.flush()
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        tracing::info!(username = ?username, "user logged out everywhere");
+    }
+    Ok(())
 }
 
-/// Check whether a raw opaque token grants the `arcane` permission.
-/// Used by Axum handlers that have direct session access (e.g. the WebSocket proxy)
-/// but are outside the Dioxus server function context.
+/// Signed-in sessions of the current user, most recently used first.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.session_list", skip_all)]
+pub async fn session_list() -> Result<Vec<SessionInfo>, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/sessions/list", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch sessions"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Sign out one of the current user's other sessions. Use `logout` for the current one.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.session_revoke", skip_all)]
+pub async fn session_revoke(id: i64) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        id: i64,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/sessions/revoke", auth_url()))
+        .json(&Req { token, id })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    tracing::info!(session_id = id, "session revoked");
+    Ok(())
+}
+
+/// Sign out every session of the current user except this one.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.session_revoke_others", skip_all)]
+pub async fn session_revoke_others() -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/sessions/revoke_others", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    Ok(())
+}
+
+/// The OAuth providers auth is configured with, in display order.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.login_providers", skip_all)]
+pub async fn login_providers() -> Result<Vec<LoginProvider>, ServerFnError> {
+    use session::*;
+
+    let resp = http_client()
+        .get(format!("{}/internal/oauth/providers", auth_url()))
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch login providers"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Whether the sign-up page should offer registration, ask for an invite code, or neither.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.registration_mode", skip_all)]
+pub async fn registration_mode() -> Result<RegistrationMode, ServerFnError> {
+    use session::*;
+
+    #[derive(Deserialize)]
+    struct Resp {
+        mode: RegistrationMode,
+    }
+
+    let resp = http_client()
+        .get(format!("{}/internal/registration", auth_url()))
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch registration mode"));
+    }
+
+    let data: Resp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(data.mode)
+}
+
+/// The message for one of auth's codes refusing to create an account, for password sign-up and
+/// the Login view's `?error=` alerts alike.
+pub fn registration_refusal(code: &str) -> &'static str {
+    match code {
+        "registration_closed" => "Registration is closed.",
+        "invite_required" => "An invite code is required to sign up.",
+        "invalid_invite" => "That invite code is unknown, expired, revoked or already used up.",
+        "invite_email_mismatch" => "That invite code was issued for a different email address.",
+        _ => "Registration failed",
+    }
+}
+
+/// After a login, returns where to send the browser to finish an OpenID Connect sign-in
+/// that was interrupted by the login, if any.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.take_pending_authorization", skip_all)]
+pub async fn take_pending_authorization() -> Result<Option<String>, ServerFnError> {
+    use session::*;
+
+    let Some(sess) = get_session() else {
+        return Ok(None);
+    };
+    let request: Option<String> = sess
+        .remove(OIDC_PENDING_REQUEST_KEY)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(request.map(|r| format!("/oauth2/continue?request={r}")))
+}
+
+/// Check the current login status from the BFF session. Also refreshes the session's
+/// opaque token with auth, storing the replacement when it was rotated and clearing the
+/// session if the token has expired or been revoked.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.check_login_status", skip_all)]
+pub async fn check_login_status() -> Result<LoginStatus, ServerFnError> {
+    use session::*;
+
+    let sess = match get_session() {
+        Some(s) => s,
+        None => return bearer_login_status().await,
+    };
+
+    let username: Option<String> = sess
+        .get("username")
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let token: Option<String> = sess
+        .get("opaque_token")
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let (Some(username), Some(token)) = (username, token) else {
+        return bearer_login_status().await;
+    };
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        rotated_token: Option<String>,
+    }
+
+    // Keeps the token alive while the user is active and rotates it when auth says it is due.
+    let resp = match http_client()
+        .post(format!("{}/internal/token/refresh", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            // Auth being unreachable is not a reason to log anyone out.
+            tracing::warn!(error = %e, "token refresh request failed");
+            return Ok(LoginStatus::LoggedIn(username));
+        }
+    };
+
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        tracing::info!(username = %username, "bff token expired or revoked; clearing session");
+        sess.flush()
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        return Ok(LoginStatus::LoggedOut);
+    }
+
+    if !resp.status().is_success() {
+        tracing::warn!(status = %resp.status(), "token refresh returned non-success");
+        return Ok(LoginStatus::LoggedIn(username));
+    }
+
+    if let Ok(Resp {
+        rotated_token: Some(rotated),
+    }) = resp.json().await
+    {
+        sess.insert("opaque_token", rotated)
+            .await
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        metrics::counter!("bff_token_rotations_total").increment(1);
+    }
+
+    Ok(LoginStatus::LoggedIn(username))
+}
+
+/// Login status for a desktop or mobile app, from the BFF token it sent as a bearer token. There
+/// is no session to refresh; the token is only looked up.
 #[cfg(feature = "server")]
-#[tracing::instrument(name = "bff.has_arcane_permission", skip_all)]
-pub async fn has_arcane_permission(token: &str) -> bool {
-    use session::{auth_url, service_secret};
+async fn bearer_login_status() -> Result<LoginStatus, ServerFnError> {
+    use session::*;
+
+    let Some(token) = bearer_token() else {
+        return Ok(LoginStatus::LoggedOut);
+    };
 
     #[derive(Serialize)]
-    struct Req<'a> {
-        token: &'a str,
+    struct Req {
+        token: String,
     }
     #[derive(Deserialize)]
     struct Resp {
-        permissions: Vec<String>,
+        username: String,
     }
 
-    let result = http_client()
+    let resp = http_client()
         .post(format!("{}/internal/token/introspect", auth_url()))
-        .header("x-service-token", service_secret())
         .json(&Req { token })
         .send()
-        .await;
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    match result {
-        Ok(r) if r.status().is_success() => r
-            .json::<Resp>()
-            .await
-            .map(|data| data.permissions.iter().any(|p| p == "arcane"))
-            .unwrap_or(false),
-        Ok(r) => {
-            tracing::warn!(status = %r.status(), "arcane permission introspect returned non-success");
-            false
-        }
-        Err(e) => {
-            tracing::error!(error = %e, "arcane permission introspect request failed");
-            false
-        }
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(LoginStatus::LoggedOut);
+    }
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to check the device login"));
     }
+
+    let data: Resp = resp
+        .json()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(LoginStatus::LoggedIn(data.username))
 }
 
-/// Check whether the current user holds a specific permission.
+/// Register a new account and auto-login on success.
 #[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.check_permission", skip_all, fields(permission = %name))]
-pub async fn check_permission(name: String) -> Result<bool, ServerFnError> {
-    let result = get_my_permissions().await?.contains(&name);
-    tracing::debug!(permission = %name, granted = result, "permission check");
-    Ok(result)
+#[tracing::instrument(name = "bff.register_password", skip_all, fields(username = %username))]
+pub async fn register_password(
+    username: String,
+    email: String,
+    password: String,
+    invite_code: String,
+) -> Result<LoginStatus, ServerFnError> {
+    use session::*;
+
+    #[derive(Serialize)]
+    struct Req {
+        username: String,
+        email: String,
+        password: String,
+        invite_code: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        token: String,
+        username: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/register", auth_url()))
+        .json(&Req {
+            username: username.clone(),
+            email,
+            password,
+            invite_code: Some(invite_code.trim().to_string()).filter(|c| !c.is_empty()),
+        })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if resp.status() == reqwest::StatusCode::FORBIDDEN {
+        let body = resp.text().await.unwrap_or_default();
+        tracing::warn!(username = %username, reason = %body, "registration refused");
+        metrics::counter!("bff_register_attempts_total", "status" => "refused").increment(1);
+        return Err(ServerFnError::new(registration_refusal(&body)));
+    }
+
+    if resp.status() == reqwest::StatusCode::CONFLICT {
+        let body = resp.text().await.unwrap_or_default();
+        tracing::warn!(username = %username, reason = %body, "registration conflict");
+        metrics::counter!("bff_register_attempts_total", "status" => "conflict").increment(1);
+        return Err(ServerFnError::new(body));
+    }
+
+    if !resp.status().is_success() {
+        tracing::error!(username = %username, status = %resp.status(), "registration failed");
+        metrics::counter!("bff_register_attempts_total", "status" => "error").increment(1);
+        return Err(ServerFnError::new("Registration failed"));
+    }
+
+    let data: Resp = resp
+        .json()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    sess.insert("opaque_token", data.token)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    sess.insert("username", data.username.clone())
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    tracing::info!(username = %data.username, "registration succeeded");
+    metrics::counter!("bff_register_attempts_total", "status" => "success").increment(1);
+    Ok(LoginStatus::LoggedIn(data.username))
+}
+
+/// Ask auth to email a password reset link. Succeeds whether or not the email
+/// belongs to an account, so the form can't be used to probe for users.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.forgot_password", skip_all)]
+pub async fn forgot_password(email: String) -> Result<(), ServerFnError> {
+    use session::*;
+
+    #[derive(Serialize)]
+    struct Req {
+        email: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/password/forgot", auth_url()))
+        .json(&Req { email })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        tracing::error!(status = %resp.status(), "password reset request failed");
+        return Err(ServerFnError::new("Could not send reset email, please try again later"));
+    }
+    Ok(())
+}
+
+/// Set a new password with the token from a reset email. Every existing session
+/// of the account is revoked, so the user has to log in again afterwards.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.reset_password", skip_all)]
+pub async fn reset_password(token: String, password: String) -> Result<(), ServerFnError> {
+    use session::*;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        password: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/password/reset", auth_url()))
+        .json(&Req { token, password })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        tracing::warn!(reason = %body, "password reset failed");
+        return Err(ServerFnError::new(body));
+    }
+
+    tracing::info!("password reset succeeded");
+    Ok(())
+}
+
+/// Redeem the token from a verification email. Works without being logged in,
+/// since the link may be opened on another device.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.verify_email", skip_all)]
+pub async fn verify_email(token: String) -> Result<(), ServerFnError> {
+    use session::*;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/email/verify", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        tracing::warn!(reason = %body, "email verification failed");
+        return Err(ServerFnError::new(body));
+    }
+
+    tracing::info!("email verified");
+    Ok(())
+}
+
+/// Email address of the current user and whether it is verified.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.email_status", skip_all)]
+pub async fn email_status() -> Result<EmailStatus, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/email/status", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch email status"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Send a new verification email to the current user.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.resend_verification_email", skip_all)]
+pub async fn resend_verification_email() -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/email/resend", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    Ok(())
+}
+
+/// Returns the list of permission names held by the current session's user.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.get_my_permissions", skip_all)]
+pub async fn get_my_permissions() -> Result<Vec<String>, ServerFnError> {
+    use session::*;
+
+    let token = match session_token().await? {
+        Some(t) => t,
+        None => return Ok(vec![]),
+    };
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        permissions: Vec<String>,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/token/introspect", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Ok(vec![]);
+    }
+
+    let data: Resp = resp
+        .json()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    Ok(data.permissions)
+}
+
+/// Check whether a raw opaque token grants the `arcane` permission.
+/// Used by Axum handlers that have direct session access (e.g. the WebSocket proxy)
+/// but are outside the Dioxus server function context.
+#[cfg(feature = "server")]
+#[tracing::instrument(name = "bff.has_arcane_permission", skip_all)]
+pub async fn has_arcane_permission(token: &str) -> bool {
+    use session::auth_url;
+
+    #[derive(Serialize)]
+    struct Req<'a> {
+        token: &'a str,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        permissions: Vec<String>,
+    }
+
+    let result = http_client()
+        .post(format!("{}/internal/token/introspect", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await;
+
+    match result {
+        Ok(r) if r.status().is_success() => r
+            .json::<Resp>()
+            .await
+            .map(|data| data.permissions.iter().any(|p| p == "arcane"))
+            .unwrap_or(false),
+        Ok(r) => {
+            tracing::warn!(status = %r.status(), "arcane permission introspect returned non-success");
+            false
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "arcane permission introspect request failed");
+            false
+        }
+    }
+}
+
+/// Check whether the current user holds a specific permission.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.check_permission", skip_all, fields(permission = %name))]
+pub async fn check_permission(name: String) -> Result<bool, ServerFnError> {
+    let result = get_my_permissions().await?.contains(&name);
+    tracing::debug!(permission = %name, granted = result, "permission check");
+    Ok(result)
+}
+
+/// Get the number of active players on the Ark server.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.ark_player_count", skip_all)]
+pub async fn ark_player_count() -> Result<i32, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    ark_player_count_for(&token)
+        .await
+        .map_err(|_| ServerFnError::new("Ark request failed"))
+}
+
+/// Execute an Ark server command (start | stop | restart).
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.ark_command", skip_all, fields(cmd = %cmd))]
+pub async fn ark_command(cmd: String) -> Result<CommandResult, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+    let username: Option<String> = match get_session() {
+        Some(sess) => sess.get("username").await.ok().flatten(),
+        None => None,
+    };
+    tracing::info!(username = ?username, cmd = %cmd, "ark command requested");
+
+    ark_command_for(&token, &cmd).await.map_err(|e| match e {
+        ArkError::NoResult => ServerFnError::new("No command result"),
+        _ => ServerFnError::new("Ark command failed"),
+    })
+}
+
+/// Why an Ark request through auth failed.
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub enum ArkError {
+    /// The token is unknown or expired, or doesn't grant the `llama` permission.
+    Denied,
+    UnknownCommand,
+    /// The Ark host answered without a result.
+    NoResult,
+    Failed(String),
+}
+
+/// The number of players on the Ark server, for a BFF token or a personal access token. The
+/// server function and the public API both come through here.
+#[cfg(feature = "server")]
+#[tracing::instrument(name = "bff.ark_player_count_for", skip_all)]
+pub async fn ark_player_count_for(token: &str) -> Result<i32, ArkError> {
+    use session::auth_url;
+
+    #[derive(Serialize)]
+    struct Req<'a> {
+        token: &'a str,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/ark/num_players", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ArkError::Failed(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        metrics::counter!("bff_ark_commands_total", "cmd" => "num_players", "status" => "error").increment(1);
+        return Err(match resp.status() {
+            reqwest::StatusCode::FORBIDDEN => ArkError::Denied,
+            s => ArkError::Failed(format!("auth returned {s}")),
+        });
+    }
+
+    let body: DockerRequestResponse = resp
+        .json()
+        .await
+        .map_err(|e| ArkError::Failed(e.to_string()))?;
+
+    metrics::counter!("bff_ark_commands_total", "cmd" => "num_players", "status" => "success").increment(1);
+    Ok(match body.command_result {
+        Some(CommandResult::NumPlayers(n)) => n,
+        _ => -1,
+    })
+}
+
+/// Runs an Ark command (start | stop | restart) for a BFF token or a personal access token.
+#[cfg(feature = "server")]
+#[tracing::instrument(name = "bff.ark_command_for", skip_all, fields(cmd = %cmd))]
+pub async fn ark_command_for(token: &str, cmd: &str) -> Result<CommandResult, ArkError> {
+    use session::auth_url;
+
+    #[derive(Serialize)]
+    struct Req<'a> {
+        token: &'a str,
+        cmd: &'a str,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/ark/command", auth_url()))
+        .json(&Req { token, cmd })
+        .send()
+        .await
+        .map_err(|e| ArkError::Failed(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        metrics::counter!("bff_ark_commands_total", "cmd" => cmd.to_string(), "status" => "error").increment(1);
+        return Err(match resp.status() {
+            reqwest::StatusCode::FORBIDDEN => ArkError::Denied,
+            reqwest::StatusCode::BAD_REQUEST => ArkError::UnknownCommand,
+            s => ArkError::Failed(format!("auth returned {s}")),
+        });
+    }
+
+    let body: DockerRequestResponse = resp
+        .json()
+        .await
+        .map_err(|e| ArkError::Failed(e.to_string()))?;
+
+    metrics::counter!("bff_ark_commands_total", "cmd" => cmd.to_string(), "status" => "success").increment(1);
+    body.command_result.ok_or(ArkError::NoResult)
+}
+
+// ---- Personal access tokens ----
+
+/// The current user's personal access tokens, newest first.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.access_token_list", skip_all)]
+pub async fn access_token_list() -> Result<Vec<AccessTokenInfo>, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/access_tokens/list", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch access tokens"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Create a personal access token and return it. This is the only time it can be seen.
+/// `scopes` must be permissions the user holds; `expires_in_days` of `None` never expires.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.access_token_create", skip_all)]
+pub async fn access_token_create(
+    name: String,
+    scopes: Vec<String>,
+    expires_in_days: Option<u32>,
+) -> Result<String, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        name: String,
+        scopes: Vec<String>,
+        expires_in_days: Option<u32>,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        id: i64,
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/access_tokens/create", auth_url()))
+        .json(&Req { token, name, scopes, expires_in_days })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if resp.status() == reqwest::StatusCode::BAD_REQUEST || resp.status() == reqwest::StatusCode::CONFLICT {
+        return Err(ServerFnError::new(resp.text().await.unwrap_or_default()));
+    }
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to create access token"));
+    }
+
+    let created: Resp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    tracing::info!(token_id = created.id, "access token created");
+    Ok(created.token)
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.access_token_revoke", skip_all, fields(token_id = id))]
+pub async fn access_token_revoke(id: i64) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        id: i64,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/access_tokens/revoke", auth_url()))
+        .json(&Req { token, id })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    tracing::info!(token_id = id, "access token revoked");
+    Ok(())
+}
+
+// ---- Device login ----
+
+/// The pending native-app sign-in with this user code, for the `/device` approval page.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.device_lookup", skip_all)]
+pub async fn device_lookup(user_code: String) -> Result<DeviceRequest, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        user_code: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/device/lookup", auth_url()))
+        .json(&Req { token, user_code })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(ServerFnError::new(resp.text().await.unwrap_or_default()));
+    }
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to look up the code"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Approve (`approve = true`) or deny a native app's sign-in. On approval the app receives a
+/// session token for the current user on its next poll.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.device_decide", skip_all, fields(approve))]
+pub async fn device_decide(user_code: String, approve: bool) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        user_code: String,
+    }
+
+    let path = if approve { "approve" } else { "deny" };
+    let resp = http_client()
+        .post(format!("{}/internal/device/{path}", auth_url()))
+        .json(&Req { token, user_code })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(ServerFnError::new(resp.text().await.unwrap_or_default()));
+    }
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to record your answer"));
+    }
+    tracing::info!(approve, "device login decided");
+    Ok(())
+}
+
+// ---- Two-factor authentication ----
+
+/// Two-factor status of the current user.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.mfa_status", skip_all)]
+pub async fn mfa_status() -> Result<MfaStatus, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/mfa/status", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch two-factor status"));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Start TOTP enrollment. The secret is not active until [`mfa_confirm`] succeeds.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.mfa_enroll", skip_all)]
+pub async fn mfa_enroll() -> Result<MfaEnrollment, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/mfa/enroll", auth_url()))
+        .json(&Req { token })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Confirm TOTP enrollment with a code from the authenticator app. Returns the
+/// recovery codes, which are only ever shown this once.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.mfa_confirm", skip_all)]
+pub async fn mfa_confirm(code: String) -> Result<Vec<String>, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        code: String,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        recovery_codes: Vec<String>,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/mfa/enroll/confirm", auth_url()))
+        .json(&Req { token, code })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+
+    let data: Resp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    tracing::info!("two-factor authentication enabled");
+    Ok(data.recovery_codes)
+}
+
+/// Turn off TOTP for the current user. Requires a current TOTP or recovery code.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.mfa_disable", skip_all)]
+pub async fn mfa_disable(code: String) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        code: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/mfa/disable", auth_url()))
+        .json(&Req { token, code })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    tracing::info!("two-factor authentication disabled");
+    Ok(())
+}
+
+// ---- Passkeys (WebAuthn) ----
+//
+// Ceremony options and browser responses are passed through as JSON; auth keeps the
+// challenge state and the BFF session only remembers which ceremony is in flight.
+
+#[cfg(feature = "server")]
+async fn start_passkey_ceremony(
+    path: &str,
+    token: Option<String>,
+) -> Result<serde_json::Value, ServerFnError> {
+    use session::*;
+
+    #[derive(Deserialize)]
+    struct Resp {
+        ceremony_id: String,
+        options: serde_json::Value,
+    }
+
+    let resp = http_client()
+        .post(format!("{}{}", auth_url(), path))
+        .json(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+
+    let data: Resp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    sess.insert("webauthn_ceremony", data.ceremony_id)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(data.options)
+}
+
+#[cfg(feature = "server")]
+async fn take_passkey_ceremony() -> Result<String, ServerFnError> {
+    use session::*;
+
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    let ceremony: Option<String> = sess
+        .remove("webauthn_ceremony")
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    ceremony.ok_or_else(|| ServerFnError::new("No passkey request in progress"))
+}
+
+/// Begin a passkey login. Returns the options for `navigator.credentials.get()`.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.passkey_login_start", skip_all)]
+pub async fn passkey_login_start() -> Result<serde_json::Value, ServerFnError> {
+    start_passkey_ceremony("/internal/webauthn/login/start", None).await
+}
+
+/// Finish a passkey login with the browser's assertion.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.passkey_login_finish", skip_all)]
+pub async fn passkey_login_finish(
+    credential: serde_json::Value,
+) -> Result<LoginStatus, ServerFnError> {
+    use session::*;
+
+    #[derive(Serialize)]
+    struct Req {
+        ceremony_id: String,
+        credential: serde_json::Value,
+    }
+    #[derive(Deserialize)]
+    struct Resp {
+        token: String,
+        username: String,
+    }
+
+    let ceremony_id = take_passkey_ceremony().await?;
+
+    let resp = http_client()
+        .post(format!("{}/internal/webauthn/login/finish", auth_url()))
+        .json(&Req { ceremony_id, credential })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        tracing::warn!(reason = %body, "passkey login failed");
+        metrics::counter!("bff_login_attempts_total", "method" => "passkey", "status" => "failure").increment(1);
+        return Err(ServerFnError::new(body));
+    }
+
+    let data: Resp = resp
+        .json()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    let sess = get_session().ok_or_else(|| ServerFnError::new("no session context"))?;
+    sess.insert("opaque_token", data.token)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    sess.insert("username", data.username.clone())
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    tracing::info!(username = %data.username, "passkey login succeeded");
+    metrics::counter!("bff_login_attempts_total", "method" => "passkey", "status" => "success").increment(1);
+    Ok(LoginStatus::LoggedIn(data.username))
+}
+
+/// Begin registering a passkey for the current user. Returns the options for
+/// `navigator.credentials.create()`.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.passkey_register_start", skip_all)]
+pub async fn passkey_register_start() -> Result<serde_json::Value, ServerFnError> {
+    let token = session::require_token().await?;
+    start_passkey_ceremony("/internal/webauthn/register/start", Some(token)).await
+}
+
+/// Finish registering a passkey with the browser's attestation.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.passkey_register_finish", skip_all)]
+pub async fn passkey_register_finish(
+    name: String,
+    credential: serde_json::Value,
+) -> Result<PasskeyInfo, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        ceremony_id: String,
+        name: String,
+        credential: serde_json::Value,
+    }
+
+    let ceremony_id = take_passkey_ceremony().await?;
+
+    let resp = http_client()
+        .post(format!("{}/internal/webauthn/register/finish", auth_url()))
+        .json(&Req { token, ceremony_id, name, credential })
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+
+    tracing::info!("passkey registered");
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
 }
 
-/// Get the number of active players on the Ark server.
+/// Passkeys registered to the current user.
 #[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.ark_player_count", skip_all)]
-pub async fn ark_player_count() -> Result<i32, ServerFnError> {
+#[tracing::instrument(name = "bff.passkey_list", skip_all)]
+pub async fn passkey_list() -> Result<Vec<PasskeyInfo>, ServerFnError> {
     use session::*;
 
-    let sess = get_session().ok_or_else(|| ServerFnError::new("Not authenticated"))?;
-    let token: Option<String> = sess
-        .get("opaque_token")
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
-    let token = token.ok_or_else(|| ServerFnError::new("Not authenticated"))?;
+    let token = require_token().await?;
 
     #[derive(Serialize)]
     struct Req {
@@ -473,73 +1902,128 @@ pub async fn ark_player_count() -> Result<i32, ServerFnError> {
     }
 
     let resp = http_client()
-        .post(format!("{}/internal/ark/num_players", auth_url()))
-        .header("x-service-token", service_secret())
+        .post(format!("{}/internal/webauthn/credentials/list", auth_url()))
         .json(&Req { token })
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
     if !resp.status().is_success() {
-        metrics::counter!("bff_ark_commands_total", "cmd" => "num_players", "status" => "error").increment(1);
-        return Err(ServerFnError::new("Ark request failed"));
+        return Err(ServerFnError::new("Failed to fetch passkeys"));
     }
 
-    let body: DockerRequestResponse = resp
-        .json()
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.passkey_rename", skip_all)]
+pub async fn passkey_rename(id: i64, name: String) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        id: i64,
+        name: String,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/webauthn/credentials/rename", auth_url()))
+        .json(&Req { token, id, name })
+        .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    metrics::counter!("bff_ark_commands_total", "cmd" => "num_players", "status" => "success").increment(1);
-    Ok(match body.command_result {
-        Some(CommandResult::NumPlayers(n)) => n,
-        _ => -1,
-    })
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    Ok(())
 }
 
-/// Execute an Ark server command (start | stop | restart).
 #[server(prefix = "/bff")]
-#[tracing::instrument(name = "bff.ark_command", skip_all, fields(cmd = %cmd))]
-pub async fn ark_command(cmd: String) -> Result<CommandResult, ServerFnError> {
+#[tracing::instrument(name = "bff.passkey_delete", skip_all)]
+pub async fn passkey_delete(id: i64) -> Result<(), ServerFnError> {
     use session::*;
 
-    let sess = get_session().ok_or_else(|| ServerFnError::new("Not authenticated"))?;
-    let username: Option<String> = sess.get("username").await.ok().flatten();
-    let token: Option<String> = sess
-        .get("opaque_token")
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        id: i64,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/webauthn/credentials/delete", auth_url()))
+        .json(&Req { token, id })
+        .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
-    let token = token.ok_or_else(|| ServerFnError::new("Not authenticated"))?;
-    tracing::info!(username = ?username, cmd = %cmd, "ark command requested");
+
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    tracing::info!(passkey_id = id, "passkey deleted");
+    Ok(())
+}
+
+/// Upstream accounts the current user can log in with.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.identity_list", skip_all)]
+pub async fn identity_list() -> Result<Vec<LinkedIdentity>, ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
 
     #[derive(Serialize)]
     struct Req {
         token: String,
-        cmd: String,
     }
 
-    let cmd_label = cmd.clone();
     let resp = http_client()
-        .post(format!("{}/internal/ark/command", auth_url()))
-        .header("x-service-token", service_secret())
-        .json(&Req { token, cmd })
+        .post(format!("{}/internal/identities/list", auth_url()))
+        .json(&Req { token })
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
     if !resp.status().is_success() {
-        metrics::counter!("bff_ark_commands_total", "cmd" => cmd_label, "status" => "error").increment(1);
-        return Err(ServerFnError::new("Ark command failed"));
+        return Err(ServerFnError::new("Failed to fetch linked logins"));
     }
 
-    let body: DockerRequestResponse = resp
-        .json()
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.identity_unlink", skip_all)]
+pub async fn identity_unlink(id: i64) -> Result<(), ServerFnError> {
+    use session::*;
+
+    let token = require_token().await?;
+
+    #[derive(Serialize)]
+    struct Req {
+        token: String,
+        id: i64,
+    }
+
+    let resp = http_client()
+        .post(format!("{}/internal/identities/unlink", auth_url()))
+        .json(&Req { token, id })
+        .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    metrics::counter!("bff_ark_commands_total", "cmd" => cmd_label, "status" => "success").increment(1);
-    body.command_result
-        .ok_or_else(|| ServerFnError::new("No command result"))
+    if !resp.status().is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ServerFnError::new(body));
+    }
+    tracing::info!(identity_id = id, "identity unlinked");
+    Ok(())
 }
 
 // ---- Admin RBAC server functions ----
@@ -552,11 +2036,20 @@ pub async fn admin_list_users(page: u32, limit: u32, search: String) -> Result<P
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     #[derive(Deserialize)]
-    struct RoleRef { id: i32, name: String }
+    struct RoleRef { id: i32, name: String, expires_at: Option<String> }
     #[derive(Deserialize)]
-    struct UserResp { id: i64, username: String, email: Option<String>, roles: Vec<RoleRef> }
+    struct UserResp {
+        id: i64,
+        username: String,
+        email: Option<String>,
+        mfa_enabled: bool,
+        disabled_at: Option<String>,
+        disabled_reason: Option<String>,
+        roles: Vec<RoleRef>,
+    }
     #[derive(Deserialize)]
     struct Paged { items: Vec<UserResp>, total: i64 }
 
@@ -569,7 +2062,7 @@ pub async fn admin_list_users(page: u32, limit: u32, search: String) -> Result<P
 
     let resp = http_client()
         .get(url)
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -585,7 +2078,10 @@ pub async fn admin_list_users(page: u32, limit: u32, search: String) -> Result<P
             id: u.id,
             username: u.username,
             email: u.email,
-            roles: u.roles.into_iter().map(|r| AdminUserRole { id: r.id, name: r.name }).collect(),
+            mfa_enabled: u.mfa_enabled,
+            disabled_at: u.disabled_at,
+            disabled_reason: u.disabled_reason,
+            roles: u.roles.into_iter().map(|r| AdminUserRole { id: r.id, name: r.name, expires_at: r.expires_at }).collect(),
         }).collect(),
     })
 }
@@ -598,11 +2094,20 @@ pub async fn admin_list_roles(page: u32, limit: u32, search: String) -> Result<P
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     #[derive(Deserialize)]
-    struct PermRef { id: i32, name: String }
+    struct PermRef { id: i32, name: String, description: Option<String> }
+    #[derive(Deserialize)]
+    struct RoleRef { id: i32, name: String }
     #[derive(Deserialize)]
-    struct RoleResp { id: i32, name: String, permissions: Vec<PermRef> }
+    struct RoleResp {
+        id: i32,
+        name: String,
+        description: Option<String>,
+        permissions: Vec<PermRef>,
+        parents: Vec<RoleRef>,
+    }
     #[derive(Deserialize)]
     struct Paged { items: Vec<RoleResp>, total: i64 }
 
@@ -615,7 +2120,7 @@ pub async fn admin_list_roles(page: u32, limit: u32, search: String) -> Result<P
 
     let resp = http_client()
         .get(url)
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -630,7 +2135,13 @@ pub async fn admin_list_roles(page: u32, limit: u32, search: String) -> Result<P
         items: data.items.into_iter().map(|r| AdminRole {
             id: r.id,
             name: r.name,
-            permissions: r.permissions.into_iter().map(|p| AdminPermission { id: p.id, name: p.name }).collect(),
+            description: r.description,
+            permissions: r.permissions.into_iter().map(|p| AdminPermission {
+                id: p.id,
+                name: p.name,
+                description: p.description,
+            }).collect(),
+            parents: r.parents.into_iter().map(|p| AdminUserRole { id: p.id, name: p.name, expires_at: None }).collect(),
         }).collect(),
     })
 }
@@ -644,13 +2155,14 @@ pub async fn admin_list_all_roles() -> Result<Vec<AdminRole>, ServerFnError> {
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     #[derive(Deserialize)]
-    struct RoleResp { id: i32, name: String, permissions: Vec<serde_json::Value> }
+    struct RoleResp { id: i32, name: String, description: Option<String> }
 
     let resp = http_client()
         .get(format!("{}/internal/admin/roles/all", auth_url()))
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -660,7 +2172,10 @@ pub async fn admin_list_all_roles() -> Result<Vec<AdminRole>, ServerFnError> {
     }
 
     let data: Vec<RoleResp> = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
-    Ok(data.into_iter().map(|r| AdminRole { id: r.id, name: r.name, permissions: vec![] }).collect())
+    Ok(data
+        .into_iter()
+        .map(|r| AdminRole { id: r.id, name: r.name, description: r.description, permissions: vec![], parents: vec![] })
+        .collect())
 }
 
 #[server(prefix = "/bff")]
@@ -671,16 +2186,18 @@ pub async fn admin_list_permissions() -> Result<Vec<AdminPermission>, ServerFnEr
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     #[derive(Deserialize)]
     struct PermResp {
         id: i32,
         name: String,
+        description: Option<String>,
     }
 
     let resp = http_client()
         .get(format!("{}/internal/admin/permissions", auth_url()))
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -690,28 +2207,43 @@ pub async fn admin_list_permissions() -> Result<Vec<AdminPermission>, ServerFnEr
     }
 
     let data: Vec<PermResp> = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
-    Ok(data.into_iter().map(|p| AdminPermission { id: p.id, name: p.name }).collect())
+    Ok(data.into_iter().map(|p| AdminPermission { id: p.id, name: p.name, description: p.description }).collect())
 }
 
+/// Grant a role for `duration`. Granting a role the user already has replaces its expiry.
 #[server(prefix = "/bff")]
 #[tracing::instrument(name = "bff.admin_assign_user_role", skip_all, fields(user_id, role_id))]
-pub async fn admin_assign_user_role(user_id: i64, role_id: i32) -> Result<(), ServerFnError> {
-    use session::*;
-
-    if !check_permission("manage_permissions".to_string()).await? {
-        return Err(ServerFnError::new("Forbidden"));
+pub async fn admin_assign_user_role(
+    user_id: i64,
+    role_id: i32,
+    duration: GrantDuration,
+) -> Result<(), ServerFnError> {
+    #[derive(Serialize, Default)]
+    struct Req {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_in_secs: Option<u64>,
     }
 
-    let resp = http_client()
-        .post(format!("{}/internal/admin/users/{user_id}/roles/{role_id}", auth_url()))
-        .header("x-service-token", service_secret())
-        .send()
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
-
-    if !resp.status().is_success() {
-        return Err(ServerFnError::new("Failed to assign role"));
-    }
+    let body = match duration {
+        GrantDuration::Forever => Req::default(),
+        GrantDuration::Hours(hours) => Req { expires_in_secs: Some(u64::from(hours) * 3600), ..Req::default() },
+        GrantDuration::Until(date) => {
+            let is_date = date.len() == 10
+                && date.char_indices().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() });
+            if !is_date {
+                return Err(ServerFnError::new("Pick a date to grant the role until"));
+            }
+            Req { expires_at: Some(format!("{date}T23:59:59Z")), ..Req::default() }
+        }
+    };
+    send_admin_change(
+        reqwest::Method::POST,
+        &format!("/internal/admin/users/{user_id}/roles/{role_id}"),
+        Some(&body),
+    )
+    .await?;
     Ok(())
 }
 
@@ -723,14 +2255,18 @@ pub async fn admin_revoke_user_role(user_id: i64, role_id: i32) -> Result<(), Se
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     let resp = http_client()
         .delete(format!("{}/internal/admin/users/{user_id}/roles/{role_id}", auth_url()))
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
+    if resp.status() == reqwest::StatusCode::CONFLICT {
+        return Err(ServerFnError::new(resp.text().await.unwrap_or_default()));
+    }
     if !resp.status().is_success() {
         return Err(ServerFnError::new("Failed to revoke role"));
     }
@@ -745,10 +2281,11 @@ pub async fn admin_assign_role_permission(role_id: i32, permission_id: i32) -> R
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     let resp = http_client()
         .post(format!("{}/internal/admin/roles/{role_id}/permissions/{permission_id}", auth_url()))
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
@@ -767,16 +2304,343 @@ pub async fn admin_revoke_role_permission(role_id: i32, permission_id: i32) -> R
     if !check_permission("manage_permissions".to_string()).await? {
         return Err(ServerFnError::new("Forbidden"));
     }
+    let token = require_token().await?;
 
     let resp = http_client()
         .delete(format!("{}/internal/admin/roles/{role_id}/permissions/{permission_id}", auth_url()))
-        .header("x-service-token", service_secret())
+        .header(ACTING_USER_HEADER, &token)
         .send()
         .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
 
+    if resp.status() == reqwest::StatusCode::CONFLICT {
+        return Err(ServerFnError::new(resp.text().await.unwrap_or_default()));
+    }
     if !resp.status().is_success() {
         return Err(ServerFnError::new("Failed to revoke permission"));
     }
     Ok(())
 }
+
+/// Body of the create and update calls for roles and permissions.
+#[cfg(feature = "server")]
+#[derive(Serialize)]
+struct NamedReq {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+/// Sends an admin change and returns the response, turning auth's refusals (invalid or taken
+/// names, protected roles, the last `manage_permissions` holder, acting on your own account) into
+/// their message.
+#[cfg(feature = "server")]
+async fn send_admin_change<B: Serialize>(
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&B>,
+) -> Result<reqwest::Response, ServerFnError> {
+    use session::*;
+
+    if !check_permission("manage_permissions".to_string()).await? {
+        return Err(ServerFnError::new("Forbidden"));
+    }
+    let token = require_token().await?;
+
+    let mut req = http_client()
+        .request(method, format!("{}{path}", auth_url()))
+        .header(ACTING_USER_HEADER, &token);
+    if let Some(body) = body {
+        req = req.json(body);
+    }
+    let resp = req.send().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    match resp.status() {
+        s if s.is_success() => Ok(resp),
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::CONFLICT => {
+            Err(ServerFnError::new(resp.text().await.unwrap_or_default()))
+        }
+        reqwest::StatusCode::NOT_FOUND => Err(ServerFnError::new("It no longer exists")),
+        s => Err(ServerFnError::new(format!("Request failed ({s})"))),
+    }
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_create_role", skip_all)]
+pub async fn admin_create_role(name: String, description: String) -> Result<AdminRole, ServerFnError> {
+    #[derive(Deserialize)]
+    struct RoleResp { id: i32, name: String, description: Option<String> }
+
+    let body = NamedReq { name: Some(name), description: Some(description) };
+    let resp = send_admin_change(reqwest::Method::POST, "/internal/admin/roles", Some(&body)).await?;
+    let r: RoleResp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    tracing::info!(role_id = r.id, "role created");
+    Ok(AdminRole { id: r.id, name: r.name, description: r.description, permissions: vec![], parents: vec![] })
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_update_role", skip_all, fields(role_id))]
+pub async fn admin_update_role(role_id: i32, name: String, description: String) -> Result<(), ServerFnError> {
+    let body = NamedReq { name: Some(name), description: Some(description) };
+    send_admin_change(reqwest::Method::PATCH, &format!("/internal/admin/roles/{role_id}"), Some(&body)).await?;
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_delete_role", skip_all, fields(role_id))]
+pub async fn admin_delete_role(role_id: i32) -> Result<(), ServerFnError> {
+    send_admin_change(reqwest::Method::DELETE, &format!("/internal/admin/roles/{role_id}"), None::<&()>).await?;
+    tracing::info!(role_id, "role deleted");
+    Ok(())
+}
+
+/// Make `role_id` inherit every permission of `parent_id`. Refused if that would make a cycle.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_add_role_parent", skip_all, fields(role_id, parent_id))]
+pub async fn admin_add_role_parent(role_id: i32, parent_id: i32) -> Result<(), ServerFnError> {
+    send_admin_change(
+        reqwest::Method::POST,
+        &format!("/internal/admin/roles/{role_id}/parents/{parent_id}"),
+        None::<&()>,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_remove_role_parent", skip_all, fields(role_id, parent_id))]
+pub async fn admin_remove_role_parent(role_id: i32, parent_id: i32) -> Result<(), ServerFnError> {
+    send_admin_change(
+        reqwest::Method::DELETE,
+        &format!("/internal/admin/roles/{role_id}/parents/{parent_id}"),
+        None::<&()>,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_create_permission", skip_all)]
+pub async fn admin_create_permission(name: String, description: String) -> Result<AdminPermission, ServerFnError> {
+    #[derive(Deserialize)]
+    struct PermResp { id: i32, name: String, description: Option<String> }
+
+    let body = NamedReq { name: Some(name), description: Some(description) };
+    let resp = send_admin_change(reqwest::Method::POST, "/internal/admin/permissions", Some(&body)).await?;
+    let p: PermResp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    tracing::info!(permission_id = p.id, "permission created");
+    Ok(AdminPermission { id: p.id, name: p.name, description: p.description })
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_update_permission", skip_all, fields(permission_id))]
+pub async fn admin_update_permission(permission_id: i32, name: String, description: String) -> Result<(), ServerFnError> {
+    let body = NamedReq { name: Some(name), description: Some(description) };
+    send_admin_change(
+        reqwest::Method::PATCH,
+        &format!("/internal/admin/permissions/{permission_id}"),
+        Some(&body),
+    )
+    .await?;
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_delete_permission", skip_all, fields(permission_id))]
+pub async fn admin_delete_permission(permission_id: i32) -> Result<(), ServerFnError> {
+    send_admin_change(reqwest::Method::DELETE, &format!("/internal/admin/permissions/{permission_id}"), None::<&()>).await?;
+    tracing::info!(permission_id, "permission deleted");
+    Ok(())
+}
+
+/// Disable an account, logging it out everywhere. `reason` is shown to admins only.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_disable_user", skip_all, fields(user_id))]
+pub async fn admin_disable_user(user_id: i64, reason: String) -> Result<(), ServerFnError> {
+    #[derive(Serialize)]
+    struct Req { reason: String }
+
+    send_admin_change(
+        reqwest::Method::POST,
+        &format!("/internal/admin/users/{user_id}/disable"),
+        Some(&Req { reason }),
+    )
+    .await?;
+    tracing::info!(user_id, "user disabled");
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_enable_user", skip_all, fields(user_id))]
+pub async fn admin_enable_user(user_id: i64) -> Result<(), ServerFnError> {
+    send_admin_change(reqwest::Method::POST, &format!("/internal/admin/users/{user_id}/enable"), None::<&()>).await?;
+    tracing::info!(user_id, "user enabled");
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_delete_user", skip_all, fields(user_id))]
+pub async fn admin_delete_user(user_id: i64) -> Result<(), ServerFnError> {
+    send_admin_change(reqwest::Method::DELETE, &format!("/internal/admin/users/{user_id}"), None::<&()>).await?;
+    tracing::info!(user_id, "user deleted");
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_list_invites", skip_all)]
+pub async fn admin_list_invites(page: u32, limit: u32) -> Result<PagedResult<AdminInvite>, ServerFnError> {
+    use session::*;
+
+    if !check_permission("manage_permissions".to_string()).await? {
+        return Err(ServerFnError::new("Forbidden"));
+    }
+    let token = require_token().await?;
+
+    let mut url = reqwest::Url::parse(&format!("{}/internal/admin/invites", auth_url()))
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("limit", &limit.to_string());
+
+    let resp = http_client()
+        .get(url)
+        .header(ACTING_USER_HEADER, &token)
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch invite codes"));
+    }
+    resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Create an invite code and return it. This is the only time the code can be seen.
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_create_invite", skip_all)]
+pub async fn admin_create_invite(invite: NewInvite) -> Result<String, ServerFnError> {
+    #[derive(Serialize)]
+    struct Req {
+        max_uses: Option<i32>,
+        expires_in_secs: Option<i64>,
+        email: Option<String>,
+        role_ids: Vec<i32>,
+        note: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Resp { id: i64, code: String }
+
+    let blank_to_none = |s: String| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    let body = Req {
+        max_uses: invite.max_uses,
+        expires_in_secs: invite.expires_in_secs,
+        email: blank_to_none(invite.email),
+        role_ids: invite.role_ids,
+        note: blank_to_none(invite.note),
+    };
+    let resp = send_admin_change(reqwest::Method::POST, "/internal/admin/invites", Some(&body)).await?;
+    let created: Resp = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    tracing::info!(invite_id = created.id, "invite code created");
+    Ok(created.code)
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_revoke_invite", skip_all, fields(invite_id))]
+pub async fn admin_revoke_invite(invite_id: i64) -> Result<(), ServerFnError> {
+    send_admin_change(reqwest::Method::DELETE, &format!("/internal/admin/invites/{invite_id}"), None::<&()>).await?;
+    tracing::info!(invite_id, "invite code revoked");
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_reset_user_mfa", skip_all, fields(user_id))]
+pub async fn admin_reset_user_mfa(user_id: i64) -> Result<(), ServerFnError> {
+    use session::*;
+
+    if !check_permission("manage_permissions".to_string()).await? {
+        return Err(ServerFnError::new("Forbidden"));
+    }
+    let token = require_token().await?;
+
+    let resp = http_client()
+        .delete(format!("{}/internal/admin/users/{user_id}/mfa", auth_url()))
+        .header(ACTING_USER_HEADER, &token)
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to reset two-factor authentication"));
+    }
+    Ok(())
+}
+
+#[server(prefix = "/bff")]
+#[tracing::instrument(name = "bff.admin_list_audit_events", skip_all)]
+pub async fn admin_list_audit_events(
+    page: u32,
+    limit: u32,
+    action: String,
+    actor: String,
+    target: String,
+) -> Result<PagedResult<AuditEvent>, ServerFnError> {
+    use session::*;
+
+    if !check_permission("manage_permissions".to_string()).await? {
+        return Err(ServerFnError::new("Forbidden"));
+    }
+    let token = require_token().await?;
+
+    #[derive(Deserialize)]
+    struct EventResp {
+        id: i64,
+        occurred_at: String,
+        actor_user_id: Option<i64>,
+        actor_username: Option<String>,
+        action: String,
+        target: Option<String>,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+        trace_id: Option<String>,
+        client_ip: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Paged { items: Vec<EventResp>, total: i64 }
+
+    let mut url = reqwest::Url::parse(&format!("{}/internal/admin/audit", auth_url()))
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("limit", &limit.to_string())
+        .append_pair("action", &action)
+        .append_pair("actor", &actor)
+        .append_pair("target", &target);
+
+    let resp = http_client()
+        .get(url)
+        .header(ACTING_USER_HEADER, &token)
+        .send()
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(ServerFnError::new("Failed to fetch audit events"));
+    }
+
+    let pretty = |v: Option<serde_json::Value>| v.map(|v| serde_json::to_string_pretty(&v).unwrap_or_default());
+    let data: Paged = resp.json().await.map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(PagedResult {
+        total: data.total,
+        items: data.items.into_iter().map(|e| AuditEvent {
+            id: e.id,
+            occurred_at: e.occurred_at,
+            actor_user_id: e.actor_user_id,
+            actor_username: e.actor_username,
+            action: e.action,
+            target: e.target,
+            before: pretty(e.before),
+            after: pretty(e.after),
+            trace_id: e.trace_id,
+            client_ip: e.client_ip,
+        }).collect(),
+    })
+}
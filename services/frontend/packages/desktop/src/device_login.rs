@@ -0,0 +1,132 @@
+//! Signs the desktop app in with the OAuth device authorization grant (RFC 8628).
+//!
+//! The app can't hold the website's session cookie, so it asks auth for a device code, shows the
+//! user code, and polls `/oauth2/token` while the user approves the code on the website's
+//! `/device` page. The BFF token it gets back is kept in the user's config directory and sent as
+//! `Authorization: Bearer …` with every server function call, which the frontend accepts in place
+//! of a session.
+
+use std::{fs, io, io::Write, path::PathBuf, time::Duration};
+
+use dioxus::fullstack::{clear_request_headers, set_request_headers, HeaderMap, HeaderValue};
+use serde::Deserialize;
+
+/// The public site. Server functions and the `/oauth2/*` endpoints are both reached through it.
+pub const SERVER_URL: &str = match option_env!("MILESSTORM_URL") {
+    Some(url) => url,
+    None => "https://milesstorm.com",
+};
+
+/// Registered with `auth register-oauth-client milesstorm-desktop … --public --device`.
+const CLIENT_ID: &str = "milesstorm-desktop";
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DeviceCode {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    interval: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResp {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Starts a sign-in. The user code is shown to the user; pass the result to [`poll_token`].
+pub async fn request_code() -> Result<DeviceCode, String> {
+    let resp = reqwest::Client::new()
+        .post(format!("{SERVER_URL}/oauth2/device_authorization"))
+        .form(&[("client_id", CLIENT_ID)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Could not start signing in ({})", resp.status()));
+    }
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+/// Waits until the user approves the code and returns the BFF token. Fails if they deny it or
+/// the code expires first.
+pub async fn poll_token(code: &DeviceCode) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let mut interval = code.interval.max(1);
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        let resp = client
+            .post(format!("{SERVER_URL}/oauth2/token"))
+            .form(&[
+                ("grant_type", GRANT_TYPE),
+                ("device_code", code.device_code.as_str()),
+                ("client_id", CLIENT_ID),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if resp.status().is_success() {
+            let token: TokenResp = resp.json().await.map_err(|e| e.to_string())?;
+            return Ok(token.access_token);
+        }
+        let error: TokenError = resp.json().await.map_err(|e| e.to_string())?;
+        match error.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += 5,
+            _ => return Err(error.error_description.unwrap_or(error.error)),
+        }
+    }
+}
+
+fn token_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("milesstorm").join("token"))
+}
+
+/// Sends `token` with every server function call from now on.
+fn present(token: &str) {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&format!("Bearer {token}")) {
+        headers.insert("authorization", value);
+    }
+    set_request_headers(headers);
+}
+
+/// Picks up the token saved by an earlier sign-in, if any.
+pub fn restore() {
+    let token = token_path().and_then(|path| fs::read_to_string(path).ok());
+    if let Some(token) = token.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        present(token);
+    }
+}
+
+/// Saves the token for the next start and presents it from now on.
+pub fn store(token: &str) -> io::Result<()> {
+    present(token);
+    let path = token_path().ok_or_else(|| io::Error::other("no config directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(token.as_bytes())
+}
+
+/// Stops presenting the token and deletes the saved copy. Revoke it first with `api::logout`.
+pub fn forget() {
+    clear_request_headers();
+    if let Some(path) = token_path() {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -1,8 +1,9 @@
 use dioxus::prelude::*;
 
 use ui::{data_dir::LoginStatus, Navbar};
-use views::{Blog, Home};
+use views::{Blog, Home, Login};
 
+mod device_login;
 mod views;
 
 pub static LOGIN_STATUS: GlobalSignal<LoginStatus> = Signal::global(|| LoginStatus::LoggedOut);
@@ -15,11 +16,15 @@ enum Route {
     Home {},
     #[route("/blog/:id")]
     Blog { id: i32 },
+    #[route("/login")]
+    Login {},
 }
 
 const MAIN_CSS: Asset = asset!("/assets/main.css");
 
 fn main() {
+    dioxus::fullstack::set_server_url(device_login::SERVER_URL);
+    device_login::restore();
     dioxus::launch(App);
 }
 
@@ -27,6 +32,12 @@ fn main() {
 fn App() -> Element {
     // Build cool things ✌️
 
+    use_future(|| async {
+        if let Ok(status) = api::check_login_status().await {
+            *LOGIN_STATUS.write() = status;
+        }
+    });
+
     rsx! {
         // Global app resources
         document::Link { rel: "stylesheet", href: MAIN_CSS }
@@ -39,9 +50,21 @@ fn App() -> Element {
 /// which allows us to use the desktop-specific `Route` enum.
 #[component]
 fn DesktopNavbar() -> Element {
+    let logout_handler = move |_: ()| {
+        spawn(async move {
+            let _ = api::logout().await;
+            device_login::forget();
+            *LOGIN_STATUS.write() = LoginStatus::LoggedOut;
+        });
+    };
+
     rsx! {
         Navbar {
-            user: LOGIN_STATUS()
+            user: LOGIN_STATUS(),
+            on_logout: logout_handler,
+            has_ark: false,
+            has_arcane: false,
+            has_admin: false,
         }
 
         Outlet::<Route> {}
@@ -0,0 +1,72 @@
+use dioxus::prelude::*;
+
+use crate::device_login::{self, DeviceCode};
+use crate::{Route, LOGIN_STATUS};
+
+#[derive(Clone, PartialEq)]
+enum Step {
+    Start,
+    Waiting(DeviceCode),
+    Failed(String),
+}
+
+/// Signs in through the website: shows a code for the user to approve on its `/device` page.
+#[component]
+pub fn Login() -> Element {
+    let mut step = use_signal(|| Step::Start);
+    let nav = use_navigator();
+
+    let sign_in = move |_| {
+        spawn(async move {
+            let code = match device_login::request_code().await {
+                Ok(code) => code,
+                Err(e) => {
+                    step.set(Step::Failed(e));
+                    return;
+                }
+            };
+            step.set(Step::Waiting(code.clone()));
+
+            let token = match device_login::poll_token(&code).await {
+                Ok(token) => token,
+                Err(e) => {
+                    step.set(Step::Failed(e));
+                    return;
+                }
+            };
+            if let Err(e) = device_login::store(&token) {
+                // Signed in for this run anyway; the next start just asks again.
+                eprintln!("could not save the login token: {e}");
+            }
+            if let Ok(status) = api::check_login_status().await {
+                *LOGIN_STATUS.write() = status;
+            }
+            nav.replace(Route::Home {});
+        });
+    };
+
+    rsx! {
+        div { id: "login",
+            h1 { "Sign in" }
+            match step() {
+                Step::Start => rsx! {
+                    p { "You sign in with your milesstorm.com account in a browser." }
+                    button { onclick: sign_in, "Sign in" }
+                },
+                Step::Waiting(code) => rsx! {
+                    p {
+                        "Open "
+                        a { href: "{code.verification_uri_complete}", target: "_blank", "{code.verification_uri}" }
+                        " in a browser where you are signed in and enter this code:"
+                    }
+                    p { class: "user-code", "{code.user_code}" }
+                    p { "Waiting for you to approve it…" }
+                },
+                Step::Failed(error) => rsx! {
+                    p { class: "error", "{error}" }
+                    button { onclick: sign_in, "Try again" }
+                },
+            }
+        }
+    }
+}
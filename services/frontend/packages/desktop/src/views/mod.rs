@@ -3,3 +3,6 @@ pub use home::Home;
 
 mod blog;
 pub use blog::Blog;
+
+mod login;
+pub use login::Login;
@@ -16,6 +16,14 @@ impl LoginStatus {
     }
 }
 
+/// Result of the password step of a login. Accounts with TOTP enabled stop at
+/// `MfaRequired` until a code is submitted.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum LoginOutcome {
+    Complete(LoginStatus),
+    MfaRequired,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum Theme {
     Dark,
@@ -106,6 +114,7 @@ pub struct ClientUser {
 pub struct AdminPermission {
     pub id: i32,
     pub name: String,
+    pub description: Option<String>,
 }
 
 /// A role as returned in the roles listing (includes its permissions).
@@ -113,14 +122,29 @@ pub struct AdminPermission {
 pub struct AdminRole {
     pub id: i32,
     pub name: String,
+    pub description: Option<String>,
     pub permissions: Vec<AdminPermission>,
+    /// The roles this one inherits permissions from.
+    pub parents: Vec<AdminUserRole>,
 }
 
-/// A role reference as returned in user listings (no permissions attached).
+/// A role reference as returned in user listings and a role's parents (no permissions attached).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AdminUserRole {
     pub id: i32,
     pub name: String,
+    /// For a user's role, when the grant expires (RFC 3339); unset if it doesn't.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+/// How long an admin grants a role for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum GrantDuration {
+    Forever,
+    Hours(u32),
+    /// Through the end of this `YYYY-MM-DD` date, UTC.
+    Until(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -128,11 +152,176 @@ pub struct AdminUser {
     pub id: i64,
     pub username: String,
     pub email: Option<String>,
+    pub mfa_enabled: bool,
+    /// RFC 3339; set while the account is disabled.
+    pub disabled_at: Option<String>,
+    pub disabled_reason: Option<String>,
     pub roles: Vec<AdminUserRole>,
 }
 
+/// An entry of the auth service's audit log. `before` and `after` are pretty-printed JSON and
+/// `occurred_at` is RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub occurred_at: String,
+    pub actor_user_id: Option<i64>,
+    pub actor_username: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub trace_id: Option<String>,
+    pub client_ip: Option<String>,
+}
+
+/// Who may create an account, as configured on the auth service.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    Open,
+    InviteOnly,
+    Closed,
+}
+
+/// An invite code as listed on the admin panel. The code itself is only shown once, when it is
+/// created; `hint` is its last four characters. Times are RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdminInvite {
+    pub id: i64,
+    pub hint: String,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    /// `None` for unlimited uses.
+    pub max_uses: Option<i32>,
+    pub use_count: i32,
+    /// Only an account with this email may redeem the code.
+    pub email: Option<String>,
+    pub revoked_at: Option<String>,
+    /// `active`, `revoked`, `expired` or `used_up`.
+    pub status: String,
+    /// Granted on redemption.
+    pub roles: Vec<String>,
+    pub redemptions: Vec<InviteRedemption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InviteRedemption {
+    /// `None` once the account is deleted.
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub redeemed_at: String,
+}
+
+/// What an admin asks for when creating an invite code.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewInvite {
+    pub max_uses: Option<i32>,
+    pub expires_in_secs: Option<i64>,
+    pub email: String,
+    pub role_ids: Vec<i32>,
+    pub note: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PagedResult<T> {
     pub items: Vec<T>,
     pub total: i64,
 }
+
+/// Two-factor state of the current user, as shown on the profile page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MfaStatus {
+    /// False for OAuth-only accounts, which can't enroll.
+    pub available: bool,
+    pub enabled: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+/// A pending TOTP enrollment. `qr_png_base64` encodes `otpauth_uri`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MfaEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_png_base64: String,
+}
+
+/// Email address of the current user and whether it has been confirmed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmailStatus {
+    pub email: Option<String>,
+    pub verified: bool,
+}
+
+/// A passkey registered to the current user. Timestamps are RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PasskeyInfo {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// An upstream account (GitHub, Google, ...) the current user can log in with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkedIdentity {
+    pub id: i64,
+    /// Provider name, as in `/oauth/start/{provider}`.
+    pub provider: String,
+    pub display_name: String,
+    pub email: Option<String>,
+    /// RFC 3339.
+    pub linked_at: String,
+}
+
+/// An upstream identity provider offered on the Login view.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoginProvider {
+    /// Used in `/oauth/start/{name}`.
+    pub name: String,
+    pub display_name: String,
+}
+
+/// One of the current user's personal access tokens. `prefix` is the token's first characters;
+/// the token itself is only shown when it is created. Timestamps are RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccessTokenInfo {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    /// Permission names the token grants while the user holds them.
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// A native app waiting for the current user to approve its sign-in on the `/device` page.
+/// `client_ip` and `user_agent` are those of the device. Timestamps are RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeviceRequest {
+    pub user_code: String,
+    /// Name of the registered app.
+    pub client_name: String,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+/// One of the current user's signed-in sessions. Timestamps are RFC 3339.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionInfo {
+    pub id: i64,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub user_agent: Option<String>,
+    pub client_ip: Option<String>,
+    /// How the session was started: `password`, `passkey`, `device` or the name of an OAuth
+    /// provider.
+    pub login_method: String,
+    /// True for the session viewing the list.
+    pub current: bool,
+}
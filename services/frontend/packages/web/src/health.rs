@@ -0,0 +1,127 @@
+//! Liveness and readiness probes for Kubernetes.
+//!
+//! `/healthz` answers as long as the server is up. `/readyz` also needs the Redis session store
+//! and the auth service to answer, and fails from the moment a SIGTERM arrives: the server then
+//! keeps serving for `SHUTDOWN_DRAIN_SECS` so the mesh drains traffic before the process exits.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use serde_json::json;
+use tower_sessions_redis_store::fred::prelude::*;
+
+/// How long a dependency may take to answer before it counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+pub fn router(redis: Pool) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(redis)
+}
+
+/// Installs the SIGTERM handler, once however often the router is rebuilt. `dioxus::serve`
+/// doesn't shut down gracefully on its own, so after draining the process simply exits.
+pub fn drain_on_sigterm() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let drain = std::env::var("SHUTDOWN_DRAIN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+        tokio::spawn(async move {
+            let mut terminate =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                    .expect("Failed to install signal handler");
+            terminate.recv().await;
+            DRAINING.store(true, Ordering::SeqCst);
+            tracing::info!(drain_secs = drain, "received SIGTERM, draining before exit");
+            tokio::time::sleep(Duration::from_secs(drain)).await;
+            std::process::exit(0);
+        });
+    });
+}
+
+#[derive(Serialize)]
+struct Check {
+    status: &'static str,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs `check` with [`CHECK_TIMEOUT`], timing it.
+async fn run<F>(check: F) -> Check
+where
+    F: std::future::Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err("timed out".to_string()),
+    };
+    Check {
+        status: if result.is_ok() { "ok" } else { "fail" },
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err(),
+    }
+}
+
+async fn healthz() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn readyz(State(redis): State<Pool>) -> Response {
+    if DRAINING.load(Ordering::SeqCst) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "draining", "checks": {} })),
+        )
+            .into_response();
+    }
+
+    let redis = run(async {
+        if !redis.is_connected() {
+            return Err("pool is not connected".to_string());
+        }
+        redis
+            .ping::<String>(None)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+    .await;
+    let auth = run(api::auth_alive()).await;
+
+    let ready = redis.ok() && auth.ok();
+    if !ready {
+        tracing::warn!(redis = ?redis.error, auth = ?auth.error, "not ready");
+    }
+    let body = json!({
+        "status": if ready { "ok" } else { "fail" },
+        "checks": { "redis": redis, "auth": auth },
+    });
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
@@ -4,8 +4,15 @@ use dioxus::prelude::*;
 
 use api::{check_login_status, get_my_permissions, logout};
 use ui::{data_dir::LoginStatus, setup_mode, CookieConsent, Navbar, TAILWIND};
-use views::{AdminPanel, Arcane, Ark, AssholeTimer, Landing, Login, NotFound, Profile, Register};
+use views::{
+    AdminPanel, Arcane, Ark, AssholeTimer, Device, ForgotPassword, Landing, Login, NotFound, Profile,
+    Register, ResetPassword, VerifyEmail,
+};
 
+#[cfg(not(target_arch = "wasm32"))]
+mod health;
+#[cfg(not(target_arch = "wasm32"))]
+mod public_api;
 mod views;
 
 pub static LOGIN_STATUS: GlobalSignal<LoginStatus> = Signal::global(|| LoginStatus::LoggedOut);
@@ -163,7 +170,8 @@ fn server_launch() -> ! {
             pool.wait_for_connect()
                 .await
                 .expect("failed to connect to Redis");
-            let session_store = RedisStore::new(pool);
+            let session_store = RedisStore::new(pool.clone());
+            health::drain_on_sigterm();
 
             let layer = SessionManagerLayer::new(session_store)
                 .with_secure(!cfg!(debug_assertions))
@@ -176,17 +184,23 @@ fn server_launch() -> ! {
             let router = Router::new()
                 .serve_dioxus_application(ServeConfig::default(), App)
                 .route("/oauth/start/{provider}", get(oauth_start))
+                .route("/oauth/link/{provider}", get(oauth_link_start))
                 .route("/oauth/callback/{provider}", get(oauth_callback))
+                .route("/oauth2/continue", get(oidc_continue))
                 .route("/ws/arcane", get(arcane_ws_proxy))
+                .merge(public_api::router())
                 .route(
                     "/metrics",
                     get(move || async move { metric_handle.render() }),
                 )
                 .layer(layer)
+                .layer(axum::middleware::from_fn(capture_bearer_token))
+                .layer(axum::middleware::from_fn(capture_client_info))
                 .layer(axum::middleware::from_fn(capture_traceparent))
                 .layer(OtelInResponseLayer)
                 .layer(OtelAxumLayer::default())
-                .layer(prometheus_layer);
+                .layer(prometheus_layer)
+                .merge(health::router(pool));
             Ok(router)
         }
     })
@@ -265,6 +279,63 @@ async fn proxy_ws(client: axum::extract::ws::WebSocket, upstream_url: String) {
     }
 }
 
+// ---- Client info capture middleware ----
+
+/// Resolves the browser's address from the Istio edge proxy headers and stores it, with
+/// the user agent, as `api::ClientInfo` so server functions can forward both to auth. The
+/// last `x-forwarded-for` hop is used because it is the one the gateway appended itself;
+/// earlier hops are client-controlled.
+#[cfg(not(target_arch = "wasm32"))]
+async fn capture_client_info(
+    mut req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let headers = req.headers();
+    let ip = headers
+        .get("x-envoy-external-address")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit(',').next())
+        })
+        .and_then(|v| v.trim().parse::<std::net::IpAddr>().ok())
+        .map(|ip| ip.to_string());
+    let user_agent = headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    req.extensions_mut()
+        .insert(api::ClientInfo { ip, user_agent });
+    next.run(req).await
+}
+
+// ---- Bearer token capture middleware ----
+
+/// Stores a BFF token sent as `Authorization: Bearer …` as `api::BearerToken`, so server
+/// functions work for the desktop and mobile apps, which sign in with the device flow and have
+/// no session cookie. Personal access tokens are left to the `/api/v1` routes.
+#[cfg(not(target_arch = "wasm32"))]
+async fn capture_bearer_token(
+    mut req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let token = req
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty() && !t.starts_with("pat_"))
+        .map(str::to_string);
+    if let Some(token) = token {
+        req.extensions_mut().insert(api::BearerToken(token));
+    }
+    next.run(req).await
+}
+
 // ---- Trace context capture middleware ----
 
 /// Runs after `OtelAxumLayer` has created the request span. Reads back the current
@@ -306,35 +377,78 @@ async fn capture_traceparent(
 
 const OAUTH_CSRF_KEY: &str = "oauth_csrf_state";
 const OAUTH_PROVIDER_KEY: &str = "oauth_provider";
+/// Set while the OAuth flow in progress links an account to the logged-in user instead of
+/// logging in.
+const OAUTH_LINK_KEY: &str = "oauth_link";
+/// Auth's codes for refusing to create an account on a first login, passed on to the Login view.
+const REGISTRATION_REFUSALS: [&str; 4] = [
+    "registration_closed",
+    "invite_required",
+    "invalid_invite",
+    "invite_email_mismatch",
+];
 
 /// Begin the OAuth flow for `provider`. Asks auth (cluster-internal) for the provider's
 /// authorization URL, stashes the CSRF state in the BFF session, and redirects the browser.
+/// An `?invite=` code is passed on to auth and redeemed if the login creates an account.
 #[cfg(not(target_arch = "wasm32"))]
 async fn oauth_start(
     axum::extract::Path(provider): axum::extract::Path<String>,
     session: tower_sessions::Session,
+    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+) -> axum::response::Response {
+    let _ = session.remove::<bool>(OAUTH_LINK_KEY).await;
+    let invite = params.get("invite").map(|c| c.trim()).filter(|c| !c.is_empty());
+    begin_oauth(&provider, invite, &session, "/login?error=").await
+}
+
+/// Begin the OAuth flow for linking `provider` to the logged-in user, from the profile page.
+#[cfg(not(target_arch = "wasm32"))]
+async fn oauth_link_start(
+    axum::extract::Path(provider): axum::extract::Path<String>,
+    session: tower_sessions::Session,
 ) -> axum::response::Response {
     use axum::response::{IntoResponse, Redirect};
 
-    if provider != "github" && provider != "google" {
-        return Redirect::to("/login?error=unknown_provider").into_response();
+    let token: Option<String> = session.get("opaque_token").await.ok().flatten();
+    if token.is_none() {
+        return Redirect::to("/login").into_response();
+    }
+    if let Err(e) = session.insert(OAUTH_LINK_KEY, true).await {
+        tracing::error!(error = %e, %provider, "oauth_link_start: failed to write link flag");
+        return Redirect::to("/profile?link=session_failed").into_response();
     }
+    begin_oauth(&provider, None, &session, "/profile?link=").await
+}
+
+/// Redirects to the provider, or to `error_base` followed by an error code.
+#[cfg(not(target_arch = "wasm32"))]
+async fn begin_oauth(
+    provider: &str,
+    invite_code: Option<&str>,
+    session: &tower_sessions::Session,
+    error_base: &str,
+) -> axum::response::Response {
+    use axum::response::{IntoResponse, Redirect};
 
-    match api::start_oauth(&provider).await {
+    match api::start_oauth(provider, invite_code).await {
         Ok((auth_url, state)) => {
             if let Err(e) = session.insert(OAUTH_CSRF_KEY, &state).await {
                 tracing::error!(error = %e, %provider, "oauth_start: failed to write CSRF state");
-                return Redirect::to("/login?error=session_failed").into_response();
+                return Redirect::to(&format!("{error_base}session_failed")).into_response();
             }
-            if let Err(e) = session.insert(OAUTH_PROVIDER_KEY, &provider).await {
+            if let Err(e) = session.insert(OAUTH_PROVIDER_KEY, provider).await {
                 tracing::error!(error = %e, %provider, "oauth_start: failed to write provider");
-                return Redirect::to("/login?error=session_failed").into_response();
+                return Redirect::to(&format!("{error_base}session_failed")).into_response();
             }
             Redirect::to(&auth_url).into_response()
         }
+        Err(e) if e == "unknown_provider" => {
+            Redirect::to(&format!("{error_base}unknown_provider")).into_response()
+        }
         Err(e) => {
             tracing::error!(error = %e, %provider, "oauth_start: api::start_oauth failed");
-            Redirect::to("/login?error=start_failed").into_response()
+            Redirect::to(&format!("{error_base}start_failed")).into_response()
         }
     }
 }
@@ -344,6 +458,7 @@ async fn oauth_start(
 #[cfg(not(target_arch = "wasm32"))]
 async fn oauth_callback(
     axum::extract::Path(provider): axum::extract::Path<String>,
+    client: Option<axum::Extension<api::ClientInfo>>,
     session: tower_sessions::Session,
     axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
 ) -> axum::response::Response {
@@ -360,13 +475,24 @@ async fn oauth_callback(
     let expected_provider: Option<String> = session.get(OAUTH_PROVIDER_KEY).await.ok().flatten();
     let _ = session.remove::<String>(OAUTH_CSRF_KEY).await;
     let _ = session.remove::<String>(OAUTH_PROVIDER_KEY).await;
+    let linking: bool = session
+        .remove(OAUTH_LINK_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(false);
 
     if expected_state.as_deref() != Some(&state) || expected_provider.as_deref() != Some(&provider)
     {
         return Redirect::to("/login?error=csrf_mismatch").into_response();
     }
 
-    match api::exchange_oauth_code(&provider, &code).await {
+    if linking {
+        return finish_link(&session, &provider, &code, &state).await;
+    }
+
+    let client = client.map(|axum::Extension(c)| c).unwrap_or_default();
+    match api::exchange_oauth_code(&provider, &code, &state, client).await {
         Ok((token, username)) => {
             if let Err(e) = session.insert("opaque_token", token).await {
                 tracing::error!(error = %e, %provider, "oauth_callback: failed to write opaque_token");
@@ -376,11 +502,30 @@ async fn oauth_callback(
                 tracing::error!(error = %e, %provider, "oauth_callback: failed to write username");
                 return Redirect::to("/login?error=session_failed").into_response();
             }
-            Redirect::to("/").into_response()
+            let pending: Option<String> = session
+                .remove(api::OIDC_PENDING_REQUEST_KEY)
+                .await
+                .ok()
+                .flatten();
+            match pending {
+                Some(request) => {
+                    Redirect::to(&format!("/oauth2/continue?request={request}")).into_response()
+                }
+                None => Redirect::to("/").into_response(),
+            }
         }
         Err(e) if e.contains("email_exists") || e.contains("Email already in use") => {
             Redirect::to("/login?error=email_exists").into_response()
         }
+        Err(e) if e.contains("username_taken") => {
+            Redirect::to("/login?error=username_taken").into_response()
+        }
+        Err(e) if e.contains("account_disabled") => {
+            Redirect::to("/login?error=account_disabled").into_response()
+        }
+        Err(e) if REGISTRATION_REFUSALS.contains(&e.as_str()) => {
+            Redirect::to(&format!("/login?error={e}")).into_response()
+        }
         Err(e) => {
             tracing::error!(error = %e, %provider, "oauth_callback: exchange_oauth_code failed");
             Redirect::to("/login?error=exchange_failed").into_response()
@@ -388,6 +533,68 @@ async fn oauth_callback(
     }
 }
 
+/// Second half of [`oauth_link_start`]: links the provider account to the session's user and
+/// returns to the profile page.
+#[cfg(not(target_arch = "wasm32"))]
+async fn finish_link(
+    session: &tower_sessions::Session,
+    provider: &str,
+    code: &str,
+    state: &str,
+) -> axum::response::Response {
+    use axum::response::{IntoResponse, Redirect};
+
+    let Some(token) = session.get::<String>("opaque_token").await.ok().flatten() else {
+        return Redirect::to("/login").into_response();
+    };
+    match api::link_oauth_identity(&token, provider, code, state).await {
+        Ok(()) => Redirect::to("/profile?link=linked").into_response(),
+        Err(e) if e == "identity_in_use" => {
+            Redirect::to("/profile?link=identity_in_use").into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = %e, %provider, "oauth_callback: link_oauth_identity failed");
+            Redirect::to("/profile?link=link_failed").into_response()
+        }
+    }
+}
+
+/// Resumes an OpenID Connect authorization request parked by auth's `/oauth2/authorize`.
+/// A logged-in browser is sent straight back to the relying party with a code; otherwise the
+/// request is remembered on the session and picked up again after login.
+#[cfg(not(target_arch = "wasm32"))]
+async fn oidc_continue(
+    session: tower_sessions::Session,
+    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+) -> axum::response::Response {
+    use axum::response::{IntoResponse, Redirect};
+
+    let Some(request) = params.get("request").cloned() else {
+        return Redirect::to("/login?error=invalid_request").into_response();
+    };
+
+    let token: Option<String> = session.get("opaque_token").await.ok().flatten();
+    let approval = match token {
+        Some(token) => api::approve_oidc_request(&token, &request).await,
+        None => api::OidcApproval::LoginRequired,
+    };
+
+    match approval {
+        api::OidcApproval::Redirect(url) => Redirect::to(&url).into_response(),
+        api::OidcApproval::LoginRequired => {
+            if let Err(e) = session.insert(api::OIDC_PENDING_REQUEST_KEY, &request).await {
+                tracing::error!(error = %e, "oidc_continue: failed to write pending request");
+                return Redirect::to("/login?error=session_failed").into_response();
+            }
+            Redirect::to("/login").into_response()
+        }
+        api::OidcApproval::Failed(e) => {
+            tracing::error!(error = %e, "oidc_continue: approve_oidc_request failed");
+            Redirect::to("/login?error=authorization_failed").into_response()
+        }
+    }
+}
+
 // ---- Dioxus app ----
 
 #[derive(Debug, Clone, Routable, PartialEq)]
@@ -400,8 +607,16 @@ enum Route {
         Login { error: String },
         #[route("/register")]
         Register {},
-        #[route("/profile")]
-        Profile {},
+        #[route("/forgot-password")]
+        ForgotPassword {},
+        #[route("/reset-password?:token")]
+        ResetPassword { token: String },
+        #[route("/verify-email?:token")]
+        VerifyEmail { token: String },
+        #[route("/device?:code")]
+        Device { code: String },
+        #[route("/profile?:link")]
+        Profile { link: String },
         #[route("/ark")]
         Ark {},
         #[route("/arcane")]
@@ -0,0 +1,84 @@
+//! Token-authenticated API for scripts and bots, under `/api/v1`.
+//!
+//! Requests carry a personal access token, created on the profile page, as
+//! `Authorization: Bearer pat_…`. There is no session or cookie involved; auth checks the token's
+//! scopes on every call. Responses are JSON, errors `{"error": "…"}`.
+//!
+//! - `GET /api/v1/ark/players` → `{"players": 3}` (scope `llama`)
+//! - `POST /api/v1/ark/{start|stop|restart}` → `{"result": "Started"}` (scope `llama`)
+
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::json;
+
+use api::ArkError;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/v1/ark/players", get(ark_players))
+        .route("/api/v1/ark/{cmd}", post(ark_command))
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+/// The personal access token from the `Authorization` header. Session tokens aren't accepted.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| t.starts_with("pat_"))
+}
+
+/// `401` for a request without a usable [`bearer_token`].
+fn unauthorized() -> Response {
+    let mut resp = error(StatusCode::UNAUTHORIZED, "A personal access token is required");
+    resp.headers_mut()
+        .insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+    resp
+}
+
+fn ark_error(e: ArkError) -> Response {
+    match e {
+        ArkError::Denied => error(
+            StatusCode::FORBIDDEN,
+            "The token is invalid, expired or revoked, or lacks the llama scope",
+        ),
+        ArkError::UnknownCommand => error(StatusCode::NOT_FOUND, "Unknown command"),
+        ArkError::NoResult => error(StatusCode::BAD_GATEWAY, "The Ark host returned no result"),
+        ArkError::Failed(e) => {
+            tracing::error!(error = %e, "public api: ark request failed");
+            error(StatusCode::BAD_GATEWAY, "Could not reach the Ark host")
+        }
+    }
+}
+
+#[tracing::instrument(name = "public_api.ark_players", skip_all)]
+async fn ark_players(headers: HeaderMap) -> Response {
+    let Some(token) = bearer_token(&headers) else {
+        return unauthorized();
+    };
+    match api::ark_player_count_for(token).await {
+        Ok(players) => Json(json!({ "players": players })).into_response(),
+        Err(e) => ark_error(e),
+    }
+}
+
+#[tracing::instrument(name = "public_api.ark_command", skip_all, fields(cmd = %cmd))]
+async fn ark_command(Path(cmd): Path<String>, headers: HeaderMap) -> Response {
+    let Some(token) = bearer_token(&headers) else {
+        return unauthorized();
+    };
+    match api::ark_command_for(token, &cmd).await {
+        Ok(result) => Json(json!({ "result": result })).into_response(),
+        Err(e) => ark_error(e),
+    }
+}
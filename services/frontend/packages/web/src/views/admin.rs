@@ -1,9 +1,14 @@
 use dioxus::prelude::*;
 
 use api::{
-    admin_assign_role_permission, admin_assign_user_role, admin_list_all_roles,
-    admin_list_permissions, admin_list_roles, admin_list_users, admin_revoke_role_permission,
-    admin_revoke_user_role, AdminPermission, AdminRole, AdminUser,
+    admin_add_role_parent, admin_assign_role_permission, admin_assign_user_role,
+    admin_create_invite, admin_create_permission, admin_create_role, admin_delete_permission,
+    admin_delete_role, admin_delete_user, admin_disable_user, admin_enable_user,
+    admin_list_all_roles, admin_list_audit_events, admin_list_invites, admin_list_permissions,
+    admin_list_roles, admin_list_users, admin_remove_role_parent, admin_reset_user_mfa,
+    admin_revoke_invite, admin_revoke_role_permission, admin_revoke_user_role,
+    admin_update_permission, admin_update_role, AdminInvite, AdminPermission, AdminRole,
+    AdminUser, AuditEvent, GrantDuration, NewInvite,
 };
 use ui::data_dir::LoginStatus;
 
@@ -11,6 +16,22 @@ use crate::{LOGIN_STATUS, PERMISSIONS};
 
 const PAGE_SIZE: u32 = 25;
 
+/// Durations offered when granting a role, in hours; `None` never expires.
+const GRANT_DURATIONS: &[(&str, Option<u32>)] = &[
+    ("Forever", None),
+    ("24 hours", Some(24)),
+    ("7 days", Some(7 * 24)),
+    ("30 days", Some(30 * 24)),
+];
+
+/// How long a new invite code stays valid, in seconds; `None` never expires.
+const INVITE_EXPIRIES: &[(&str, Option<i64>)] = &[
+    ("7 days", Some(7 * 86_400)),
+    ("24 hours", Some(86_400)),
+    ("30 days", Some(30 * 86_400)),
+    ("Never", None),
+];
+
 #[component]
 pub fn AdminPanel() -> Element {
     let has_perm = PERMISSIONS.read().contains_key("manage_permissions");
@@ -34,6 +55,9 @@ pub fn AdminPanel() -> Element {
 enum Tab {
     Users,
     Roles,
+    Permissions,
+    Invites,
+    Audit,
 }
 
 #[component]
@@ -41,7 +65,7 @@ fn AdminPanelInner() -> Element {
     let mut tab = use_signal(|| Tab::Users);
 
     // all_roles and all_permissions are small lists used only for assignment dropdowns.
-    let support = use_resource(move || async move {
+    let mut support = use_resource(move || async move {
         let r = admin_list_all_roles().await;
         let p = admin_list_permissions().await;
         (r, p)
@@ -86,11 +110,33 @@ fn AdminPanelInner() -> Element {
                         onclick: move |_| tab.set(Tab::Roles),
                         "Roles"
                     }
+                    button {
+                        class: if tab() == Tab::Permissions { "tab tab-active" } else { "tab" },
+                        onclick: move |_| tab.set(Tab::Permissions),
+                        "Permissions"
+                    }
+                    button {
+                        class: if tab() == Tab::Invites { "tab tab-active" } else { "tab" },
+                        onclick: move |_| tab.set(Tab::Invites),
+                        "Invites"
+                    }
+                    button {
+                        class: if tab() == Tab::Audit { "tab tab-active" } else { "tab" },
+                        onclick: move |_| tab.set(Tab::Audit),
+                        "Audit"
+                    }
                 }
 
                 match tab() {
                     Tab::Users => rsx! { UsersTab { all_roles } },
-                    Tab::Roles => rsx! { RolesTab { all_permissions } },
+                    Tab::Roles => rsx! {
+                        RolesTab { all_roles, all_permissions, on_change: move |_| support.restart() }
+                    },
+                    Tab::Permissions => rsx! {
+                        PermissionsTab { all_permissions, on_change: move |_| support.restart() }
+                    },
+                    Tab::Invites => rsx! { InvitesTab { all_roles } },
+                    Tab::Audit => rsx! { AuditTab {} },
                 }
             }
         }
@@ -158,7 +204,10 @@ fn UsersTab(all_roles: Vec<AdminRole>) -> Element {
                         tr {
                             th { "Username" }
                             th { "Email" }
+                            th { "Status" }
+                            th { "MFA" }
                             th { "Roles" }
+                            th {}
                         }
                     }
                     tbody {
@@ -194,11 +243,47 @@ fn UserRow(user: AdminUser, all_roles: Vec<AdminRole>, on_change: EventHandler<(
 
     let mut selected_role_id =
         use_signal(|| unassigned.first().map(|r| r.id).unwrap_or(0i32));
+    // An index into GRANT_DURATIONS, or "until" to pick a date.
+    let mut duration = use_signal(|| "0".to_string());
+    let mut until = use_signal(String::new);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
 
     rsx! {
         tr {
             td { class: "font-medium", "{user.username}" }
             td { class: "text-base-content/60", { user.email.as_deref().unwrap_or("—") } }
+            td {
+                if user.disabled_at.is_some() {
+                    span {
+                        class: "badge badge-error",
+                        title: user.disabled_reason.clone().unwrap_or_default(),
+                        "Disabled"
+                    }
+                } else {
+                    span { class: "badge badge-success badge-outline", "Active" }
+                }
+            }
+            td {
+                if user.mfa_enabled {
+                    div { class: "flex gap-1 items-center",
+                        span { class: "badge badge-success", "On" }
+                        button {
+                            class: "btn btn-ghost btn-xs",
+                            title: "Remove this user's authenticator and recovery codes",
+                            onclick: move |_| {
+                                let user_id = user.id;
+                                spawn(async move {
+                                    let _ = admin_reset_user_mfa(user_id).await;
+                                    on_change.call(());
+                                });
+                            },
+                            "Reset"
+                        }
+                    }
+                } else {
+                    span { class: "badge badge-ghost", "Off" }
+                }
+            }
             td {
                 div { class: "flex flex-wrap gap-1 items-center",
                     for role in user.roles.iter() {
@@ -206,14 +291,27 @@ fn UserRow(user: AdminUser, all_roles: Vec<AdminRole>, on_change: EventHandler<(
                             let role_id = role.id;
                             let user_id = user.id;
                             let role_name = role.name.clone();
+                            // RFC 3339 from auth, always UTC: show "YYYY-MM-DD HH:MM".
+                            let until = role
+                                .expires_at
+                                .as_deref()
+                                .map(|at| at.get(..16).unwrap_or(at).replace('T', " "));
                             rsx! {
-                                span { class: "badge badge-primary gap-1",
+                                span {
+                                    class: if until.is_some() { "badge badge-primary badge-outline gap-1" } else { "badge badge-primary gap-1" },
+                                    title: until.as_ref().map(|u| format!("Expires {u} UTC")).unwrap_or_default(),
                                     "{role_name}"
+                                    if let Some(u) = &until {
+                                        span { class: "opacity-60", "until {u}" }
+                                    }
                                     button {
                                         class: "btn btn-ghost btn-xs p-0 min-h-0 h-auto leading-none",
                                         onclick: move |_| {
                                             spawn(async move {
-                                                let _ = admin_revoke_user_role(user_id, role_id).await;
+                                                match admin_revoke_user_role(user_id, role_id).await {
+                                                    Ok(()) => error.set(None),
+                                                    Err(e) => error.set(Some(e.to_string())),
+                                                }
                                                 on_change.call(());
                                             });
                                         },
@@ -236,13 +334,40 @@ fn UserRow(user: AdminUser, all_roles: Vec<AdminRole>, on_change: EventHandler<(
                                     option { value: "{role.id}", "{role.name}" }
                                 }
                             }
+                            select {
+                                class: "select select-xs select-bordered",
+                                title: "How long the role is granted for",
+                                onchange: move |e: Event<FormData>| duration.set(e.value()),
+                                for (i, (label, _)) in GRANT_DURATIONS.iter().enumerate() {
+                                    option { value: "{i}", "{label}" }
+                                }
+                                option { value: "until", "Until date…" }
+                            }
+                            if duration() == "until" {
+                                input {
+                                    class: "input input-xs input-bordered",
+                                    r#type: "date",
+                                    value: "{until}",
+                                    oninput: move |e| until.set(e.value()),
+                                }
+                            }
                             button {
                                 class: "btn btn-xs btn-success",
                                 onclick: move |_| {
                                     let role_id = selected_role_id();
                                     let user_id = user.id;
+                                    let grant = match duration().parse::<usize>() {
+                                        Ok(i) => match GRANT_DURATIONS.get(i).and_then(|(_, h)| *h) {
+                                            Some(hours) => GrantDuration::Hours(hours),
+                                            None => GrantDuration::Forever,
+                                        },
+                                        Err(_) => GrantDuration::Until(until()),
+                                    };
                                     spawn(async move {
-                                        let _ = admin_assign_user_role(user_id, role_id).await;
+                                        match admin_assign_user_role(user_id, role_id, grant).await {
+                                            Ok(()) => error.set(None),
+                                            Err(e) => error.set(Some(e.to_string())),
+                                        }
                                         on_change.call(());
                                     });
                                 },
@@ -251,6 +376,116 @@ fn UserRow(user: AdminUser, all_roles: Vec<AdminRole>, on_change: EventHandler<(
                         }
                     }
                 }
+                if let Some(err) = error() {
+                    div { class: "alert alert-error mt-2 py-1 text-sm", span { "{err}" } }
+                }
+            }
+            td { class: "text-right whitespace-nowrap",
+                AccountActions {
+                    user: user.clone(),
+                    on_error: move |e| error.set(Some(e)),
+                    on_change: move |_| {
+                        error.set(None);
+                        on_change.call(());
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Which account action is waiting for confirmation.
+#[derive(Clone, Copy, PartialEq)]
+enum AccountAction {
+    Disable,
+    Delete,
+}
+
+/// Disable (with a reason), enable and delete buttons for a user, the destructive ones confirmed
+/// in a modal first.
+#[component]
+fn AccountActions(
+    user: AdminUser,
+    on_error: EventHandler<String>,
+    on_change: EventHandler<()>,
+) -> Element {
+    let mut confirming: Signal<Option<AccountAction>> = use_signal(|| None);
+    let mut reason = use_signal(String::new);
+    let user_id = user.id;
+    let username = user.username.clone();
+
+    let mut run = move |action: Option<AccountAction>| {
+        confirming.set(None);
+        spawn(async move {
+            let result = match action {
+                Some(AccountAction::Disable) => admin_disable_user(user_id, reason()).await,
+                Some(AccountAction::Delete) => admin_delete_user(user_id).await,
+                None => admin_enable_user(user_id).await,
+            };
+            match result {
+                Ok(()) => on_change.call(()),
+                Err(e) => on_error.call(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        if user.disabled_at.is_some() {
+            button {
+                class: "btn btn-ghost btn-xs",
+                onclick: move |_| run(None),
+                "Enable"
+            }
+        } else {
+            button {
+                class: "btn btn-ghost btn-xs text-warning",
+                onclick: move |_| {
+                    reason.set(String::new());
+                    confirming.set(Some(AccountAction::Disable));
+                },
+                "Disable"
+            }
+        }
+        button {
+            class: "btn btn-ghost btn-xs text-error",
+            onclick: move |_| confirming.set(Some(AccountAction::Delete)),
+            "Delete"
+        }
+        if let Some(action) = confirming() {
+            dialog { class: "modal modal-open",
+                div { class: "modal-box text-left whitespace-normal",
+                    if action == AccountAction::Disable {
+                        h3 { class: "font-bold text-lg", "Disable {username}?" }
+                        p { class: "py-4",
+                            "They are logged out everywhere and can't log in until re-enabled. Their username and email stay taken."
+                        }
+                        input {
+                            class: "input input-bordered input-sm w-full",
+                            r#type: "text",
+                            maxlength: "500",
+                            placeholder: "Reason (shown to admins only)",
+                            value: "{reason}",
+                            oninput: move |e| reason.set(e.value()),
+                        }
+                    } else {
+                        h3 { class: "font-bold text-lg", "Delete {username}?" }
+                        p { class: "py-4",
+                            "Their roles, sessions, credentials and linked logins are removed with them. This cannot be undone."
+                        }
+                    }
+                    div { class: "modal-action",
+                        button {
+                            class: "btn btn-ghost",
+                            onclick: move |_| confirming.set(None),
+                            "Cancel"
+                        }
+                        button {
+                            class: if action == AccountAction::Disable { "btn btn-warning" } else { "btn btn-error" },
+                            onclick: move |_| run(Some(action)),
+                            if action == AccountAction::Disable { "Disable" } else { "Delete" }
+                        }
+                    }
+                }
             }
         }
     }
@@ -259,7 +494,11 @@ fn UserRow(user: AdminUser, all_roles: Vec<AdminRole>, on_change: EventHandler<(
 // ── Roles tab ─────────────────────────────────────────────────────────────────
 
 #[component]
-fn RolesTab(all_permissions: Vec<AdminPermission>) -> Element {
+fn RolesTab(
+    all_roles: Vec<AdminRole>,
+    all_permissions: Vec<AdminPermission>,
+    on_change: EventHandler<()>,
+) -> Element {
     let mut search = use_signal(|| String::new());
     let mut page = use_signal(|| 0u32);
     let mut refresh = use_signal(|| 0u32);
@@ -306,6 +545,14 @@ fn RolesTab(all_permissions: Vec<AdminPermission>) -> Element {
                 span { class: "text-sm text-base-content/50 shrink-0", "{total} role(s)" }
             }
 
+            CreateForm {
+                kind: Kind::Role,
+                on_change: move |_| {
+                    *refresh.write() += 1;
+                    on_change.call(());
+                },
+            }
+
             if let Some(err) = load_error() {
                 div { class: "alert alert-error text-sm font-mono", "{err}" }
             }
@@ -315,7 +562,9 @@ fn RolesTab(all_permissions: Vec<AdminPermission>) -> Element {
                     thead {
                         tr {
                             th { "Role" }
+                            th { "Inherits from" }
                             th { "Permissions" }
+                            th {}
                         }
                     }
                     tbody {
@@ -323,8 +572,12 @@ fn RolesTab(all_permissions: Vec<AdminPermission>) -> Element {
                             RoleRow {
                                 key: "{role.id}",
                                 role: role.clone(),
+                                all_roles: all_roles.clone(),
                                 all_permissions: all_permissions.clone(),
-                                on_change: move |_| *refresh.write() += 1,
+                                on_change: move |_| {
+                                    *refresh.write() += 1;
+                                    on_change.call(());
+                                },
                             }
                         }
                     }
@@ -344,6 +597,7 @@ fn RolesTab(all_permissions: Vec<AdminPermission>) -> Element {
 #[component]
 fn RoleRow(
     role: AdminRole,
+    all_roles: Vec<AdminRole>,
     all_permissions: Vec<AdminPermission>,
     on_change: EventHandler<()>,
 ) -> Element {
@@ -355,10 +609,30 @@ fn RoleRow(
 
     let mut selected_perm_id =
         use_signal(|| unassigned.first().map(|p| p.id).unwrap_or(0i32));
+    let mut error: Signal<Option<String>> = use_signal(|| None);
 
     rsx! {
         tr {
-            td { class: "font-medium", "{role.name}" }
+            td {
+                NameCell {
+                    kind: Kind::Role,
+                    id: role.id,
+                    name: role.name.clone(),
+                    description: role.description.clone(),
+                    on_change,
+                }
+            }
+            td {
+                ParentsCell {
+                    role: role.clone(),
+                    all_roles,
+                    on_error: move |e| error.set(Some(e)),
+                    on_change: move |_| {
+                        error.set(None);
+                        on_change.call(());
+                    },
+                }
+            }
             td {
                 div { class: "flex flex-wrap gap-1 items-center",
                     for perm in role.permissions.iter() {
@@ -373,7 +647,10 @@ fn RoleRow(
                                         class: "btn btn-ghost btn-xs p-0 min-h-0 h-auto leading-none",
                                         onclick: move |_| {
                                             spawn(async move {
-                                                let _ = admin_revoke_role_permission(role_id, perm_id).await;
+                                                match admin_revoke_role_permission(role_id, perm_id).await {
+                                                    Ok(()) => error.set(None),
+                                                    Err(e) => error.set(Some(e.to_string())),
+                                                }
                                                 on_change.call(());
                                             });
                                         },
@@ -411,7 +688,850 @@ fn RoleRow(
                         }
                     }
                 }
+                if let Some(err) = error() {
+                    div { class: "alert alert-error mt-2 py-1 text-sm", span { "{err}" } }
+                }
+            }
+            td { class: "text-right",
+                DeleteButton {
+                    kind: Kind::Role,
+                    id: role.id,
+                    name: role.name.clone(),
+                    on_error: move |e| error.set(Some(e)),
+                    on_change,
+                }
+            }
+        }
+    }
+}
+
+/// The roles `role` inherits from, with controls to add and remove them. Adding one that already
+/// inherits from `role` is refused by the auth service.
+#[component]
+fn ParentsCell(
+    role: AdminRole,
+    all_roles: Vec<AdminRole>,
+    on_error: EventHandler<String>,
+    on_change: EventHandler<()>,
+) -> Element {
+    let candidates: Vec<AdminRole> = all_roles
+        .into_iter()
+        .filter(|r| r.id != role.id && !role.parents.iter().any(|p| p.id == r.id))
+        .collect();
+    let mut selected_parent_id =
+        use_signal(|| candidates.first().map(|r| r.id).unwrap_or(0i32));
+    let role_id = role.id;
+
+    let report = move |result: Result<(), ServerFnError>| match result {
+        Ok(()) => on_change.call(()),
+        Err(e) => on_error.call(e.to_string()),
+    };
+
+    rsx! {
+        div { class: "flex flex-wrap gap-1 items-center",
+            for parent in role.parents.iter() {
+                {
+                    let parent_id = parent.id;
+                    let parent_name = parent.name.clone();
+                    rsx! {
+                        span { class: "badge badge-accent gap-1",
+                            "{parent_name}"
+                            button {
+                                class: "btn btn-ghost btn-xs p-0 min-h-0 h-auto leading-none",
+                                onclick: move |_| {
+                                    spawn(async move {
+                                        report(admin_remove_role_parent(role_id, parent_id).await);
+                                    });
+                                },
+                                "✕"
+                            }
+                        }
+                    }
+                }
+            }
+            if !candidates.is_empty() {
+                div { class: "flex gap-1 items-center",
+                    select {
+                        class: "select select-xs select-bordered",
+                        onchange: move |e: Event<FormData>| {
+                            if let Ok(id) = e.value().parse::<i32>() {
+                                selected_parent_id.set(id);
+                            }
+                        },
+                        for candidate in &candidates {
+                            option { value: "{candidate.id}", "{candidate.name}" }
+                        }
+                    }
+                    button {
+                        class: "btn btn-xs btn-success",
+                        onclick: move |_| {
+                            let parent_id = selected_parent_id();
+                            spawn(async move {
+                                report(admin_add_role_parent(role_id, parent_id).await);
+                            });
+                        },
+                        "+"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ── Permissions tab ───────────────────────────────────────────────────────────
+
+#[component]
+fn PermissionsTab(all_permissions: Vec<AdminPermission>, on_change: EventHandler<()>) -> Element {
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    rsx! {
+        div { class: "space-y-3",
+            p { class: "text-sm text-base-content/60",
+                "Services check permissions by name, so renaming or deleting one they use takes "
+                "effect on their next token refresh."
+            }
+
+            CreateForm { kind: Kind::Permission, on_change }
+
+            if let Some(err) = error() {
+                div { class: "alert alert-error text-sm", span { "{err}" } }
+            }
+
+            div { class: "overflow-x-auto",
+                table { class: "table w-full",
+                    thead {
+                        tr {
+                            th { "Permission" }
+                            th {}
+                        }
+                    }
+                    tbody {
+                        for perm in all_permissions {
+                            tr { key: "{perm.id}",
+                                td {
+                                    NameCell {
+                                        kind: Kind::Permission,
+                                        id: perm.id,
+                                        name: perm.name.clone(),
+                                        description: perm.description.clone(),
+                                        on_change,
+                                    }
+                                }
+                                td { class: "text-right",
+                                    DeleteButton {
+                                        kind: Kind::Permission,
+                                        id: perm.id,
+                                        name: perm.name.clone(),
+                                        on_error: move |e| error.set(Some(e)),
+                                        on_change,
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ── Role and permission editing ───────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Role,
+    Permission,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Role => "role",
+            Kind::Permission => "permission",
+        }
+    }
+
+    async fn create(self, name: String, description: String) -> Result<(), ServerFnError> {
+        match self {
+            Kind::Role => admin_create_role(name, description).await.map(|_| ()),
+            Kind::Permission => admin_create_permission(name, description).await.map(|_| ()),
+        }
+    }
+
+    async fn update(self, id: i32, name: String, description: String) -> Result<(), ServerFnError> {
+        match self {
+            Kind::Role => admin_update_role(id, name, description).await,
+            Kind::Permission => admin_update_permission(id, name, description).await,
+        }
+    }
+
+    async fn delete(self, id: i32) -> Result<(), ServerFnError> {
+        match self {
+            Kind::Role => admin_delete_role(id).await,
+            Kind::Permission => admin_delete_permission(id).await,
+        }
+    }
+}
+
+#[component]
+fn CreateForm(kind: Kind, on_change: EventHandler<()>) -> Element {
+    let mut name = use_signal(String::new);
+    let mut description = use_signal(String::new);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+    let label = kind.label();
+
+    rsx! {
+        form {
+            class: "flex flex-wrap items-center gap-2",
+            onsubmit: move |evt: FormEvent| {
+                evt.prevent_default();
+                spawn(async move {
+                    match kind.create(name(), description()).await {
+                        Ok(()) => {
+                            name.set(String::new());
+                            description.set(String::new());
+                            error.set(None);
+                            on_change.call(());
+                        }
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+            },
+            input {
+                class: "input input-bordered input-sm w-48",
+                r#type: "text",
+                placeholder: "new_{label}_name",
+                value: "{name}",
+                oninput: move |e| name.set(e.value()),
+            }
+            input {
+                class: "input input-bordered input-sm w-full max-w-sm",
+                r#type: "text",
+                placeholder: "Description (optional)",
+                value: "{description}",
+                oninput: move |e| description.set(e.value()),
+            }
+            button {
+                class: "btn btn-sm btn-success",
+                r#type: "submit",
+                disabled: name().trim().is_empty(),
+                "Create {label}"
+            }
+            if let Some(err) = error() {
+                span { class: "text-sm text-error", "{err}" }
+            }
+        }
+    }
+}
+
+/// A role's or permission's name and description, with an inline form to change them.
+#[component]
+fn NameCell(
+    kind: Kind,
+    id: i32,
+    name: String,
+    description: Option<String>,
+    on_change: EventHandler<()>,
+) -> Element {
+    let mut editing = use_signal(|| false);
+    let mut new_name = use_signal(|| name.clone());
+    let mut new_description = use_signal(|| description.clone().unwrap_or_default());
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    if !editing() {
+        return rsx! {
+            div { class: "flex items-start gap-2",
+                div {
+                    div { class: "font-medium", "{name}" }
+                    if let Some(d) = &description {
+                        div { class: "text-xs text-base-content/60", "{d}" }
+                    }
+                }
+                button {
+                    class: "btn btn-ghost btn-xs",
+                    title: "Rename or change the description",
+                    onclick: move |_| editing.set(true),
+                    "✎"
+                }
+            }
+        };
+    }
+
+    rsx! {
+        form {
+            class: "flex flex-col gap-1",
+            onsubmit: move |evt: FormEvent| {
+                evt.prevent_default();
+                spawn(async move {
+                    match kind.update(id, new_name(), new_description()).await {
+                        Ok(()) => {
+                            error.set(None);
+                            editing.set(false);
+                            on_change.call(());
+                        }
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+            },
+            input {
+                class: "input input-bordered input-xs",
+                r#type: "text",
+                value: "{new_name}",
+                oninput: move |e| new_name.set(e.value()),
+            }
+            input {
+                class: "input input-bordered input-xs",
+                r#type: "text",
+                placeholder: "Description",
+                value: "{new_description}",
+                oninput: move |e| new_description.set(e.value()),
+            }
+            div { class: "flex gap-1",
+                button { class: "btn btn-xs btn-primary", r#type: "submit", "Save" }
+                button {
+                    class: "btn btn-xs btn-ghost",
+                    r#type: "button",
+                    onclick: move |_| editing.set(false),
+                    "Cancel"
+                }
+            }
+            if let Some(err) = error() {
+                span { class: "text-xs text-error", "{err}" }
+            }
+        }
+    }
+}
+
+/// Delete button that asks for confirmation in a modal first.
+#[component]
+fn DeleteButton(
+    kind: Kind,
+    id: i32,
+    name: String,
+    on_error: EventHandler<String>,
+    on_change: EventHandler<()>,
+) -> Element {
+    let mut confirming = use_signal(|| false);
+    let label = kind.label();
+    let consequence = match kind {
+        Kind::Role => "Every user who has it loses it and the permissions it grants.",
+        Kind::Permission => "Every role that has it loses it.",
+    };
+
+    rsx! {
+        button {
+            class: "btn btn-ghost btn-xs text-error",
+            onclick: move |_| confirming.set(true),
+            "Delete"
+        }
+        if confirming() {
+            dialog { class: "modal modal-open",
+                div { class: "modal-box text-left",
+                    h3 { class: "font-bold text-lg", "Delete {label} {name}?" }
+                    p { class: "py-4", "{consequence} This cannot be undone." }
+                    div { class: "modal-action",
+                        button {
+                            class: "btn btn-ghost",
+                            onclick: move |_| confirming.set(false),
+                            "Cancel"
+                        }
+                        button {
+                            class: "btn btn-error",
+                            onclick: move |_| {
+                                confirming.set(false);
+                                spawn(async move {
+                                    match kind.delete(id).await {
+                                        Ok(()) => on_change.call(()),
+                                        Err(e) => on_error.call(e.to_string()),
+                                    }
+                                });
+                            },
+                            "Delete"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ── Invites tab ───────────────────────────────────────────────────────────────
+
+#[component]
+fn InvitesTab(all_roles: Vec<AdminRole>) -> Element {
+    let mut page = use_signal(|| 0u32);
+    let mut refresh = use_signal(|| 0u32);
+    let mut load_error: Signal<Option<String>> = use_signal(|| None);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    let data = use_resource(move || {
+        let p = page();
+        let _ = refresh();
+        async move { admin_list_invites(p, PAGE_SIZE).await }
+    });
+
+    let (invites, total) = match data.value()() {
+        None => {
+            return rsx! {
+                div { class: "flex justify-center p-12",
+                    span { class: "loading loading-spinner loading-lg" }
+                }
+            }
+        }
+        Some(Ok(r)) => {
+            load_error.set(None);
+            (r.items, r.total)
+        }
+        Some(Err(e)) => {
+            load_error.set(Some(e.to_string()));
+            (vec![], 0i64)
+        }
+    };
+
+    rsx! {
+        div { class: "space-y-3",
+            CreateInviteForm { all_roles, on_change: move |_| *refresh.write() += 1 }
+
+            span { class: "text-sm text-base-content/50", "{total} invite code(s)" }
+
+            if let Some(err) = load_error() {
+                div { class: "alert alert-error text-sm font-mono", "{err}" }
+            }
+            if let Some(err) = error() {
+                div { class: "alert alert-error text-sm", span { "{err}" } }
+            }
+
+            div { class: "overflow-x-auto",
+                table { class: "table w-full",
+                    thead {
+                        tr {
+                            th { "Code" }
+                            th { "Status" }
+                            th { "Uses" }
+                            th { "Restrictions" }
+                            th { "Redeemed by" }
+                            th {}
+                        }
+                    }
+                    tbody {
+                        for invite in invites {
+                            InviteRow {
+                                key: "{invite.id}",
+                                invite: invite.clone(),
+                                on_error: move |e| error.set(Some(e)),
+                                on_change: move |_| {
+                                    error.set(None);
+                                    *refresh.write() += 1;
+                                },
+                            }
+                        }
+                    }
+                }
+            }
+
+            Pagination {
+                page: page(),
+                total,
+                limit: PAGE_SIZE,
+                on_page: move |p| page.set(p),
+            }
+        }
+    }
+}
+
+/// Creates an invite code and shows it once; it can't be retrieved afterwards.
+#[component]
+fn CreateInviteForm(all_roles: Vec<AdminRole>, on_change: EventHandler<()>) -> Element {
+    let mut max_uses = use_signal(|| "1".to_string());
+    // An index into INVITE_EXPIRIES.
+    let mut expiry = use_signal(|| 0usize);
+    let mut email = use_signal(String::new);
+    let mut note = use_signal(String::new);
+    let mut role_ids: Signal<Vec<i32>> = use_signal(Vec::new);
+    let mut created: Signal<Option<String>> = use_signal(|| None);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    rsx! {
+        form {
+            class: "bg-base-100 p-4 rounded-lg space-y-3",
+            onsubmit: move |evt: FormEvent| {
+                evt.prevent_default();
+                let max_uses = match max_uses().trim() {
+                    "" => None,
+                    n => match n.parse::<i32>() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => {
+                            error.set(Some("Uses must be a positive number, or blank for unlimited".to_string()));
+                            return;
+                        }
+                    },
+                };
+                let invite = NewInvite {
+                    max_uses,
+                    expires_in_secs: INVITE_EXPIRIES.get(expiry()).and_then(|(_, secs)| *secs),
+                    email: email(),
+                    role_ids: role_ids(),
+                    note: note(),
+                };
+                spawn(async move {
+                    match admin_create_invite(invite).await {
+                        Ok(code) => {
+                            created.set(Some(code));
+                            email.set(String::new());
+                            note.set(String::new());
+                            role_ids.set(Vec::new());
+                            error.set(None);
+                            on_change.call(());
+                        }
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+            },
+            div { class: "flex flex-wrap items-center gap-2",
+                input {
+                    class: "input input-bordered input-sm w-24",
+                    r#type: "number",
+                    min: "1",
+                    title: "How many accounts can use the code; blank for unlimited",
+                    placeholder: "∞ uses",
+                    value: "{max_uses}",
+                    oninput: move |e| max_uses.set(e.value()),
+                }
+                select {
+                    class: "select select-bordered select-sm",
+                    title: "How long the code stays valid",
+                    onchange: move |e: Event<FormData>| {
+                        if let Ok(i) = e.value().parse::<usize>() {
+                            expiry.set(i);
+                        }
+                    },
+                    for (i, (label, _)) in INVITE_EXPIRIES.iter().enumerate() {
+                        option { value: "{i}", selected: expiry() == i, "{label}" }
+                    }
+                }
+                input {
+                    class: "input input-bordered input-sm w-56",
+                    r#type: "email",
+                    placeholder: "Only for this email (optional)",
+                    value: "{email}",
+                    oninput: move |e| email.set(e.value()),
+                }
+                input {
+                    class: "input input-bordered input-sm w-full max-w-xs",
+                    r#type: "text",
+                    placeholder: "Note (optional)",
+                    value: "{note}",
+                    oninput: move |e| note.set(e.value()),
+                }
+                button { class: "btn btn-sm btn-success", r#type: "submit", "Create invite code" }
+            }
+            if !all_roles.is_empty() {
+                div { class: "flex flex-wrap items-center gap-3 text-sm",
+                    span { class: "text-base-content/60", "Grant on sign-up:" }
+                    for role in all_roles {
+                        {
+                            let role_id = role.id;
+                            rsx! {
+                                label { class: "flex items-center gap-1 cursor-pointer",
+                                    input {
+                                        class: "checkbox checkbox-xs",
+                                        r#type: "checkbox",
+                                        checked: role_ids().contains(&role_id),
+                                        onchange: move |_| {
+                                            let mut ids = role_ids.write();
+                                            match ids.iter().position(|id| *id == role_id) {
+                                                Some(i) => { ids.remove(i); }
+                                                None => ids.push(role_id),
+                                            }
+                                        },
+                                    }
+                                    "{role.name}"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(code) = created() {
+                div { class: "alert alert-success",
+                    span {
+                        "New invite code: "
+                        code { class: "font-mono font-bold select-all", "{code}" }
+                        " — copy it now, it won't be shown again."
+                    }
+                    button {
+                        class: "btn btn-ghost btn-xs",
+                        r#type: "button",
+                        onclick: move |_| created.set(None),
+                        "✕"
+                    }
+                }
+            }
+            if let Some(err) = error() {
+                div { class: "text-sm text-error", "{err}" }
+            }
+        }
+    }
+}
+
+#[component]
+fn InviteRow(invite: AdminInvite, on_error: EventHandler<String>, on_change: EventHandler<()>) -> Element {
+    let mut confirming = use_signal(|| false);
+    let invite_id = invite.id;
+    let hint = invite.hint.clone();
+    // RFC 3339 from auth, always UTC: show "YYYY-MM-DD HH:MM".
+    let short = |at: &str| at.get(..16).unwrap_or(at).replace('T', " ");
+    let created = short(&invite.created_at);
+    let expires = invite.expires_at.as_deref().map(short);
+    let uses = match invite.max_uses {
+        Some(max) => format!("{} / {max}", invite.use_count),
+        None => format!("{} / ∞", invite.use_count),
+    };
+    let (badge, status) = match invite.status.as_str() {
+        "active" => ("badge badge-success badge-outline", "Active"),
+        "revoked" => ("badge badge-error", "Revoked"),
+        "expired" => ("badge badge-ghost", "Expired"),
+        _ => ("badge badge-ghost", "Used up"),
+    };
+
+    rsx! {
+        tr {
+            td {
+                div { class: "font-mono", "…{invite.hint}" }
+                div { class: "text-xs text-base-content/60",
+                    "{created}"
+                    if let Some(by) = &invite.created_by { " by {by}" }
+                }
+                if let Some(note) = &invite.note {
+                    div { class: "text-xs italic", "{note}" }
+                }
+            }
+            td { span { class: badge, "{status}" } }
+            td { "{uses}" }
+            td { class: "text-sm",
+                div {
+                    match &expires {
+                        Some(at) => rsx! { "Expires {at} UTC" },
+                        None => rsx! { "Never expires" },
+                    }
+                }
+                if let Some(email) = &invite.email {
+                    div { "Only {email}" }
+                }
+                if !invite.roles.is_empty() {
+                    div { class: "flex flex-wrap gap-1 mt-1",
+                        for role in invite.roles.iter() {
+                            span { class: "badge badge-primary badge-sm", "{role}" }
+                        }
+                    }
+                }
+            }
+            td { class: "text-sm",
+                if invite.redemptions.is_empty() {
+                    span { class: "text-base-content/40", "—" }
+                }
+                for r in invite.redemptions.iter() {
+                    div { title: "{r.redeemed_at}",
+                        "{r.username}"
+                        if r.user_id.is_none() {
+                            span { class: "text-base-content/40", " (deleted)" }
+                        }
+                    }
+                }
+            }
+            td { class: "text-right",
+                if invite.revoked_at.is_none() {
+                    button {
+                        class: "btn btn-ghost btn-xs text-error",
+                        onclick: move |_| confirming.set(true),
+                        "Revoke"
+                    }
+                }
+                if confirming() {
+                    dialog { class: "modal modal-open",
+                        div { class: "modal-box text-left whitespace-normal",
+                            h3 { class: "font-bold text-lg", "Revoke invite code …{hint}?" }
+                            p { class: "py-4",
+                                "Nobody can sign up with it any more. Accounts already created with it are unaffected."
+                            }
+                            div { class: "modal-action",
+                                button {
+                                    class: "btn btn-ghost",
+                                    onclick: move |_| confirming.set(false),
+                                    "Cancel"
+                                }
+                                button {
+                                    class: "btn btn-error",
+                                    onclick: move |_| {
+                                        confirming.set(false);
+                                        spawn(async move {
+                                            match admin_revoke_invite(invite_id).await {
+                                                Ok(()) => on_change.call(()),
+                                                Err(e) => on_error.call(e.to_string()),
+                                            }
+                                        });
+                                    },
+                                    "Revoke"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ── Audit tab ─────────────────────────────────────────────────────────────────
+
+/// Action prefixes offered in the filter dropdown; the auth service matches by prefix.
+const AUDIT_ACTIONS: &[(&str, &str)] = &[
+    ("", "All actions"),
+    ("auth.", "Logins and logouts"),
+    ("auth.login_failed", "Failed logins"),
+    ("auth.register", "Registrations"),
+    ("rbac.", "Role and permission changes"),
+    ("rbac.user_role.expire", "Expired role grants"),
+    ("user.", "Account changes"),
+    ("invite.", "Invite codes"),
+    ("access_token.", "Access tokens"),
+    ("device.", "Device logins"),
+    ("mfa.", "Two-factor resets"),
+    ("ark.", "Ark commands"),
+];
+
+#[component]
+fn AuditTab() -> Element {
+    let mut action = use_signal(String::new);
+    let mut actor = use_signal(String::new);
+    let mut target = use_signal(String::new);
+    let mut page = use_signal(|| 0u32);
+    let mut load_error: Signal<Option<String>> = use_signal(|| None);
+
+    let data = use_resource(move || {
+        let a = action();
+        let u = actor();
+        let t = target();
+        let p = page();
+        async move { admin_list_audit_events(p, PAGE_SIZE, a, u, t).await }
+    });
+
+    let (events, total) = match data.value()() {
+        None => {
+            return rsx! {
+                div { class: "flex justify-center p-12",
+                    span { class: "loading loading-spinner loading-lg" }
+                }
+            }
+        }
+        Some(Ok(r)) => {
+            load_error.set(None);
+            (r.items, r.total)
+        }
+        Some(Err(e)) => {
+            load_error.set(Some(e.to_string()));
+            (vec![], 0i64)
+        }
+    };
+
+    rsx! {
+        div { class: "space-y-3",
+            div { class: "flex flex-wrap items-center gap-3",
+                select {
+                    class: "select select-bordered select-sm",
+                    onchange: move |e: Event<FormData>| {
+                        action.set(e.value());
+                        page.set(0);
+                    },
+                    for (prefix, label) in AUDIT_ACTIONS {
+                        option { value: "{prefix}", selected: action() == *prefix, "{label}" }
+                    }
+                }
+                input {
+                    class: "input input-bordered input-sm w-full max-w-48",
+                    r#type: "text",
+                    placeholder: "Actor…",
+                    value: "{actor}",
+                    oninput: move |e| {
+                        actor.set(e.value());
+                        page.set(0);
+                    },
+                }
+                input {
+                    class: "input input-bordered input-sm w-full max-w-48",
+                    r#type: "text",
+                    placeholder: "Target…",
+                    value: "{target}",
+                    oninput: move |e| {
+                        target.set(e.value());
+                        page.set(0);
+                    },
+                }
+                span { class: "text-sm text-base-content/50 shrink-0", "{total} event(s)" }
+            }
+
+            if let Some(err) = load_error() {
+                div { class: "alert alert-error text-sm font-mono", "{err}" }
+            }
+
+            div { class: "overflow-x-auto",
+                table { class: "table table-sm w-full",
+                    thead {
+                        tr {
+                            th { "When" }
+                            th { "Actor" }
+                            th { "Action" }
+                            th { "Target" }
+                            th { "Change" }
+                            th { "IP" }
+                        }
+                    }
+                    tbody {
+                        for event in events {
+                            AuditRow { key: "{event.id}", event: event.clone() }
+                        }
+                    }
+                }
+            }
+
+            Pagination {
+                page: page(),
+                total,
+                limit: PAGE_SIZE,
+                on_page: move |p| page.set(p),
+            }
+        }
+    }
+}
+
+#[component]
+fn AuditRow(event: AuditEvent) -> Element {
+    // "2026-07-14T09:30:12.123Z" → "2026-07-14 09:30:12"
+    let when = event.occurred_at.get(..19).unwrap_or(&event.occurred_at).replace('T', " ");
+    let actor = match (&event.actor_username, event.actor_user_id) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => format!("#{id}"),
+        (None, None) => "—".to_string(),
+    };
+
+    rsx! {
+        tr {
+            td { class: "text-sm whitespace-nowrap", title: event.trace_id.clone().unwrap_or_default(), "{when}" }
+            td { class: "font-medium", "{actor}" }
+            td { span { class: "badge badge-ghost font-mono", "{event.action}" } }
+            td { class: "text-sm", { event.target.as_deref().unwrap_or("—") } }
+            td {
+                if event.before.is_some() || event.after.is_some() {
+                    details {
+                        summary { class: "cursor-pointer text-sm", "Details" }
+                        div { class: "grid grid-cols-2 gap-2 mt-1",
+                            pre { class: "text-xs bg-base-300 p-2 rounded", { event.before.as_deref().unwrap_or("—") } }
+                            pre { class: "text-xs bg-base-300 p-2 rounded", { event.after.as_deref().unwrap_or("—") } }
+                        }
+                    }
+                }
             }
+            td { class: "text-sm font-mono text-base-content/60", { event.client_ip.as_deref().unwrap_or("—") } }
         }
     }
 }
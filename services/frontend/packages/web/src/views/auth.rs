@@ -1,14 +1,24 @@
 use dioxus::prelude::*;
 
-use api::{get_my_permissions, login_password, register_password};
+use api::{
+    forgot_password, get_my_permissions, login_mfa, login_providers, login_password, passkey_login_finish,
+    passkey_login_start, register_password, registration_mode, registration_refusal, reset_password,
+    take_pending_authorization, verify_email,
+};
+use ui::data_dir::{LoginOutcome, LoginProvider, RegistrationMode};
+
+use super::passkey;
 
 use crate::{LOGIN_STATUS, PERMISSIONS};
+use ui::data_dir::LoginStatus;
 
 // ---- Login ----
 
 #[component]
 pub fn Login(error: String) -> Element {
     let mut login_error = use_signal(String::new);
+    let mut mfa_pending = use_signal(|| false);
+    let providers = use_resource(login_providers);
 
     let handle_login = move |evt: FormEvent| {
         evt.prevent_default();
@@ -17,19 +27,44 @@ pub fn Login(error: String) -> Element {
             let password = form_text(&evt, "password");
 
             match login_password(username, password).await {
-                Ok(status) => {
-                    *LOGIN_STATUS.write() = status;
-                    if let Ok(perms) = get_my_permissions().await {
-                        let map = perms.into_iter().map(|n| (n, true)).collect();
-                        *PERMISSIONS.write() = map;
-                    }
-                    navigator().push("/");
+                Ok(LoginOutcome::Complete(status)) => finish_login(status).await,
+                Ok(LoginOutcome::MfaRequired) => {
+                    login_error.set(String::new());
+                    mfa_pending.set(true);
                 }
                 Err(e) => login_error.set(e.to_string()),
             }
         });
     };
 
+    let handle_mfa = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            let code = form_text(&evt, "code");
+
+            match login_mfa(code).await {
+                Ok(status) => finish_login(status).await,
+                Err(e) => login_error.set(e.to_string()),
+            }
+        });
+    };
+
+    let handle_passkey = move |_| {
+        spawn(async move {
+            let result = async {
+                let options = passkey_login_start().await.map_err(|e| e.to_string())?;
+                let credential = passkey::get_credential(options).await?;
+                passkey_login_finish(credential).await.map_err(|e| e.to_string())
+            }
+            .await;
+
+            match result {
+                Ok(status) => finish_login(status).await,
+                Err(e) => login_error.set(e),
+            }
+        });
+    };
+
     rsx! {
         div { class: "h-[calc(100vh-5rem)] flex items-center justify-center flex-col",
             div { class: "bg-base-200 p-8 rounded-lg shadow-lg max-w-md w-full",
@@ -44,62 +79,71 @@ pub fn Login(error: String) -> Element {
                 // GET that reaches the BFF Axum handler. preventDefault stops the router's
                 // listener (it honors defaultPrevented), then we force a real navigation.
                 div { class: "space-y-4 flex flex-col mb-4",
-                    a {
-                        href: "/oauth/start/github",
-                        class: "btn bg-black text-white border-black",
-                        onclick: |evt: MouseEvent| {
-                            evt.prevent_default();
-                            spawn(async move {
-                                let _ = document::eval(
-                                    "window.location.href = '/oauth/start/github';",
-                                ).await;
-                            });
-                        },
-                        github_icon {}
-                        "Login with GitHub"
+                    if let Some(Ok(providers)) = providers() {
+                        for provider in providers {
+                            ProviderButton { key: "{provider.name}", provider }
+                        }
                     }
-                    a {
-                        href: "/oauth/start/google",
-                        class: "btn bg-white text-black border-[#e5e5e5]",
-                        onclick: |evt: MouseEvent| {
-                            evt.prevent_default();
-                            spawn(async move {
-                                let _ = document::eval(
-                                    "window.location.href = '/oauth/start/google';",
-                                ).await;
-                            });
-                        },
-                        google_icon {}
-                        "Login with Google"
+                    button {
+                        r#type: "button",
+                        class: "btn btn-outline",
+                        onclick: handle_passkey,
+                        passkey_icon {}
+                        "Sign in with a passkey"
                     }
                 }
 
                 div { class: "divider", "OR" }
 
-                // Password form
-                form { onsubmit: handle_login,
-                    div { class: "space-y-4",
-                        div {
-                            label { class: "sr-only", r#for: "username", "Username" }
-                            input {
-                                r#type: "text",
-                                name: "username",
-                                placeholder: "Username",
-                                class: "input input-bordered w-full",
-                                id: "username"
+                // Password form, swapped for the second-factor prompt once the password is accepted
+                if mfa_pending() {
+                    form { onsubmit: handle_mfa,
+                        div { class: "space-y-4",
+                            p { class: "text-sm text-base-content/70",
+                                "Enter the 6-digit code from your authenticator app, or one of your recovery codes."
+                            }
+                            div {
+                                label { class: "sr-only", r#for: "code", "Authentication code" }
+                                input {
+                                    r#type: "text",
+                                    name: "code",
+                                    placeholder: "123456",
+                                    autocomplete: "one-time-code",
+                                    class: "input input-bordered w-full",
+                                    id: "code"
+                                }
                             }
+                            button { r#type: "submit", class: "btn btn-primary w-full", "Verify" }
                         }
-                        div {
-                            label { class: "sr-only", r#for: "password", "Password" }
-                            input {
-                                r#type: "password",
-                                name: "password",
-                                placeholder: "Password",
-                                class: "input input-bordered w-full",
-                                id: "password"
+                    }
+                } else {
+                    form { onsubmit: handle_login,
+                        div { class: "space-y-4",
+                            div {
+                                label { class: "sr-only", r#for: "username", "Username" }
+                                input {
+                                    r#type: "text",
+                                    name: "username",
+                                    placeholder: "Username",
+                                    class: "input input-bordered w-full",
+                                    id: "username"
+                                }
+                            }
+                            div {
+                                label { class: "sr-only", r#for: "password", "Password" }
+                                input {
+                                    r#type: "password",
+                                    name: "password",
+                                    placeholder: "Password",
+                                    class: "input input-bordered w-full",
+                                    id: "password"
+                                }
+                            }
+                            button { r#type: "submit", class: "btn btn-primary w-full", "Log In" }
+                            div { class: "text-center",
+                                Link { class: "link link-hover text-sm", to: "/forgot-password", "Forgot password?" }
                             }
                         }
-                        button { r#type: "submit", class: "btn btn-primary w-full", "Log In" }
                     }
                 }
 
@@ -109,9 +153,30 @@ pub fn Login(error: String) -> Element {
                         span { "{login_error()}" }
                     }
                 }
+                if error.contains("username_taken") {
+                    div { class: "alert alert-warning mt-4",
+                        span {
+                            "An account with that username already exists. If it's yours, log in and link this provider from your profile."
+                        }
+                    }
+                }
+                if error.contains("account_disabled") {
+                    div { class: "alert alert-error mt-4",
+                        span { "This account has been disabled." }
+                    }
+                }
+                if ["registration_closed", "invite_required", "invalid_invite", "invite_email_mismatch"]
+                    .contains(&error.as_str())
+                {
+                    div { class: "alert alert-warning mt-4",
+                        span { "{registration_refusal(&error)}" }
+                    }
+                }
                 if error.contains("email_exists") {
                     div { class: "alert alert-warning mt-4",
-                        span { "An account with this email already exists. Try logging in instead." }
+                        span {
+                            "An account with this email already exists. Log in to it and link this provider from your profile."
+                        }
                     }
                 }
             }
@@ -119,11 +184,50 @@ pub fn Login(error: String) -> Element {
     }
 }
 
+#[component]
+fn ProviderButton(provider: LoginProvider) -> Element {
+    let href = format!("/oauth/start/{}", provider.name);
+    let class = match provider.name.as_str() {
+        "github" => "btn bg-black text-white border-black",
+        "google" => "btn bg-white text-black border-[#e5e5e5]",
+        "discord" => "btn bg-[#5865f2] text-white border-[#5865f2]",
+        _ => "btn btn-outline",
+    };
+    let target = href.clone();
+
+    rsx! {
+        a {
+            href: "{href}",
+            class,
+            onclick: move |evt: MouseEvent| {
+                evt.prevent_default();
+                let target = target.clone();
+                spawn(async move {
+                    let _ = document::eval(&format!("window.location.href = {target:?};")).await;
+                });
+            },
+            match provider.name.as_str() {
+                "github" => rsx! { github_icon {} },
+                "google" => rsx! { google_icon {} },
+                _ => rsx! {},
+            }
+            "Login with {provider.display_name}"
+        }
+    }
+}
+
 // ---- Register ----
 
 #[component]
 pub fn Register() -> Element {
     let mut reg_error = use_signal(String::new);
+    let mut invite = use_signal(String::new);
+    let mode = use_resource(registration_mode);
+    // Open if auth couldn't be asked; it enforces the mode either way.
+    let mode = match &*mode.read() {
+        Some(Ok(mode)) => *mode,
+        _ => RegistrationMode::Open,
+    };
 
     let handle_register = move |evt: FormEvent| {
         evt.prevent_default();
@@ -132,48 +236,76 @@ pub fn Register() -> Element {
             let email = form_text(&evt, "email");
             let password = form_text(&evt, "password");
 
-            match register_password(username, email, password).await {
-                Ok(status) => {
-                    *LOGIN_STATUS.write() = status;
-                    if let Ok(perms) = get_my_permissions().await {
-                        let map = perms.into_iter().map(|n| (n, true)).collect();
-                        *PERMISSIONS.write() = map;
-                    }
-                    navigator().push("/");
-                }
+            match register_password(username, email, password, invite()).await {
+                Ok(status) => finish_login(status).await,
                 Err(e) => reg_error.set(e.to_string()),
             }
         });
     };
 
+    // The code goes in the OAuth start link's query, so keep only what a code can contain.
+    let invite_query = move || {
+        let code: String = invite().chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+        if code.is_empty() { String::new() } else { format!("?invite={code}") }
+    };
+    let email_taken = reg_error().contains("Email already in use");
+    let username_taken = reg_error().contains("User already exists");
+
+    if mode == RegistrationMode::Closed {
+        return rsx! {
+            div { class: "h-[calc(100vh-5rem)] flex items-center justify-center",
+                div { class: "p-8 bg-base-200 shadow-lg rounded-lg max-w-md w-full text-center",
+                    h2 { class: "text-2xl font-bold mb-4", "Sign Up" }
+                    p { class: "mb-6", "Registration is currently closed." }
+                    Link { class: "btn btn-primary", to: "/login", "Log In" }
+                }
+            }
+        };
+    }
+
     rsx! {
         div { class: "h-[calc(100vh-5rem)] flex items-center justify-center",
             div { class: "p-8 bg-base-200 shadow-lg rounded-lg max-w-md w-full",
                 h2 { class: "text-center text-2xl font-bold mb-6", "Sign Up" }
 
+                div { class: "mb-6",
+                    div { class: "label",
+                        span { class: "label-text",
+                            if mode == RegistrationMode::InviteOnly { "Invite code" } else { "Invite code (optional)" }
+                        }
+                    }
+                    input {
+                        r#type: "text",
+                        name: "invite_code",
+                        placeholder: "XXXX-XXXX-XXXX",
+                        autocomplete: "off",
+                        class: "input input-bordered w-full font-mono",
+                        value: "{invite}",
+                        oninput: move |evt| invite.set(evt.value()),
+                    }
+                }
+
                 div { class: "flex flex-col space-y-4 mb-6",
                     a {
-                        href: "/oauth/start/github",
+                        href: "/oauth/start/github{invite_query()}",
                         class: "btn btn-outline btn-accent w-full",
-                        onclick: |evt: MouseEvent| {
+                        onclick: move |evt: MouseEvent| {
                             evt.prevent_default();
+                            let target = format!("/oauth/start/github{}", invite_query());
                             spawn(async move {
-                                let _ = document::eval(
-                                    "window.location.href = '/oauth/start/github';",
-                                ).await;
+                                let _ = document::eval(&format!("window.location.href = {target:?};")).await;
                             });
                         },
                         "Sign up with GitHub"
                     }
                     a {
-                        href: "/oauth/start/google",
+                        href: "/oauth/start/google{invite_query()}",
                         class: "btn btn-outline btn-accent w-full",
-                        onclick: |evt: MouseEvent| {
+                        onclick: move |evt: MouseEvent| {
                             evt.prevent_default();
+                            let target = format!("/oauth/start/google{}", invite_query());
                             spawn(async move {
-                                let _ = document::eval(
-                                    "window.location.href = '/oauth/start/google';",
-                                ).await;
+                                let _ = document::eval(&format!("window.location.href = {target:?};")).await;
                             });
                         },
                         "Sign up with Google"
@@ -186,7 +318,7 @@ pub fn Register() -> Element {
                     div { class: "mb-4",
                         div { class: "label",
                             span { class: "label-text", "Email" }
-                            if reg_error().contains("email") {
+                            if email_taken {
                                 span { class: "label-text-alt text-error", "Email already in use" }
                             }
                         }
@@ -194,13 +326,13 @@ pub fn Register() -> Element {
                             r#type: "email",
                             name: "email",
                             class: "input input-bordered w-full",
-                            class: if reg_error().contains("email") { "input-error" }
+                            class: if email_taken { "input-error" }
                         }
                     }
                     div { class: "mb-4",
                         div { class: "label",
                             span { class: "label-text", "Username" }
-                            if reg_error().contains("User") || reg_error().contains("username") {
+                            if username_taken {
                                 span { class: "label-text-alt text-error", "Username already in use" }
                             }
                         }
@@ -208,7 +340,7 @@ pub fn Register() -> Element {
                             r#type: "text",
                             name: "username",
                             class: "input input-bordered w-full",
-                            class: if reg_error().contains("User") { "input-error" }
+                            class: if username_taken { "input-error" }
                         }
                     }
                     div { class: "mb-6",
@@ -223,7 +355,7 @@ pub fn Register() -> Element {
                     button { r#type: "submit", class: "btn btn-primary w-full", "Sign up with Email" }
                 }
 
-                if !reg_error().is_empty() && !reg_error().contains("email") && !reg_error().contains("User") {
+                if !reg_error().is_empty() && !email_taken && !username_taken {
                     div { class: "alert alert-error mt-4",
                         span { "{reg_error()}" }
                     }
@@ -233,6 +365,186 @@ pub fn Register() -> Element {
     }
 }
 
+// ---- Password reset ----
+
+#[component]
+pub fn ForgotPassword() -> Element {
+    let mut sent = use_signal(|| false);
+    let mut error = use_signal(String::new);
+
+    let handle_submit = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            match forgot_password(form_text(&evt, "email")).await {
+                Ok(()) => {
+                    error.set(String::new());
+                    sent.set(true);
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "h-[calc(100vh-5rem)] flex items-center justify-center flex-col",
+            div { class: "bg-base-200 p-8 rounded-lg shadow-lg max-w-md w-full",
+                h2 { class: "text-2xl font-bold text-center mb-8", "Forgot Password" }
+                if sent() {
+                    div { class: "alert alert-success",
+                        span { "If an account uses that email, a reset link is on its way. The link is valid for one hour." }
+                    }
+                } else {
+                    form { onsubmit: handle_submit,
+                        div { class: "space-y-4",
+                            p { class: "text-sm text-base-content/70",
+                                "Enter the email address of your account and we'll send you a link to choose a new password."
+                            }
+                            input {
+                                r#type: "email",
+                                name: "email",
+                                placeholder: "Email",
+                                required: true,
+                                class: "input input-bordered w-full"
+                            }
+                            button { r#type: "submit", class: "btn btn-primary w-full", "Send reset link" }
+                        }
+                    }
+                }
+                if !error().is_empty() {
+                    div { class: "alert alert-error mt-4",
+                        span { "{error()}" }
+                    }
+                }
+                div { class: "text-center mt-4",
+                    Link { class: "link link-hover text-sm", to: "/login", "Back to log in" }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn ResetPassword(token: String) -> Element {
+    let mut done = use_signal(|| false);
+    let mut error = use_signal(String::new);
+
+    let handle_submit = move |evt: FormEvent| {
+        evt.prevent_default();
+        let token = token.clone();
+        spawn(async move {
+            let password = form_text(&evt, "password");
+            if password != form_text(&evt, "confirm") {
+                error.set("Passwords do not match".to_string());
+                return;
+            }
+            match reset_password(token, password).await {
+                Ok(()) => {
+                    error.set(String::new());
+                    done.set(true);
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "h-[calc(100vh-5rem)] flex items-center justify-center flex-col",
+            div { class: "bg-base-200 p-8 rounded-lg shadow-lg max-w-md w-full",
+                h2 { class: "text-2xl font-bold text-center mb-8", "Choose a New Password" }
+                if done() {
+                    div { class: "alert alert-success mb-4",
+                        span { "Your password has been changed and all other sessions were signed out." }
+                    }
+                    Link { class: "btn btn-primary w-full", to: "/login", "Log In" }
+                } else {
+                    form { onsubmit: handle_submit,
+                        div { class: "space-y-4",
+                            input {
+                                r#type: "password",
+                                name: "password",
+                                placeholder: "New password",
+                                autocomplete: "new-password",
+                                required: true,
+                                class: "input input-bordered w-full"
+                            }
+                            input {
+                                r#type: "password",
+                                name: "confirm",
+                                placeholder: "Confirm new password",
+                                autocomplete: "new-password",
+                                required: true,
+                                class: "input input-bordered w-full"
+                            }
+                            button { r#type: "submit", class: "btn btn-primary w-full", "Set password" }
+                        }
+                    }
+                }
+                if !error().is_empty() {
+                    div { class: "alert alert-error mt-4",
+                        span { "{error()}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ---- Email verification ----
+
+#[component]
+pub fn VerifyEmail(token: String) -> Element {
+    let result = use_resource(move || {
+        let token = token.clone();
+        async move {
+            verify_email(token).await?;
+            // Permissions may have been withheld until now.
+            if let Ok(perms) = get_my_permissions().await {
+                *PERMISSIONS.write() = perms.into_iter().map(|n| (n, true)).collect();
+            }
+            Ok::<(), ServerFnError>(())
+        }
+    });
+
+    rsx! {
+        div { class: "h-[calc(100vh-5rem)] flex items-center justify-center flex-col",
+            div { class: "bg-base-200 p-8 rounded-lg shadow-lg max-w-md w-full",
+                h2 { class: "text-2xl font-bold text-center mb-8", "Verify Email" }
+                match result.value()() {
+                    None => rsx! { span { class: "loading loading-spinner loading-md" } },
+                    Some(Ok(())) => rsx! {
+                        div { class: "alert alert-success mb-4",
+                            span { "Thanks, your email address is confirmed." }
+                        }
+                        Link { class: "btn btn-primary w-full", to: "/", "Continue" }
+                    },
+                    Some(Err(e)) => rsx! {
+                        div { class: "alert alert-error mb-4",
+                            span { "{e}" }
+                        }
+                        p { class: "text-sm text-base-content/70",
+                            "You can request a new link from your profile page."
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+async fn finish_login(status: LoginStatus) {
+    *LOGIN_STATUS.write() = status;
+    if let Ok(perms) = get_my_permissions().await {
+        let map = perms.into_iter().map(|n| (n, true)).collect();
+        *PERMISSIONS.write() = map;
+    }
+    // An app signing in through us sent the browser here; hand it back once logged in.
+    if let Ok(Some(url)) = take_pending_authorization().await {
+        let _ = document::eval(&format!("window.location.href = {url:?};")).await;
+        return;
+    }
+    navigator().push("/");
+}
+
 fn form_text(evt: &FormData, name: &str) -> String {
     match evt.get_first(name) {
         Some(FormValue::Text(s)) => s,
@@ -268,3 +580,23 @@ fn google_icon() -> Element {
         }
     }
 }
+
+#[component]
+fn passkey_icon() -> Element {
+    rsx! {
+        svg {
+            height: "16",
+            width: "16",
+            view_box: "0 0 24 24",
+            fill: "none",
+            stroke: "currentColor",
+            stroke_width: "2",
+            stroke_linecap: "round",
+            stroke_linejoin: "round",
+            xmlns: "http://www.w3.org/2000/svg",
+            circle { cx: "7.5", cy: "15.5", r: "5.5" }
+            path { d: "m21 2-9.6 9.6" }
+            path { d: "m15.5 7.5 3 3L22 7l-3-3" }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use dioxus::prelude::*;
+
+use api::{device_decide, device_lookup};
+use ui::data_dir::{DeviceRequest, LoginStatus};
+
+use crate::LOGIN_STATUS;
+
+/// Where the desktop and mobile apps send the user to approve their sign-in. `code` is the user
+/// code when the app links here with it filled in.
+#[component]
+pub fn Device(code: String) -> Element {
+    match LOGIN_STATUS() {
+        LoginStatus::LoggedOut => rsx! {
+            div { class: "flex h-screen items-center justify-center",
+                div {
+                    p { "Please log in, then open this page again to sign in your device." }
+                    Link { class: "btn btn-primary mt-4", to: "/login", "Log In" }
+                }
+            }
+        },
+        LoginStatus::LoggedIn(_) => rsx! { DeviceForm { code } },
+    }
+}
+
+#[component]
+fn DeviceForm(code: String) -> Element {
+    let mut user_code = use_signal(|| code.clone());
+    let mut request: Signal<Option<DeviceRequest>> = use_signal(|| None);
+    // Whether the request was approved, once answered.
+    let mut decided: Signal<Option<bool>> = use_signal(|| None);
+    let mut error = use_signal(String::new);
+
+    let handle_lookup = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            match device_lookup(user_code()).await {
+                Ok(r) => {
+                    error.set(String::new());
+                    request.set(Some(r));
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    let decide = move |approve: bool| {
+        let Some(r) = request() else { return };
+        spawn(async move {
+            match device_decide(r.user_code, approve).await {
+                Ok(()) => {
+                    error.set(String::new());
+                    decided.set(Some(approve));
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "h-[calc(100vh-5rem)] flex items-center justify-center flex-col",
+            div { class: "bg-base-200 p-8 rounded-lg shadow-lg max-w-md w-full",
+                h2 { class: "text-2xl font-bold text-center mb-8", "Sign in a device" }
+                match (decided(), request()) {
+                    (Some(true), _) => rsx! {
+                        div { class: "alert alert-success",
+                            span { "Your device is signed in. You can close this page and return to the app." }
+                        }
+                    },
+                    (Some(false), _) => rsx! {
+                        div { class: "alert alert-info",
+                            span { "The sign-in was denied. Nothing has access to your account." }
+                        }
+                    },
+                    (None, Some(r)) => rsx! {
+                        div { class: "space-y-4",
+                            p { class: "text-sm text-base-content/70",
+                                "Only approve if you started this sign-in yourself and the code matches the one your app shows."
+                            }
+                            table { class: "table table-sm",
+                                tbody {
+                                    tr { th { "App" } td { "{r.client_name}" } }
+                                    tr { th { "Code" } td { class: "font-mono", "{r.user_code}" } }
+                                    tr { th { "IP" } td { {r.client_ip.clone().unwrap_or_else(|| "Unknown".into())} } }
+                                    tr { th { "Device" } td { class: "text-xs", {r.user_agent.clone().unwrap_or_else(|| "Unknown".into())} } }
+                                }
+                            }
+                            p { class: "text-sm text-base-content/70",
+                                "The app will have the same access as you have here, until you sign it out from your profile's session list."
+                            }
+                            div { class: "flex gap-2",
+                                button { class: "btn btn-primary flex-1", onclick: move |_| decide(true), "Approve" }
+                                button { class: "btn btn-outline flex-1", onclick: move |_| decide(false), "Deny" }
+                            }
+                        }
+                    },
+                    (None, None) => rsx! {
+                        form { onsubmit: handle_lookup,
+                            div { class: "space-y-4",
+                                p { class: "text-sm text-base-content/70",
+                                    "Enter the code shown by the app you are signing in to."
+                                }
+                                input {
+                                    r#type: "text",
+                                    name: "user_code",
+                                    placeholder: "XXXX-XXXX",
+                                    autocomplete: "off",
+                                    required: true,
+                                    class: "input input-bordered w-full font-mono uppercase",
+                                    value: "{user_code}",
+                                    oninput: move |evt| user_code.set(evt.value()),
+                                }
+                                button { r#type: "submit", class: "btn btn-primary w-full", "Continue" }
+                            }
+                        }
+                    },
+                }
+                if !error().is_empty() {
+                    div { class: "alert alert-error mt-4",
+                        span { "{error()}" }
+                    }
+                }
+            }
+        }
+    }
+}
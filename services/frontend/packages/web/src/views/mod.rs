@@ -2,15 +2,18 @@ mod admin;
 mod arcane;
 mod ark;
 mod auth;
+mod device;
 mod landing;
 mod miles_countdown;
 mod page_404;
+mod passkey;
 mod profile;
 
 pub use admin::AdminPanel;
 pub use arcane::Arcane;
 pub use ark::Ark;
-pub use auth::{Login, Register};
+pub use auth::{ForgotPassword, Login, Register, ResetPassword, VerifyEmail};
+pub use device::Device;
 pub use landing::Landing;
 pub use miles_countdown::AssholeTimer;
 pub use page_404::NotFound;
@@ -0,0 +1,95 @@
+//! Browser half of the passkey ceremonies.
+//!
+//! Auth sends WebAuthn options with binary fields base64url-encoded, but
+//! `navigator.credentials` wants `ArrayBuffer`s and returns them too, so these helpers convert
+//! in both directions around the actual browser call.
+
+use dioxus::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+const CODEC_JS: &str = r#"
+const fromB64u = (s) => {
+    const b64 = s.replace(/-/g, "+").replace(/_/g, "/");
+    const padded = b64.padEnd(b64.length + ((4 - (b64.length % 4)) % 4), "=");
+    return Uint8Array.from(atob(padded), (c) => c.charCodeAt(0)).buffer;
+};
+const toB64u = (buf) =>
+    btoa(String.fromCharCode(...new Uint8Array(buf)))
+        .replace(/\+/g, "-")
+        .replace(/\//g, "_")
+        .replace(/=+$/, "");
+"#;
+
+const CREATE_JS: &str = r#"
+const options = await dioxus.recv();
+try {
+    const pk = options.publicKey;
+    pk.challenge = fromB64u(pk.challenge);
+    pk.user.id = fromB64u(pk.user.id);
+    (pk.excludeCredentials || []).forEach((c) => { c.id = fromB64u(c.id); });
+    const cred = await navigator.credentials.create({ publicKey: pk });
+    dioxus.send({ ok: {
+        id: cred.id,
+        rawId: toB64u(cred.rawId),
+        type: cred.type,
+        extensions: cred.getClientExtensionResults(),
+        response: {
+            attestationObject: toB64u(cred.response.attestationObject),
+            clientDataJSON: toB64u(cred.response.clientDataJSON),
+        },
+    } });
+} catch (e) {
+    dioxus.send({ error: e.message || String(e) });
+}
+"#;
+
+const GET_JS: &str = r#"
+const options = await dioxus.recv();
+try {
+    const pk = options.publicKey;
+    pk.challenge = fromB64u(pk.challenge);
+    (pk.allowCredentials || []).forEach((c) => { c.id = fromB64u(c.id); });
+    const cred = await navigator.credentials.get({ publicKey: pk });
+    dioxus.send({ ok: {
+        id: cred.id,
+        rawId: toB64u(cred.rawId),
+        type: cred.type,
+        extensions: cred.getClientExtensionResults(),
+        response: {
+            authenticatorData: toB64u(cred.response.authenticatorData),
+            clientDataJSON: toB64u(cred.response.clientDataJSON),
+            signature: toB64u(cred.response.signature),
+            userHandle: cred.response.userHandle ? toB64u(cred.response.userHandle) : null,
+        },
+    } });
+} catch (e) {
+    dioxus.send({ error: e.message || String(e) });
+}
+"#;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CeremonyResult {
+    Ok(Value),
+    Error(String),
+}
+
+async fn run(script: &str, options: Value) -> Result<Value, String> {
+    let mut eval = document::eval(&format!("{CODEC_JS}{script}"));
+    eval.send(options).map_err(|e| e.to_string())?;
+    match eval.recv::<CeremonyResult>().await.map_err(|e| e.to_string())? {
+        CeremonyResult::Ok(credential) => Ok(credential),
+        CeremonyResult::Error(e) => Err(e),
+    }
+}
+
+/// Runs `navigator.credentials.create()` with options from `passkey_register_start`.
+pub(super) async fn create_credential(options: Value) -> Result<Value, String> {
+    run(CREATE_JS, options).await
+}
+
+/// Runs `navigator.credentials.get()` with options from `passkey_login_start`.
+pub(super) async fn get_credential(options: Value) -> Result<Value, String> {
+    run(GET_JS, options).await
+}
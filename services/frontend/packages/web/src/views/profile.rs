@@ -1,12 +1,20 @@
 use dioxus::prelude::*;
 
+use api::{
+    access_token_create, access_token_list, access_token_revoke, email_status, identity_list, identity_unlink, login_providers, logout, logout_everywhere, mfa_confirm, mfa_disable, mfa_enroll, mfa_status,
+    passkey_delete, passkey_list, passkey_register_finish, passkey_register_start, passkey_rename,
+    resend_verification_email, session_list, session_revoke, session_revoke_others, MfaEnrollment,
+};
 use ui::{data_dir::Theme, default_profile_picture, get_mode, set_mode};
 
-use crate::LOGIN_STATUS;
+use crate::{LOGIN_STATUS, PERMISSIONS};
 use ui::data_dir::LoginStatus;
 
+use super::passkey;
+
+/// `link` carries the outcome of a "link another login" round trip through the provider.
 #[component]
-pub fn Profile() -> Element {
+pub fn Profile(link: String) -> Element {
     match LOGIN_STATUS() {
         LoginStatus::LoggedOut => rsx! {
             div { class: "flex h-screen items-center justify-center",
@@ -16,12 +24,12 @@ pub fn Profile() -> Element {
                 }
             }
         },
-        LoginStatus::LoggedIn(_) => rsx! { ProfileForm {} },
+        LoginStatus::LoggedIn(_) => rsx! { ProfileForm { link } },
     }
 }
 
 #[component]
-fn ProfileForm() -> Element {
+fn ProfileForm(link: String) -> Element {
     rsx! {
         div { class: "container mx-auto mt-10 px-4",
             div { class: "bg-base-200 p-10 rounded-lg shadow-lg max-w-4xl mx-auto",
@@ -82,6 +90,12 @@ fn ProfileForm() -> Element {
                 div { class: "flex justify-end mt-10",
                     button { class: "btn bg-purple-500 hover:bg-purple-700 text-white", "Update" }
                 }
+                EmailVerificationNotice {}
+                TwoFactorSection {}
+                PasskeysSection {}
+                LinkedLoginsSection { link }
+                SessionsSection {}
+                AccessTokensSection {}
                 div { class: "mt-10",
                     h2 { class: "text-xl font-bold mb-2", "Delete Account" }
                     div { class: "mb-4",
@@ -97,3 +111,805 @@ fn ProfileForm() -> Element {
         }
     }
 }
+
+// ── Email verification ────────────────────────────────────────────────────────
+
+/// Only renders while the account's email is unverified.
+#[component]
+fn EmailVerificationNotice() -> Element {
+    let status = use_resource(email_status);
+    let mut message = use_signal(String::new);
+
+    let resend = move |_| {
+        spawn(async move {
+            match resend_verification_email().await {
+                Ok(()) => message.set("A new link is on its way.".to_string()),
+                Err(e) => message.set(e.to_string()),
+            }
+        });
+    };
+
+    match status.value()() {
+        Some(Ok(s)) if !s.verified => {
+            let email = s.email.unwrap_or_default();
+            rsx! {
+                div { class: "alert alert-warning mt-10 flex flex-wrap",
+                    span {
+                        "Please confirm {email} using the link we emailed you. "
+                        "Some features stay locked until you do."
+                    }
+                    button { class: "btn btn-sm", onclick: resend, "Resend email" }
+                    if !message().is_empty() {
+                        span { class: "text-sm", "{message()}" }
+                    }
+                }
+            }
+        }
+        _ => rsx! {},
+    }
+}
+
+// ── Linked logins ─────────────────────────────────────────────────────────────
+
+#[component]
+fn LinkedLoginsSection(link: String) -> Element {
+    let mut refresh = use_signal(|| 0u32);
+    let mut error = use_signal(String::new);
+
+    let identities = use_resource(move || {
+        let _ = refresh();
+        async move { identity_list().await }
+    });
+    let providers = use_resource(login_providers);
+
+    let outcome = match link.as_str() {
+        "" => None,
+        "linked" => Some(("alert-success", "Login linked.")),
+        "identity_in_use" => Some((
+            "alert-warning",
+            "That account is already linked to a different user here.",
+        )),
+        _ => Some(("alert-error", "Linking failed. Please try again.")),
+    };
+
+    rsx! {
+        div { class: "mt-10",
+            h2 { class: "text-xl font-bold mb-2", "Linked logins" }
+            p { class: "mb-4 text-base-content/70",
+                "Accounts on other sites you can use to log in here."
+            }
+
+            if let Some((class, message)) = outcome {
+                div { class: "alert {class} mb-4",
+                    span { "{message}" }
+                }
+            }
+
+            match identities.value()() {
+                None => rsx! { span { class: "loading loading-spinner loading-sm" } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-error text-sm font-mono", "{e}" } },
+                Some(Ok(list)) if list.is_empty() => rsx! {
+                    p { class: "text-sm text-base-content/60 mb-4", "No other logins linked yet." }
+                },
+                Some(Ok(list)) => rsx! {
+                    table { class: "table mb-4",
+                        thead {
+                            tr {
+                                th { "Provider" }
+                                th { "Email" }
+                                th { "Linked" }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for identity in list {
+                                tr { key: "{identity.id}",
+                                    td { "{identity.display_name}" }
+                                    td { class: "text-sm", "{identity.email.as_deref().unwrap_or(\"—\")}" }
+                                    td { class: "text-sm", "{short_date(&identity.linked_at)}" }
+                                    td { class: "flex justify-end",
+                                        button {
+                                            class: "btn btn-xs bg-red-500 hover:bg-red-700 text-white",
+                                            onclick: move |_| {
+                                                spawn(async move {
+                                                    match identity_unlink(identity.id).await {
+                                                        Ok(()) => {
+                                                            error.set(String::new());
+                                                            *refresh.write() += 1;
+                                                        }
+                                                        Err(e) => error.set(e.to_string()),
+                                                    }
+                                                });
+                                            },
+                                            "Unlink"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+
+            // Real navigations, not router links: see the OAuth buttons on the Login view.
+            if let Some(Ok(providers)) = providers() {
+                div { class: "flex flex-wrap gap-2",
+                    for provider in providers {
+                        a {
+                            key: "{provider.name}",
+                            href: "/oauth/link/{provider.name}",
+                            class: "btn btn-outline",
+                            onclick: move |evt: MouseEvent| {
+                                evt.prevent_default();
+                                let target = format!("/oauth/link/{}", provider.name);
+                                spawn(async move {
+                                    let _ = document::eval(&format!("window.location.href = {target:?};")).await;
+                                });
+                            },
+                            "Link {provider.display_name}"
+                        }
+                    }
+                }
+            }
+
+            if !error().is_empty() {
+                div { class: "alert alert-error mt-4",
+                    span { "{error()}" }
+                }
+            }
+        }
+    }
+}
+
+// ── Sessions ──────────────────────────────────────────────────────────────────
+
+#[component]
+fn SessionsSection() -> Element {
+    let mut refresh = use_signal(|| 0u32);
+    let mut error = use_signal(String::new);
+
+    let sessions = use_resource(move || {
+        let _ = refresh();
+        async move { session_list().await }
+    });
+
+    let signed_out = move || {
+        *LOGIN_STATUS.write() = LoginStatus::LoggedOut;
+        *PERMISSIONS.write() = Default::default();
+    };
+
+    let sign_out_others = move |_| {
+        spawn(async move {
+            match session_revoke_others().await {
+                Ok(()) => {
+                    error.set(String::new());
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    let sign_out_everywhere = move |_| {
+        spawn(async move {
+            match logout_everywhere().await {
+                Ok(()) => signed_out(),
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "mt-10",
+            h2 { class: "text-xl font-bold mb-2", "Your sessions" }
+            p { class: "mb-4 text-base-content/70",
+                "Devices and browsers currently signed in to your account."
+            }
+
+            match sessions.value()() {
+                None => rsx! { span { class: "loading loading-spinner loading-sm" } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-error text-sm font-mono", "{e}" } },
+                Some(Ok(list)) => rsx! {
+                    table { class: "table mb-4",
+                        thead {
+                            tr {
+                                th { "Device" }
+                                th { "Signed in with" }
+                                th { "IP address" }
+                                th { "Signed in" }
+                                th { "Last active" }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for s in list {
+                                tr { key: "{s.id}",
+                                    td {
+                                        "{describe_user_agent(s.user_agent.as_deref())}"
+                                        if s.current {
+                                            span { class: "badge badge-primary badge-sm ml-2", "This device" }
+                                        }
+                                    }
+                                    td { class: "text-sm capitalize", "{s.login_method}" }
+                                    td { class: "text-sm font-mono", "{s.client_ip.as_deref().unwrap_or(\"Unknown\")}" }
+                                    td { class: "text-sm", "{short_date(&s.created_at)}" }
+                                    td { class: "text-sm",
+                                        "{s.last_used_at.as_deref().map(short_date).unwrap_or(\"Never\")}"
+                                    }
+                                    td { class: "flex justify-end",
+                                        button {
+                                            class: "btn btn-xs btn-ghost",
+                                            onclick: move |_| {
+                                                spawn(async move {
+                                                    if s.current {
+                                                        match logout().await {
+                                                            Ok(()) => signed_out(),
+                                                            Err(e) => error.set(e.to_string()),
+                                                        }
+                                                    } else {
+                                                        match session_revoke(s.id).await {
+                                                            Ok(()) => *refresh.write() += 1,
+                                                            Err(e) => error.set(e.to_string()),
+                                                        }
+                                                    }
+                                                });
+                                            },
+                                            "Sign out this device"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+
+            div { class: "flex gap-2",
+                button { class: "btn btn-outline", onclick: sign_out_others, "Sign out everywhere else" }
+                button { class: "btn btn-outline", onclick: sign_out_everywhere, "Sign out everywhere" }
+            }
+
+            if !error().is_empty() {
+                div { class: "alert alert-error mt-4",
+                    span { "{error()}" }
+                }
+            }
+        }
+    }
+}
+
+// ── Personal access tokens ────────────────────────────────────────────────────
+
+/// Lifetimes offered for a new access token, in days; `None` never expires.
+const TOKEN_EXPIRIES: &[(&str, Option<u32>)] = &[
+    ("30 days", Some(30)),
+    ("90 days", Some(90)),
+    ("1 year", Some(365)),
+    ("Never", None),
+];
+
+#[component]
+fn AccessTokensSection() -> Element {
+    let mut refresh = use_signal(|| 0u32);
+    let mut error = use_signal(String::new);
+    let mut name = use_signal(String::new);
+    let mut scopes: Signal<Vec<String>> = use_signal(Vec::new);
+    // An index into TOKEN_EXPIRIES.
+    let mut expiry = use_signal(|| 0usize);
+    let mut created: Signal<Option<String>> = use_signal(|| None);
+
+    let tokens = use_resource(move || {
+        let _ = refresh();
+        async move { access_token_list().await }
+    });
+
+    let mut available: Vec<String> = PERMISSIONS.read().keys().cloned().collect();
+    available.sort();
+
+    let create = move |evt: FormEvent| {
+        evt.prevent_default();
+        let expires_in_days = TOKEN_EXPIRIES.get(expiry()).and_then(|(_, days)| *days);
+        spawn(async move {
+            match access_token_create(name(), scopes(), expires_in_days).await {
+                Ok(token) => {
+                    created.set(Some(token));
+                    name.set(String::new());
+                    scopes.set(Vec::new());
+                    error.set(String::new());
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "mt-10",
+            h2 { class: "text-xl font-bold mb-2", "Personal access tokens" }
+            p { class: "mb-4 text-base-content/70",
+                "Tokens let scripts and bots use the API at "
+                code { "/api/v1" }
+                " as you, with "
+                code { "Authorization: Bearer <token>" }
+                ". A token can only do what its scopes allow, and only while you still have those permissions."
+            }
+
+            match tokens.value()() {
+                None => rsx! { span { class: "loading loading-spinner loading-sm" } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-error text-sm font-mono", "{e}" } },
+                Some(Ok(list)) if list.is_empty() => rsx! {
+                    p { class: "mb-4 text-sm text-base-content/60", "You have no access tokens." }
+                },
+                Some(Ok(list)) => rsx! {
+                    table { class: "table mb-4",
+                        thead {
+                            tr {
+                                th { "Name" }
+                                th { "Scopes" }
+                                th { "Created" }
+                                th { "Expires" }
+                                th { "Last used" }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for t in list {
+                                tr { key: "{t.id}",
+                                    td {
+                                        div { class: "font-medium", "{t.name}" }
+                                        div { class: "text-xs font-mono text-base-content/60", "{t.prefix}…" }
+                                    }
+                                    td {
+                                        div { class: "flex flex-wrap gap-1",
+                                            for scope in t.scopes.iter() {
+                                                span { class: "badge badge-ghost badge-sm font-mono", "{scope}" }
+                                            }
+                                        }
+                                    }
+                                    td { class: "text-sm", "{short_date(&t.created_at)}" }
+                                    td { class: "text-sm",
+                                        "{t.expires_at.as_deref().map(short_date).unwrap_or(\"Never\")}"
+                                    }
+                                    td { class: "text-sm",
+                                        "{t.last_used_at.as_deref().map(short_date).unwrap_or(\"Never\")}"
+                                    }
+                                    td { class: "flex justify-end",
+                                        button {
+                                            class: "btn btn-xs btn-ghost text-error",
+                                            onclick: move |_| {
+                                                spawn(async move {
+                                                    match access_token_revoke(t.id).await {
+                                                        Ok(()) => *refresh.write() += 1,
+                                                        Err(e) => error.set(e.to_string()),
+                                                    }
+                                                });
+                                            },
+                                            "Revoke"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+
+            if let Some(token) = created() {
+                div { class: "alert alert-success mb-4",
+                    div {
+                        p { "Your new token. Copy it now; it won't be shown again." }
+                        code { class: "font-mono break-all select-all", "{token}" }
+                    }
+                    button { class: "btn btn-ghost btn-xs", onclick: move |_| created.set(None), "✕" }
+                }
+            }
+
+            if available.is_empty() {
+                p { class: "text-sm text-base-content/60",
+                    "You have no permissions a token could use."
+                }
+            } else {
+                form { class: "space-y-3", onsubmit: create,
+                    div { class: "flex flex-wrap gap-2",
+                        input {
+                            r#type: "text",
+                            maxlength: "64",
+                            placeholder: "Token name, e.g. discord-bot",
+                            class: "input input-bordered w-full max-w-xs",
+                            value: "{name}",
+                            oninput: move |e| name.set(e.value()),
+                        }
+                        select {
+                            class: "select select-bordered",
+                            onchange: move |e: Event<FormData>| {
+                                if let Ok(i) = e.value().parse::<usize>() {
+                                    expiry.set(i);
+                                }
+                            },
+                            for (i, (label, _)) in TOKEN_EXPIRIES.iter().enumerate() {
+                                option { value: "{i}", selected: expiry() == i, "Expires: {label}" }
+                            }
+                        }
+                    }
+                    div { class: "flex flex-wrap items-center gap-3 text-sm",
+                        span { class: "text-base-content/60", "Scopes:" }
+                        for perm in available {
+                            {
+                                let scope = perm.clone();
+                                rsx! {
+                                    label { class: "flex items-center gap-1 cursor-pointer font-mono",
+                                        input {
+                                            class: "checkbox checkbox-xs",
+                                            r#type: "checkbox",
+                                            checked: scopes().contains(&perm),
+                                            onchange: move |_| {
+                                                let mut list = scopes.write();
+                                                match list.iter().position(|s| *s == scope) {
+                                                    Some(i) => { list.remove(i); }
+                                                    None => list.push(scope.clone()),
+                                                }
+                                            },
+                                        }
+                                        "{perm}"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-outline",
+                        r#type: "submit",
+                        disabled: name().trim().is_empty() || scopes().is_empty(),
+                        "Create token"
+                    }
+                }
+            }
+
+            if !error().is_empty() {
+                div { class: "alert alert-error mt-4",
+                    span { "{error()}" }
+                }
+            }
+        }
+    }
+}
+
+/// A rough "Browser on OS" label for a user agent string.
+fn describe_user_agent(ua: Option<&str>) -> String {
+    let Some(ua) = ua else {
+        return "Unknown device".to_string();
+    };
+    // Order matters: Edge and Opera also claim Chrome, and Chrome also claims Safari.
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .iter()
+    .find(|(needle, _)| ua.contains(needle))
+    .map(|(_, name)| *name);
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(needle, _)| ua.contains(needle))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(b), Some(o)) => format!("{b} on {o}"),
+        (Some(b), None) => b.to_string(),
+        (None, Some(o)) => o.to_string(),
+        (None, None) => "Unknown device".to_string(),
+    }
+}
+
+// ── Two-factor authentication ─────────────────────────────────────────────────
+
+#[component]
+fn TwoFactorSection() -> Element {
+    let mut refresh = use_signal(|| 0u32);
+    let mut enrollment: Signal<Option<MfaEnrollment>> = use_signal(|| None);
+    let mut recovery_codes: Signal<Vec<String>> = use_signal(Vec::new);
+    let mut error = use_signal(String::new);
+
+    let status = use_resource(move || {
+        let _ = refresh();
+        async move { mfa_status().await }
+    });
+
+    let start_enroll = move |_| {
+        spawn(async move {
+            match mfa_enroll().await {
+                Ok(e) => {
+                    error.set(String::new());
+                    enrollment.set(Some(e));
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    let confirm = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            match mfa_confirm(form_text(&evt, "code")).await {
+                Ok(codes) => {
+                    error.set(String::new());
+                    enrollment.set(None);
+                    recovery_codes.set(codes);
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    let disable = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            match mfa_disable(form_text(&evt, "code")).await {
+                Ok(()) => {
+                    error.set(String::new());
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "mt-10",
+            h2 { class: "text-xl font-bold mb-2", "Two-Factor Authentication" }
+
+            match status.value()() {
+                None => rsx! { span { class: "loading loading-spinner loading-sm" } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-error text-sm font-mono", "{e}" } },
+                Some(Ok(s)) if !s.available => rsx! {
+                    p { class: "text-base-content/70",
+                        "Two-factor authentication is available for accounts that sign in with a password."
+                    }
+                },
+                Some(Ok(_)) if !recovery_codes().is_empty() => rsx! {
+                    div { class: "alert alert-warning mb-4",
+                        span { "Save these recovery codes somewhere safe. Each works once, and they will not be shown again." }
+                    }
+                    div { class: "grid grid-cols-2 gap-2 font-mono mb-4 max-w-md",
+                        for code in recovery_codes() {
+                            span { class: "bg-base-300 rounded px-2 py-1", "{code}" }
+                        }
+                    }
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| recovery_codes.set(Vec::new()),
+                        "I have saved my codes"
+                    }
+                },
+                Some(Ok(_)) if enrollment().is_some() => {
+                    let e = enrollment().unwrap();
+                    rsx! {
+                        p { class: "mb-4", "Scan this QR code with your authenticator app, then enter the 6-digit code it shows." }
+                        img {
+                            class: "w-48 h-48 bg-white p-2 rounded mb-2",
+                            src: "data:image/png;base64,{e.qr_png_base64}",
+                            alt: "TOTP QR code",
+                        }
+                        p { class: "text-sm text-base-content/60 mb-4",
+                            "Can't scan it? Enter this key manually: "
+                            span { class: "font-mono", "{e.secret}" }
+                        }
+                        form { class: "flex gap-2", onsubmit: confirm,
+                            input {
+                                r#type: "text",
+                                name: "code",
+                                placeholder: "123456",
+                                autocomplete: "one-time-code",
+                                class: "input input-primary w-full max-w-xs"
+                            }
+                            button { r#type: "submit", class: "btn btn-primary", "Confirm" }
+                            button {
+                                r#type: "button",
+                                class: "btn btn-ghost",
+                                onclick: move |_| enrollment.set(None),
+                                "Cancel"
+                            }
+                        }
+                    }
+                },
+                Some(Ok(s)) if s.enabled => rsx! {
+                    p { class: "mb-4",
+                        span { class: "badge badge-success mr-2", "Enabled" }
+                        "{s.recovery_codes_remaining} recovery code(s) left."
+                    }
+                    form { class: "flex gap-2", onsubmit: disable,
+                        input {
+                            r#type: "text",
+                            name: "code",
+                            placeholder: "Code or recovery code",
+                            class: "input input-primary w-full max-w-xs"
+                        }
+                        button { r#type: "submit", class: "btn bg-red-500 hover:bg-red-700 text-white", "Disable" }
+                    }
+                },
+                Some(Ok(_)) => rsx! {
+                    p { class: "mb-4 text-base-content/70", "Protect your account with a code from an authenticator app." }
+                    button { class: "btn btn-primary", onclick: start_enroll, "Enable two-factor authentication" }
+                },
+            }
+
+            if !error().is_empty() {
+                div { class: "alert alert-error mt-4",
+                    span { "{error()}" }
+                }
+            }
+        }
+    }
+}
+
+// ── Passkeys ──────────────────────────────────────────────────────────────────
+
+#[component]
+fn PasskeysSection() -> Element {
+    let mut refresh = use_signal(|| 0u32);
+    let mut editing: Signal<Option<i64>> = use_signal(|| None);
+    let mut error = use_signal(String::new);
+
+    let passkeys = use_resource(move || {
+        let _ = refresh();
+        async move { passkey_list().await }
+    });
+
+    let add = move |evt: FormEvent| {
+        evt.prevent_default();
+        spawn(async move {
+            let name = form_text(&evt, "name");
+            let result = async {
+                let options = passkey_register_start().await.map_err(|e| e.to_string())?;
+                let credential = passkey::create_credential(options).await?;
+                passkey_register_finish(name, credential)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+
+            match result {
+                Ok(_) => {
+                    error.set(String::new());
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e),
+            }
+        });
+    };
+
+    let rename = move |evt: FormEvent| {
+        evt.prevent_default();
+        let Some(id) = editing() else { return };
+        spawn(async move {
+            match passkey_rename(id, form_text(&evt, "name")).await {
+                Ok(()) => {
+                    error.set(String::new());
+                    editing.set(None);
+                    *refresh.write() += 1;
+                }
+                Err(e) => error.set(e.to_string()),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "mt-10",
+            h2 { class: "text-xl font-bold mb-2", "Passkeys" }
+            p { class: "mb-4 text-base-content/70",
+                "Sign in with your device's fingerprint, face or screen lock instead of a password."
+            }
+
+            match passkeys.value()() {
+                None => rsx! { span { class: "loading loading-spinner loading-sm" } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-error text-sm font-mono", "{e}" } },
+                Some(Ok(list)) if list.is_empty() => rsx! {
+                    p { class: "text-sm text-base-content/60 mb-4", "No passkeys registered yet." }
+                },
+                Some(Ok(list)) => rsx! {
+                    table { class: "table mb-4",
+                        thead {
+                            tr {
+                                th { "Name" }
+                                th { "Added" }
+                                th { "Last used" }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for pk in list {
+                                tr { key: "{pk.id}",
+                                    if editing() == Some(pk.id) {
+                                        td { colspan: "4",
+                                            form { class: "flex gap-2", onsubmit: rename,
+                                                input {
+                                                    r#type: "text",
+                                                    name: "name",
+                                                    value: "{pk.name}",
+                                                    maxlength: "64",
+                                                    class: "input input-sm input-primary w-full max-w-xs"
+                                                }
+                                                button { r#type: "submit", class: "btn btn-sm btn-primary", "Save" }
+                                                button {
+                                                    r#type: "button",
+                                                    class: "btn btn-sm btn-ghost",
+                                                    onclick: move |_| editing.set(None),
+                                                    "Cancel"
+                                                }
+                                            }
+                                        }
+                                    } else {
+                                        td { "{pk.name}" }
+                                        td { class: "text-sm", "{short_date(&pk.created_at)}" }
+                                        td { class: "text-sm",
+                                            "{pk.last_used_at.as_deref().map(short_date).unwrap_or(\"Never\")}"
+                                        }
+                                        td { class: "flex gap-2 justify-end",
+                                            button {
+                                                class: "btn btn-xs btn-ghost",
+                                                onclick: move |_| editing.set(Some(pk.id)),
+                                                "Rename"
+                                            }
+                                            button {
+                                                class: "btn btn-xs bg-red-500 hover:bg-red-700 text-white",
+                                                onclick: move |_| {
+                                                    spawn(async move {
+                                                        match passkey_delete(pk.id).await {
+                                                            Ok(()) => *refresh.write() += 1,
+                                                            Err(e) => error.set(e.to_string()),
+                                                        }
+                                                    });
+                                                },
+                                                "Delete"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+
+            form { class: "flex gap-2", onsubmit: add,
+                input {
+                    r#type: "text",
+                    name: "name",
+                    placeholder: "Passkey name, e.g. Laptop",
+                    maxlength: "64",
+                    required: true,
+                    class: "input input-primary w-full max-w-xs"
+                }
+                button { r#type: "submit", class: "btn btn-primary", "Add a passkey" }
+            }
+
+            if !error().is_empty() {
+                div { class: "alert alert-error mt-4",
+                    span { "{error()}" }
+                }
+            }
+        }
+    }
+}
+
+/// `2026-07-02T10:11:12.345Z` → `2026-07-02`.
+fn short_date(ts: &str) -> &str {
+    ts.get(..10).unwrap_or(ts)
+}
+
+fn form_text(evt: &FormData, name: &str) -> String {
+    match evt.get_first(name) {
+        Some(FormValue::Text(s)) => s,
+        _ => String::new(),
+    }
+}